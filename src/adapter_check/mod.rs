@@ -0,0 +1,440 @@
+//! Prüfung des RS485 Adapters
+//!
+//! Antwortet kein Sensor, ist zunächst unklar ob der Adapter, die Verkabelung
+//! oder der Sensor die Ursache ist. Die Prüfung verwendet die Schnittstelle
+//! direkt, ohne libmodbus, und prüft nacheinander:
+//!
+//! 1. ob sich die Schnittstelle öffnen, schließen und erneut öffnen lässt
+//! 2. ob auf dem ruhenden Bus Bytes ankommen (Störungen oder ein anderer Master)
+//! 3. ob der Adapter die gesendeten Bytes zurück liefert (Echo)
+//! 4. ob der Sensor mit der gewählten Modbus Adresse antwortet
+//!
+//! Die Abfrage verwendet die Übertragungsart (RTU oder ASCII) der Sitzung.
+//! Das Ergebnis ist ein [`Report`] mit einer Zusammenfassung in Klartext.
+//! Wie der Sniffer benötigt die Prüfung die Schnittstelle allein.
+use crate::{
+    modbus_master::{
+        ascii, rtu::crc16, traffic::READ_INPUT_REGISTERS, LineSettings, ModbusMasterError,
+        TransmissionMode,
+    },
+    serial_interface::PortLock,
+    sniffer::{self, split_ascii_frames, split_frames, Frame, Pdu},
+};
+use futures::channel::oneshot;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    runtime::Runtime,
+    time,
+};
+use tokio_serial::Serial;
+
+// So lange wird auf dem ruhenden Bus gelauscht
+const IDLE_TIME: Duration = Duration::from_millis(1000);
+// So lange wird auf die Antwort des Sensors gewartet, auch nach dem Echo
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+// Abgefragt wird die Arbeitsweise (Rreg 1), wie beim Bus Scan
+const PROBE_REGISTER: u16 = 1;
+
+/// Ergebnis der Prüfung
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Report {
+    /// Geprüfte Schnittstelle
+    pub tty_path: String,
+    /// Schnittstellenparameter der Prüfung
+    pub line_settings: LineSettings,
+    /// Abgefragte Modbus Adresse
+    pub slave: u8,
+    /// Öffnen und Schließen, mit der Fehlermeldung der Schnittstelle
+    pub open: Result<(), String>,
+    /// Empfangene Bytes auf dem ruhenden Bus, `None` wenn nicht geprüft
+    pub noise: Option<Noise>,
+    /// Liefert der Adapter die gesendeten Bytes zurück? `None` wenn nicht geprüft
+    pub echo: Option<bool>,
+    /// Antwort des Sensors, `None` wenn nicht geprüft
+    pub probe: Option<Probe>,
+}
+
+/// Bytes die ohne Anfrage auf dem Bus empfangen wurden
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Noise {
+    /// Anzahl Bytes
+    pub bytes: usize,
+    /// Davon Frames mit korrekter CRC, stammen von einem anderen Master
+    pub frames: usize,
+}
+
+/// Antwort des Sensors auf die Abfrage
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Probe {
+    /// Der Sensor hat korrekt geantwortet
+    Answered,
+    /// Der Sensor hat mit einer Fehlerantwort (Exception Code) geantwortet
+    Exception(u8),
+    /// Geantwortet hat eine andere Modbus Adresse
+    OtherSlave(u8),
+    /// Es kamen Bytes an, aber kein gültiger Frame
+    Corrupt(usize),
+    /// Es kam nichts an
+    NoAnswer,
+    /// Die Anfrage konnte nicht gesendet werden
+    Failed(String),
+}
+
+/// Prüft den Adapter an der Schnittstelle `tty_path`
+///
+/// Die Prüfung läuft in einem eigenen Thread und dauert knapp zwei Sekunden.
+pub async fn run(tty_path: String, line_settings: LineSettings, slave: u8) -> Report {
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut rt = Runtime::new().expect("create tokio runtime");
+        let report = rt.block_on(check(tty_path, line_settings, slave));
+        let _ = tx.send(report);
+    });
+
+    rx.await.expect("adapter check thread")
+}
+
+async fn check(tty_path: String, line_settings: LineSettings, slave: u8) -> Report {
+    info!(
+        "Adapter check: tty_path: {}, line_settings: {}, slave: {}",
+        tty_path, line_settings, slave
+    );
+    let mut report = Report {
+        tty_path,
+        line_settings,
+        slave,
+        open: Ok(()),
+        noise: None,
+        echo: None,
+        probe: None,
+    };
+    let settings = sniffer::serial_port_settings(&line_settings);
+
+    let _port_lock = match PortLock::acquire(&report.tty_path) {
+        Ok(port_lock) => port_lock,
+        Err(holder) => {
+            let error = ModbusMasterError::PortBusy {
+                tty_path: report.tty_path.clone(),
+                holder,
+            };
+            report.open = Err(error.to_string());
+            return report;
+        }
+    };
+
+    // Öffnen, schließen und erneut öffnen, die Schnittstelle muss beim
+    // Schließen frei gegeben werden
+    let mut port = match Serial::from_path(&report.tty_path, &settings).and_then(|port| {
+        drop(port);
+        Serial::from_path(&report.tty_path, &settings)
+    }) {
+        Ok(port) => port,
+        Err(error) => {
+            report.open = Err(error.to_string());
+            return report;
+        }
+    };
+
+    let idle = match receive(&mut port, IDLE_TIME).await {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            report.probe = Some(Probe::Failed(error));
+            return report;
+        }
+    };
+    let mode = line_settings.transmission_mode;
+    report.noise = Some(analyse_noise(mode, &idle));
+
+    let request = probe_request(mode, slave);
+    if let Err(error) = port.write_all(&request).await {
+        report.probe = Some(Probe::Failed(error.to_string()));
+        return report;
+    }
+    match receive(&mut port, RESPONSE_TIMEOUT).await {
+        Ok(received) => {
+            let (echo, probe) = analyse_response(mode, slave, &request, &received);
+            report.echo = Some(echo);
+            report.probe = Some(probe);
+        }
+        Err(error) => report.probe = Some(Probe::Failed(error)),
+    }
+    debug!("Adapter check: {:?}", report);
+
+    report
+}
+
+// Empfängt alle Bytes bis `timeout` abgelaufen ist
+//
+// Ein Adapter mit Echo liefert zuerst die Anfrage, die Antwort folgt erst nach
+// der Antwortzeit des Sensors. Deshalb wird nicht an der ersten Pause beendet.
+async fn receive(port: &mut Serial, timeout: Duration) -> Result<Vec<u8>, String> {
+    let deadline = Instant::now() + timeout;
+    let mut received = vec![];
+    let mut buf = [0u8; 256];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_secs(0) {
+            return Ok(received);
+        }
+        match time::timeout(remaining, port.read(&mut buf)).await {
+            Ok(Ok(0)) => return Err("Schnittstelle geschlossen".to_string()),
+            Ok(Ok(n)) => received.extend_from_slice(&buf[..n]),
+            Ok(Err(error)) => return Err(error.to_string()),
+            Err(_) => return Ok(received),
+        }
+    }
+}
+
+// Anfrage Read Input Registers für das Register der Arbeitsweise
+fn probe_request(mode: TransmissionMode, slave: u8) -> Vec<u8> {
+    let mut pdu = vec![READ_INPUT_REGISTERS];
+    pdu.extend_from_slice(&PROBE_REGISTER.to_be_bytes());
+    pdu.extend_from_slice(&1u16.to_be_bytes());
+    match mode {
+        TransmissionMode::Rtu => {
+            let mut frame = vec![slave];
+            frame.extend_from_slice(&pdu);
+            let crc = crc16(&frame);
+            frame.extend_from_slice(&crc.to_le_bytes());
+            frame
+        }
+        TransmissionMode::Ascii => ascii::encode(slave, &pdu),
+    }
+}
+
+// Zerlegt die empfangenen Bytes je nach Übertragungsart in Frames
+fn split(mode: TransmissionMode, bytes: &[u8]) -> Vec<Frame> {
+    match mode {
+        TransmissionMode::Rtu => split_frames(bytes),
+        TransmissionMode::Ascii => split_ascii_frames(bytes),
+    }
+}
+
+// Wertet die Bytes auf dem ruhenden Bus aus
+fn analyse_noise(mode: TransmissionMode, bytes: &[u8]) -> Noise {
+    Noise {
+        bytes: bytes.len(),
+        frames: split(mode, bytes)
+            .iter()
+            .filter(|frame| frame.crc_ok)
+            .count(),
+    }
+}
+
+// Trennt das Echo von der Antwort und wertet die Antwort aus
+//
+// Liefert ob der Adapter ein Echo gesendet hat und die Antwort des Sensors.
+fn analyse_response(
+    mode: TransmissionMode,
+    slave: u8,
+    request: &[u8],
+    received: &[u8],
+) -> (bool, Probe) {
+    let echo = received.starts_with(request);
+    let response = if echo {
+        &received[request.len()..]
+    } else {
+        received
+    };
+    if response.is_empty() {
+        return (echo, Probe::NoAnswer);
+    }
+
+    let frames = split(mode, response);
+    let valid = || frames.iter().filter(|frame| frame.crc_ok);
+    let probe = match valid().find(|frame| frame.slave() == slave) {
+        Some(frame) => match frame.pdu() {
+            Some(Pdu::Exception { code, .. }) => Probe::Exception(code),
+            _ => Probe::Answered,
+        },
+        None => match valid().next() {
+            Some(frame) => Probe::OtherSlave(frame.slave()),
+            None => Probe::Corrupt(response.len()),
+        },
+    };
+
+    (echo, probe)
+}
+
+impl Report {
+    /// Hat der Sensor geantwortet, auch mit einer Fehlerantwort?
+    pub fn answered(&self) -> bool {
+        matches!(
+            self.probe,
+            Some(Probe::Answered) | Some(Probe::Exception(_))
+        )
+    }
+
+    /// Zusammenfassung, wo der Fehler vermutlich liegt
+    pub fn conclusion(&self) -> String {
+        let answered = self.answered();
+        let noisy = self.noise.map(|noise| noise.bytes > 0).unwrap_or(false);
+        let text = match (&self.open, &self.probe) {
+            (Err(_), _) => {
+                "Die Schnittstelle lässt sich nicht verwenden. Ist der Adapter eingesteckt, \
+                 der Treiber geladen und die Schnittstelle frei? Unter Linux muss der \
+                 Benutzer Mitglied der Gruppe 'dialout' sein."
+            }
+            (_, Some(Probe::Failed(_))) => {
+                "Der Adapter lässt sich öffnen, aber nicht verwenden. Adapter abziehen, \
+                 wieder einstecken und die Prüfung wiederholen."
+            }
+            _ if answered && self.echo == Some(true) => {
+                "Adapter, Verkabelung und Sensor arbeiten, der Adapter sendet aber ein Echo. \
+                 Das Echo abschalten (Jumper bzw. Konfiguration des Adapters) oder einen \
+                 Adapter mit automatischer Richtungsumschaltung verwenden."
+            }
+            _ if answered => "Adapter, Verkabelung und Sensor arbeiten.",
+            (_, Some(Probe::OtherSlave(other))) => {
+                return format!(
+                    "Adapter und Verkabelung arbeiten, es antwortet aber Modbus Adresse {} \
+                     statt {}. Ist die Modbus Adresse doppelt vergeben?",
+                    other, self.slave
+                )
+            }
+            (_, Some(Probe::Corrupt(_))) => {
+                "Es kommt eine Antwort an, sie ist aber unlesbar. Passen Baudrate und \
+                 Parität (Menü 'Baudrate erkennen ...')? Sonst Störungen auf der Leitung: \
+                 Abschlusswiderstände, Schirmung und Masse prüfen."
+            }
+            _ if noisy => {
+                "Der Sensor antwortet nicht und auf dem ruhenden Bus liegen Störungen oder \
+                 ein anderer Master. Verkabelung, Abschlusswiderstände und Bias (Ruhepegel) \
+                 prüfen, andere Master abschalten."
+            }
+            _ => {
+                "Der Adapter arbeitet, der Sensor antwortet aber nicht. Modbus Adresse, \
+                 Baudrate, Versorgung des Sensors und Verdrahtung (A und B vertauscht?) \
+                 prüfen."
+            }
+        };
+
+        text.to_string()
+    }
+}
+
+/// Bericht in Klartext, eine Zeile je Prüfung und die Zusammenfassung
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Prüfung {} ({}), Modbus Adresse {}",
+            self.tty_path, self.line_settings, self.slave
+        )?;
+        writeln!(f)?;
+        match &self.open {
+            Ok(()) => writeln!(f, "✔ Schnittstelle lässt sich öffnen und schließen")?,
+            Err(error) => writeln!(f, "✘ Schnittstelle lässt sich nicht öffnen: {}", error)?,
+        }
+        match self.noise {
+            Some(Noise { bytes: 0, .. }) => writeln!(f, "✔ Ruhender Bus ohne Störungen")?,
+            Some(Noise { bytes, frames: 0 }) => writeln!(
+                f,
+                "⚠ Auf dem ruhenden Bus kamen {} Bytes Störungen an",
+                bytes
+            )?,
+            Some(Noise { bytes, frames }) => writeln!(
+                f,
+                "⚠ Auf dem ruhenden Bus kamen {} Bytes an, darin {} gültige Frames eines anderen Masters",
+                bytes, frames
+            )?,
+            None => {}
+        }
+        match self.echo {
+            Some(false) => writeln!(f, "✔ Adapter sendet kein Echo")?,
+            Some(true) => writeln!(f, "⚠ Adapter sendet die gesendeten Bytes zurück (Echo)")?,
+            None => {}
+        }
+        match &self.probe {
+            Some(Probe::Answered) => writeln!(f, "✔ Sensor antwortet")?,
+            Some(Probe::Exception(code)) => writeln!(
+                f,
+                "✔ Sensor antwortet mit einer Fehlerantwort (Exception Code {})",
+                code
+            )?,
+            Some(Probe::OtherSlave(other)) => {
+                writeln!(f, "⚠ Es antwortet Modbus Adresse {}", other)?
+            }
+            Some(Probe::Corrupt(bytes)) => {
+                writeln!(f, "✘ Antwort mit {} Bytes ist unlesbar (CRC falsch)", bytes)?
+            }
+            Some(Probe::NoAnswer) => writeln!(f, "✘ Sensor antwortet nicht")?,
+            Some(Probe::Failed(error)) => {
+                writeln!(f, "✘ Schnittstelle lässt sich nicht verwenden: {}", error)?
+            }
+            None => {}
+        }
+        writeln!(f)?;
+        write!(f, "{}", self.conclusion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(slave: u8) -> Vec<u8> {
+        let mut frame = vec![slave, READ_INPUT_REGISTERS, 2, 0x00, 0x05];
+        let crc = crc16(&frame);
+        frame.extend_from_slice(&crc.to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn response_with_and_without_echo() {
+        let rtu = TransmissionMode::Rtu;
+        let request = probe_request(rtu, 12);
+        let mut received = request.clone();
+        received.extend(response(12));
+        assert_eq!(
+            analyse_response(rtu, 12, &request, &received),
+            (true, Probe::Answered)
+        );
+        assert_eq!(
+            analyse_response(rtu, 12, &request, &response(13)),
+            (false, Probe::OtherSlave(13))
+        );
+        assert_eq!(
+            analyse_response(rtu, 12, &request, &request),
+            (true, Probe::NoAnswer)
+        );
+        assert_eq!(
+            analyse_response(rtu, 12, &request, &[0xff, 0x00, 0xfe]),
+            (false, Probe::Corrupt(3))
+        );
+    }
+
+    #[test]
+    fn ascii_response() {
+        let ascii = TransmissionMode::Ascii;
+        let request = probe_request(ascii, 12);
+        assert_eq!(request, b":0C0400010001EE\r\n".to_vec());
+        let answer = ascii::encode(12, &[READ_INPUT_REGISTERS, 2, 0x00, 0x05]);
+        assert_eq!(
+            analyse_response(ascii, 12, &request, &answer),
+            (false, Probe::Answered)
+        );
+        // Ein RTU Frame ist mit ASCII unlesbar
+        assert_eq!(
+            analyse_response(ascii, 12, &request, &response(12)),
+            (false, Probe::Corrupt(7))
+        );
+    }
+
+    #[test]
+    fn noise_and_foreign_frames() {
+        let mut bytes = response(3);
+        assert_eq!(
+            analyse_noise(TransmissionMode::Rtu, &bytes),
+            Noise {
+                bytes: 7,
+                frames: 1
+            }
+        );
+        bytes.extend(&[0x00, 0xff]);
+        assert_eq!(analyse_noise(TransmissionMode::Rtu, &bytes).frames, 1);
+        assert_eq!(analyse_noise(TransmissionMode::Rtu, &[]), Noise::default());
+    }
+}
@@ -0,0 +1,23 @@
+use crate::adapter_check::Report;
+use gtk::prelude::*;
+
+/// Zeigt das Ergebnis der Adapter Prüfung
+///
+/// Hat der Sensor nicht geantwortet wird der Bericht als Warnung angezeigt.
+pub fn show_adapter_report(parent: &gtk::ApplicationWindow, report: &Report) {
+    let message_type = if report.answered() {
+        gtk::MessageType::Info
+    } else {
+        gtk::MessageType::Warning
+    };
+    let dialog = gtk::MessageDialog::new(
+        Some(parent),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        message_type,
+        gtk::ButtonsType::Ok,
+        &report.to_string(),
+    );
+    dialog.set_title("Adapter prüfen");
+    dialog.run();
+    dialog.close();
+}
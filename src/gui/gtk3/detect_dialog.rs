@@ -0,0 +1,94 @@
+use crate::gui::gtk3::GuiMessage;
+use futures::channel::mpsc::Sender;
+use glib::clone;
+use gtk::prelude::*;
+
+/// Fortschritt der Erkennung von Baudrate und Parität
+///
+/// Wie der `ScanDialog` im Code erstellt. Der Abbruch wird als `GuiMessage` an
+/// die GUI gesendet, die ihn an den Modbus Master weiterleitet.
+#[derive(Debug)]
+pub struct DetectDialog {
+    dialog: gtk::Dialog,
+    label: gtk::Label,
+    progress_bar: gtk::ProgressBar,
+    button_cancel: gtk::Widget,
+}
+
+impl DetectDialog {
+    /// Erstellt den Dialog
+    pub fn new(parent: &gtk::ApplicationWindow, gui_tx: &Sender<GuiMessage>) -> Self {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Baudrate erkennen"),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Abbrechen", gtk::ResponseType::Cancel)],
+        );
+        dialog.set_default_size(400, -1);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+
+        let label = gtk::Label::new(None);
+        content_area.pack_start(&label, false, true, 0);
+
+        let progress_bar = gtk::ProgressBar::new();
+        progress_bar.set_show_text(true);
+        content_area.pack_start(&progress_bar, false, true, 0);
+
+        let button_cancel = dialog
+            .get_widget_for_response(gtk::ResponseType::Cancel)
+            .expect("Button Abbrechen fehlt");
+
+        // Callback: Button "Abbrechen"
+        dialog.connect_response(clone!(
+            @strong gui_tx
+            => move |dialog, _response| {
+                if let Some(button) = dialog.get_widget_for_response(gtk::ResponseType::Cancel) {
+                    button.set_sensitive(false);
+                }
+                let _ = gui_tx.clone().try_send(GuiMessage::ModbusMasterCancelDetect);
+            }
+        ));
+
+        // Schließen bricht die Erkennung ab, versteckt wird der Dialog erst
+        // wenn der Modbus Master das Ende meldet
+        dialog.connect_delete_event(clone!(
+            @strong gui_tx
+            => move |_dialog, _event| {
+                let _ = gui_tx.clone().try_send(GuiMessage::ModbusMasterCancelDetect);
+                gtk::Inhibit(true)
+            }
+        ));
+
+        DetectDialog {
+            dialog,
+            label,
+            progress_bar,
+            button_cancel,
+        }
+    }
+
+    /// Zeigt den Dialog bei Start der Erkennung an
+    pub fn started(&self, text: &str) {
+        self.label.set_text(text);
+        self.progress_bar.set_fraction(0.0);
+        self.progress_bar.set_text(None);
+        self.button_cancel.set_sensitive(true);
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Aktualisiert den Fortschritt
+    pub fn update_progress(&self, done: usize, total: usize) {
+        if total > 0 {
+            self.progress_bar.set_fraction(done as f64 / total as f64);
+        }
+        self.progress_bar
+            .set_text(Some(&format!("{} / {} Versuche", done, total)));
+    }
+
+    /// Versteckt den Dialog nach Ende der Erkennung
+    pub fn finished(&self) {
+        self.dialog.hide();
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    gui::gtk3::{
+        traffic_dialog::{choose_export_file, write_port},
+        GuiMessage,
+    },
+    modbus_master::{statistics, SlaveStatistics, Statistics},
+};
+use futures::channel::mpsc::Sender;
+use glib::clone;
+use gtk::prelude::*;
+use std::{cell::RefCell, fs::File, rc::Rc, time::Duration};
+
+/// Dialog mit der Statistik der Kommunikation pro Slave
+///
+/// Zeigt für jeden angesprochenen Slave Anfragen, Fehler nach Art und die
+/// Laufzeiten der Sitzung an. Die Zähler können zurückgesetzt und als CSV
+/// Bericht exportiert werden.
+#[derive(Debug)]
+pub struct DiagnosticsDialog {
+    dialog: gtk::Dialog,
+    store: gtk::ListStore,
+    label_summary: gtk::Label,
+    statistics: Rc<RefCell<Statistics>>,
+    // Schnittstelle für den CSV Export
+    port: Rc<RefCell<Option<String>>>,
+}
+
+impl DiagnosticsDialog {
+    /// Erstellt den Dialog
+    pub fn new(parent: &gtk::ApplicationWindow, gui_tx: &Sender<GuiMessage>) -> Self {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Diagnose"),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Schließen", gtk::ResponseType::Close)],
+        );
+        dialog.set_default_size(800, 300);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+
+        // Zurücksetzen, Export
+        let hbox_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let button_reset = gtk::Button::with_label("Zurücksetzen");
+        let button_export = gtk::Button::with_label("Bericht exportieren ...");
+        hbox_buttons.pack_start(&button_reset, false, true, 0);
+        hbox_buttons.pack_end(&button_export, false, true, 0);
+        content_area.pack_start(&hbox_buttons, false, true, 0);
+
+        let label_summary = gtk::Label::new(None);
+        label_summary.set_xalign(0.0);
+        content_area.pack_start(&label_summary, false, true, 0);
+
+        // Zähler pro Slave
+        let titles = [
+            "Slave",
+            "Anfragen",
+            "Erfolgreich",
+            "Timeouts",
+            "CRC Fehler",
+            "Exceptions",
+            "Andere Fehler",
+            "Laufzeit min [ms]",
+            "Laufzeit Ø [ms]",
+            "Laufzeit max [ms]",
+        ];
+        let store = gtk::ListStore::new(&[glib::Type::String; 10]);
+        let treeview = gtk::TreeView::with_model(&store);
+        treeview.set_grid_lines(gtk::TreeViewGridLines::Horizontal);
+        for (i, title) in titles.iter().enumerate() {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            treeview.append_column(&column);
+        }
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.add(&treeview);
+        content_area.pack_start(&scrolled_window, true, true, 0);
+
+        let statistics = Rc::new(RefCell::new(Statistics::default()));
+        let port = Rc::new(RefCell::new(None));
+
+        //
+        // Callbacks
+        //
+
+        // Callback: Button "Zurücksetzen"
+        button_reset.connect_clicked(clone!(
+            @strong gui_tx
+            => move |_| {
+                let _ = gui_tx.clone().try_send(GuiMessage::ResetStatistics);
+            }
+        ));
+
+        // Callback: Button "Bericht exportieren"
+        button_export.connect_clicked(clone!(
+            @strong dialog,
+            @strong statistics,
+            @strong port,
+            @strong label_summary
+            => move |_| {
+                if let Some(path) = choose_export_file(&dialog, "Bericht exportieren", "diagnose.csv") {
+                    let result = File::create(&path).and_then(|mut file| {
+                        write_port(port.borrow().as_deref(), &mut file)?;
+                        statistics::write_csv(&statistics.borrow(), file)
+                    });
+                    match result {
+                        Ok(_) => label_summary.set_text(&format!("Bericht nach {} exportiert", path.display())),
+                        Err(error) => label_summary.set_text(&format!("Export fehlgeschlagen: {}", error)),
+                    }
+                }
+            }
+        ));
+
+        dialog.connect_response(|dialog, _response| dialog.hide());
+        dialog.connect_delete_event(|dialog, _event| {
+            dialog.hide();
+            gtk::Inhibit(true)
+        });
+
+        DiagnosticsDialog {
+            dialog,
+            store,
+            label_summary,
+            statistics,
+            port,
+        }
+    }
+
+    /// Zeigt den Dialog an
+    pub fn show(&self) {
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Übernimmt die gewählte Schnittstelle für den CSV Export
+    pub fn set_port(&self, port: Option<String>) {
+        *self.port.borrow_mut() = port;
+    }
+
+    /// Zeigt die neue Statistik an
+    pub fn update(&self, statistics: Statistics) {
+        self.store.clear();
+        for (slave, slave_statistics) in statistics.slaves() {
+            self.insert_row(&slave.to_string(), slave_statistics);
+        }
+        if !statistics.is_empty() {
+            self.insert_row("Summe", &statistics.total());
+        }
+        self.label_summary.set_text(&statistics.to_string());
+        *self.statistics.borrow_mut() = statistics;
+    }
+
+    // Fügt eine Zeile für einen Slave bzw. die Summe ein
+    fn insert_row(&self, slave: &str, statistics: &SlaveStatistics) {
+        let millis = |latency: Option<Duration>| {
+            latency
+                .map(|latency| latency.as_millis().to_string())
+                .unwrap_or_default()
+        };
+        self.store.insert_with_values(
+            None,
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+            &[
+                &slave,
+                &statistics.requests.to_string(),
+                &statistics.successes.to_string(),
+                &statistics.timeouts.to_string(),
+                &statistics.crc_errors.to_string(),
+                &statistics.exceptions.to_string(),
+                &statistics.other_errors.to_string(),
+                &millis(statistics.min_latency),
+                &millis(statistics.avg_latency()),
+                &millis(statistics.max_latency),
+            ],
+        );
+    }
+}
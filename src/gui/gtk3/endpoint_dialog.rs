@@ -0,0 +1,51 @@
+use crate::modbus_master::Endpoint;
+use gtk::prelude::*;
+
+/// Fragt nach einer weiteren Schnittstelle
+///
+/// Möglich sind Pseudo-Terminals, z.B. eines Simulators, und Netzwerk Gateways.
+/// Die Eingabe wird geprüft, bei Fehlern bleibt der Dialog offen.
+/// Liefert `None` wenn der Benutzer abbricht.
+pub fn ask_endpoint(parent: &gtk::ApplicationWindow) -> Option<Endpoint> {
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Schnittstelle hinzufügen"),
+        Some(parent),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Abbrechen", gtk::ResponseType::Cancel),
+            ("Hinzufügen", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+
+    let label = gtk::Label::new(Some(
+        "Pseudo-Terminal oder Netzwerk Gateway, z.B.\n\n/dev/pts/3\ntcp://192.168.1.10:502\nrtu+tcp://gateway:4001",
+    ));
+    label.set_xalign(0.0);
+    content_area.pack_start(&label, false, true, 0);
+
+    let entry = gtk::Entry::new();
+    entry.set_activates_default(true);
+    content_area.pack_start(&entry, false, true, 0);
+
+    let label_error = gtk::Label::new(None);
+    label_error.set_xalign(0.0);
+    label_error.set_line_wrap(true);
+    content_area.pack_start(&label_error, false, true, 0);
+
+    dialog.show_all();
+    let endpoint = loop {
+        if dialog.run() != gtk::ResponseType::Accept {
+            break None;
+        }
+        match Endpoint::parse(&entry.get_text()) {
+            Ok(endpoint) => break Some(endpoint),
+            Err(error) => label_error.set_text(&error.to_string()),
+        }
+    };
+    dialog.close();
+
+    endpoint
+}
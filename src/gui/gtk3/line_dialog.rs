@@ -0,0 +1,145 @@
+use crate::{gui::gtk3::GuiMessage, registers::Rreg};
+use futures::channel::mpsc::Sender;
+use glib::clone;
+use gtk::prelude::*;
+use std::{cell::RefCell, collections::HashMap};
+
+/// Dialog der Live Ansicht aller Sensoren eines Busses
+///
+/// Für jeden Sensor gibt es eine Zeile mit Modbus Adresse und Platine, darunter
+/// die Lese-Register des Sensors mit ihren zuletzt gelesenen Werten. Beenden
+/// wird als `GuiMessage` an die GUI gesendet.
+#[derive(Debug)]
+pub struct LineDialog {
+    dialog: gtk::Dialog,
+    store: gtk::TreeStore,
+    treeview: gtk::TreeView,
+    label_state: gtk::Label,
+    // Zeilen der Register (Modbus Slave ID, Register Nummer)
+    rows: RefCell<HashMap<(u8, u16), gtk::TreeIter>>,
+}
+
+impl LineDialog {
+    /// Erstellt den Dialog
+    pub fn new(parent: &gtk::ApplicationWindow, gui_tx: &Sender<GuiMessage>) -> Self {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Live Ansicht aller Sensoren"),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Schließen", gtk::ResponseType::Close)],
+        );
+        dialog.set_default_size(600, 500);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+
+        // Beenden
+        let hbox_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let label_state = gtk::Label::new(None);
+        label_state.set_xalign(0.0);
+        let button_stop = gtk::Button::with_label("Live Ansicht beenden");
+        hbox_buttons.pack_start(&label_state, true, true, 0);
+        hbox_buttons.pack_end(&button_stop, false, true, 0);
+        content_area.pack_start(&hbox_buttons, false, true, 0);
+
+        // Sensoren und ihre Register
+        let store = gtk::TreeStore::new(&[
+            // Register Nummer
+            glib::Type::String,
+            // Beschreibung
+            glib::Type::String,
+            // Wert
+            glib::Type::String,
+        ]);
+        let treeview = gtk::TreeView::with_model(&store);
+        treeview.set_grid_lines(gtk::TreeViewGridLines::Horizontal);
+        for (i, title) in ["Register", "Beschreibung", "Wert"].iter().enumerate() {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            treeview.append_column(&column);
+        }
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.add(&treeview);
+        content_area.pack_start(&scrolled_window, true, true, 0);
+
+        //
+        // Callbacks
+        //
+
+        // Callback: Button "Live Ansicht beenden"
+        button_stop.connect_clicked(clone!(
+            @strong gui_tx,
+            @strong label_state
+            => move |_| {
+                label_state.set_text("Live Ansicht beendet");
+                let _ = gui_tx.clone().try_send(GuiMessage::StopLine);
+            }
+        ));
+
+        // Der Dialog wird nur versteckt, die Live Ansicht läuft weiter
+        dialog.connect_response(|dialog, _response| dialog.hide());
+        dialog.connect_delete_event(|dialog, _event| {
+            dialog.hide();
+            gtk::Inhibit(true)
+        });
+
+        LineDialog {
+            dialog,
+            store,
+            treeview,
+            label_state,
+            rows: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Zeigt den Dialog an
+    pub fn show(&self) {
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Entfernt alle Sensoren
+    pub fn clear(&self) {
+        self.store.clear();
+        self.rows.borrow_mut().clear();
+        self.label_state.set_text("");
+    }
+
+    /// Fügt einen Sensor mit seinen Lese-Registern ein
+    pub fn add_slave(&self, slave: u8, platine: &str, rregs: &[Rreg]) {
+        let parent = self.store.insert_with_values(
+            None,
+            None,
+            &[0, 1, 2],
+            &[&format!("Slave {}", slave), &platine, &""],
+        );
+        let mut rows = self.rows.borrow_mut();
+        for rreg in rregs {
+            let iter = self.store.insert_with_values(
+                Some(&parent),
+                None,
+                &[0, 1, 2],
+                &[&rreg.reg_nr().to_string(), &rreg.description(), &""],
+            );
+            rows.insert((slave, rreg.reg_nr() as u16), iter);
+        }
+        self.treeview.expand_all();
+    }
+
+    /// Zeigt die neuen Werte eines Sensors an
+    pub fn update(&self, slave: u8, values: &[(u16, u16)]) {
+        let rows = self.rows.borrow();
+        for (reg_nr, value) in values {
+            if let Some(iter) = rows.get(&(slave, *reg_nr)) {
+                self.store.set_value(iter, 2, &value.to_string().to_value());
+            }
+        }
+    }
+
+    /// Zeigt den Zustand der Verbindung an
+    pub fn set_state(&self, state: &str) {
+        self.label_state.set_text(state);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::modbus_master::{line_settings::MODBUS_MODES, scanner::BAUD_RATES, LineSettings};
+use gtk::prelude::*;
+
+/// Fragt nach neuen Schnittstellenparametern für den Sensor
+///
+/// Angeboten werden nur die Baudraten (Rwreg 81) und Modbus Modes (Rwreg 82)
+/// die der Sensor kennt, vorausgewählt sind die Parameter der Sitzung.
+/// Liefert `None` wenn der Benutzer abbricht.
+pub fn ask_line_settings(
+    parent: &gtk::ApplicationWindow,
+    current: LineSettings,
+) -> Option<LineSettings> {
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Schnittstellenparameter ändern"),
+        Some(parent),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Abbrechen", gtk::ResponseType::Cancel),
+            ("Übernehmen", gtk::ResponseType::Accept),
+        ],
+    );
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+
+    let label = gtk::Label::new(Some(&format!(
+        "Aktuell: {}\n\nDer Sensor wird umgestellt und anschließend mit den neuen\nParametern geprüft. Antwortet er nicht, werden die alten\nParameter wiederhergestellt.",
+        current
+    )));
+    label.set_xalign(0.0);
+    content_area.pack_start(&label, false, true, 0);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(6);
+
+    let combo_box_text_baud_rate = gtk::ComboBoxText::new();
+    for (_value, baud_rate) in BAUD_RATES {
+        combo_box_text_baud_rate.append(Some(&baud_rate.to_string()), &baud_rate.to_string());
+    }
+    combo_box_text_baud_rate.set_active_id(Some(&current.baud_rate.to_string()));
+
+    let combo_box_text_mode = gtk::ComboBoxText::new();
+    for (value, parity, stop_bits) in MODBUS_MODES {
+        combo_box_text_mode.append(
+            Some(&value.to_string()),
+            &format!("8{}{}", parity, stop_bits),
+        );
+    }
+    if let Some((value, _, _)) = MODBUS_MODES
+        .iter()
+        .find(|(_, parity, stop_bits)| *parity == current.parity && *stop_bits == current.stop_bits)
+    {
+        combo_box_text_mode.set_active_id(Some(&value.to_string()));
+    }
+
+    grid.attach(&gtk::Label::new(Some("Baudrate")), 0, 0, 1, 1);
+    grid.attach(&combo_box_text_baud_rate, 1, 0, 1, 1);
+    grid.attach(&gtk::Label::new(Some("Modbus Mode")), 0, 1, 1, 1);
+    grid.attach(&combo_box_text_mode, 1, 1, 1, 1);
+    content_area.pack_start(&grid, false, true, 0);
+
+    dialog.show_all();
+    let response = dialog.run();
+
+    let baud_rate = combo_box_text_baud_rate
+        .get_active_id()
+        .and_then(|id| id.parse::<i32>().ok());
+    let mode = combo_box_text_mode
+        .get_active_id()
+        .and_then(|id| id.parse::<u16>().ok())
+        .and_then(|id| MODBUS_MODES.iter().find(|(value, _, _)| *value == id));
+    dialog.close();
+
+    match (response, baud_rate, mode) {
+        (gtk::ResponseType::Accept, Some(baud_rate), Some((_, parity, stop_bits))) => {
+            Some(LineSettings {
+                baud_rate,
+                parity: *parity,
+                stop_bits: *stop_bits,
+                ..current
+            })
+        }
+        _ => None,
+    }
+}
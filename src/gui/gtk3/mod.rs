@@ -2,25 +2,58 @@
 
 #[macro_use]
 mod macros;
+mod adapter_check_dialog;
+mod detect_dialog;
+mod diagnostics_dialog;
+mod endpoint_dialog;
+mod line_dialog;
+mod line_settings_dialog;
+mod restart_dialog;
 mod rreg_store;
 mod rwreg_store;
+mod scan_dialog;
+mod sniffer_dialog;
+mod traffic_dialog;
 // Reexports
+pub use adapter_check_dialog::show_adapter_report;
+pub use detect_dialog::DetectDialog;
+pub use diagnostics_dialog::DiagnosticsDialog;
+pub use endpoint_dialog::ask_endpoint;
+pub use line_dialog::LineDialog;
+pub use line_settings_dialog::ask_line_settings;
+pub use restart_dialog::confirm_restart;
 pub use rreg_store::{BoxedRregStore, RregStore};
 pub use rwreg_store::{BoxedRwregStore, RwregStore};
+pub use scan_dialog::ScanDialog;
+pub use sniffer_dialog::SnifferDialog;
+pub use traffic_dialog::TrafficDialog;
 
 use crate::{
-    modbus_master::{ModbusMaster, ModbusMasterMessage},
+    adapter_check::{self, Report},
+    modbus_master::{
+        endpoint, line_change, Addressing, BusDevice, ConnectionState, LineSettings, ModbusMaster,
+        ModbusMasterError, ModbusMasterEvent, PollTarget, Statistics, TrafficRecord,
+        TransmissionMode,
+    },
     platine::{self, *},
     registers,
-    serial_interface::SerialInterface,
+    serial_interface::{port_options, select_port, LockHolder, PortInfo, SerialInterface},
+    settings::Settings,
+    sniffer::{Sniffer, SnifferEvent},
+};
+use chrono::{DateTime, Local};
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver},
+    future::FutureExt,
 };
-use futures::channel::mpsc;
 use gio::prelude::*;
 use glib::{clone, signal};
 use gtk::{prelude::*, Application, NotebookExt};
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    ops::RangeInclusive,
     rc::Rc,
     sync::{Arc, Mutex},
 };
@@ -35,6 +68,10 @@ pub struct Gui {
     combo_box_text_ports_changed_signal: glib::SignalHandlerId,
     combo_box_text_ports_map: Rc<RefCell<HashMap<String, u32>>>,
     combo_box_text_ports: gtk::ComboBoxText,
+    serial_ports: RefCell<Vec<PortInfo>>,
+    busy_ports: RefCell<BTreeMap<String, LockHolder>>,
+    serial_interface: SerialInterface,
+    settings: Settings,
     infobar_error: gtk::InfoBar,
     infobar_info: gtk::InfoBar,
     infobar_question: gtk::InfoBar,
@@ -47,6 +84,8 @@ pub struct Gui {
     label_sensor1_value_value: gtk::Label,
     label_sensor1_value_si: gtk::Label,
     label_sensor2_value_value: gtk::Label,
+    label_statistics: gtk::Label,
+    notebook_sensor: gtk::Notebook,
     platine: BoxedPlatine,
     revealer_infobar_error: gtk::Revealer,
     revealer_infobar_info: gtk::Revealer,
@@ -58,6 +97,8 @@ pub struct Gui {
     statusbar_contexts: HashMap<StatusBarContext, u32>,
     toggle_button_connect: gtk::ToggleButton,
     check_button_mcs: gtk::CheckButton,
+    combo_box_text_addressing: gtk::ComboBoxText,
+    spin_button_modbus_address: gtk::SpinButton,
     spin_button_new_modbus_address: gtk::SpinButton,
     button_new_modbus_address: gtk::Button,
     combo_box_text_hw_version: gtk::ComboBoxText,
@@ -69,7 +110,15 @@ pub struct Gui {
     button_duo_sensor1_messgas: gtk::Button,
     button_duo_sensor2_nullpunkt: gtk::Button,
     button_duo_sensor2_messgas: gtk::Button,
-    modbus_master_tx: tokio::sync::mpsc::Sender<ModbusMasterMessage>,
+    check_menu_item_verify: gtk::CheckMenuItem,
+    check_menu_item_ascii: gtk::CheckMenuItem,
+    scan_dialog: ScanDialog,
+    detect_dialog: DetectDialog,
+    traffic_dialog: TrafficDialog,
+    sniffer_dialog: SnifferDialog,
+    diagnostics_dialog: DiagnosticsDialog,
+    line_dialog: LineDialog,
+    modbus_master: ModbusMaster,
 }
 
 /// Kommandos an die Grafische Schnittstelle
@@ -98,12 +147,160 @@ pub enum GuiMessage {
     },
     /// Update Sensor Werte
     UpdateSensorValues(Vec<(u16, u16)>),
+    /// Der Benutzer hat eine andere Schnittstelle gewählt
+    SerialPortSelected,
     /// Update verfügbare seriale Schnittstellen (Auswahlfeld oben links)
-    UpdateSerialPorts(Vec<String>),
+    UpdateSerialPorts {
+        /// Alle verfügbaren Schnittstellen
+        ports: Vec<PortInfo>,
+        /// Neu hinzugekommene Schnittstellen
+        added: Vec<PortInfo>,
+        /// Entfernte Schnittstellen
+        removed: Vec<PortInfo>,
+    },
+    /// Von anderen Programmen gesperrte Schnittstellen mit dem Inhaber der Sperre
+    UpdateBusyPorts(BTreeMap<String, LockHolder>),
     /// Verarbeite Daten der Lese-Register
-    UpdateRregs(Vec<(u16, u16)>),
+    UpdateRregs {
+        /// Modbus Slave ID
+        slave: u8,
+        /// Geänderte Werte (Register Nummer, Wert)
+        values: Vec<(u16, u16)>,
+    },
     /// Verarbeite Daten der Schreib.-/ Lese-Register
-    UpdateRwregs(Vec<(u16, u16)>),
+    UpdateRwregs {
+        /// Modbus Slave ID
+        slave: u8,
+        /// Geänderte Werte (Register Nummer, Wert)
+        values: Vec<(u16, u16)>,
+    },
+    /// Zeigt den Dialog für den Bus Scan
+    ShowScanDialog,
+    /// Diese Nachricht kommt vom ScanDialog
+    /// ScanDialog -> Gui -> ModbusMaster
+    ModbusMasterScan {
+        /// Bereich der Modbus Slave IDs
+        slaves: RangeInclusive<u8>,
+        /// Baudraten die durchsucht werden
+        baud_rates: Vec<i32>,
+    },
+    /// Bricht den Bus Scan ab (ScanDialog -> Gui -> ModbusMaster)
+    ModbusMasterCancelScan,
+    /// Der Bus Scan läuft
+    ScanStarted,
+    /// Fortschritt des Bus Scans
+    UpdateScanProgress {
+        /// bereits abgefragte Adressen
+        done: usize,
+        /// Anzahl aller Adressen
+        total: usize,
+    },
+    /// Der Bus Scan hat einen Sensor gefunden
+    ScanDeviceFound(BusDevice),
+    /// Der Bus Scan ist beendet
+    ScanFinished,
+    /// Der Zustand der Verbindung hat sich geändert (Live Ansicht)
+    ConnectionState {
+        /// Neuer Zustand
+        state: ConnectionState,
+        /// Grund des Zustandswechsels
+        reason: String,
+        /// Zeitpunkt an dem zuletzt ein Register erfolgreich gelesen wurde
+        last_poll: Option<DateTime<Local>>,
+    },
+    /// Sucht Baudrate und Parität des Sensors
+    ///
+    /// Mit der eingestellten Modbus Adresse, oder des einzigen Sensors am Bus
+    /// wenn eine Adressierung dafür gewählt ist.
+    DetectLineSettings,
+    /// Die Erkennung der Schnittstellenparameter läuft
+    DetectStarted(String),
+    /// Bricht die Erkennung ab (DetectDialog -> Gui -> ModbusMaster)
+    ModbusMasterCancelDetect,
+    /// Fortschritt der Erkennung
+    UpdateDetectProgress {
+        /// bereits probierte Kombinationen
+        done: usize,
+        /// Anzahl aller Kombinationen
+        total: usize,
+    },
+    /// Die Erkennung ist beendet, mit Slave ID und Parametern wenn gefunden
+    DetectFinished(Option<(u8, LineSettings)>),
+    /// Die Schnittstellenparameter eines Sensors wurden gefunden
+    LineSettingsDetected {
+        /// Modbus Slave ID
+        slave: u8,
+        /// Gefundene Schnittstellenparameter
+        line_settings: LineSettings,
+    },
+    /// Fragt nach neuen Schnittstellenparametern für den Sensor
+    ShowChangeLineSettings,
+    /// Fragt nach einer weiteren Schnittstelle (Pseudo-Terminal, Gateway)
+    ShowAddEndpoint,
+    /// Entfernt die gewählte, von Hand hinzugefügte, Schnittstelle
+    RemoveEndpoint,
+    /// Startet den Sensor nach Bestätigung neu, eventuell mit Grunddaten
+    RestartSensor {
+        /// Grunddaten laden?
+        factory_reset: bool,
+    },
+    /// Stellt den Sensor auf neue Schnittstellenparameter um (Gui -> ModbusMaster)
+    ChangeLineSettings(LineSettings),
+    /// Die Sitzung ist auf neue Schnittstellenparameter umgestellt
+    LineSettingsChanged(LineSettings),
+    /// Wechselt die Übertragungsart der Sitzung (Gui -> ModbusMaster)
+    SetTransmissionMode(TransmissionMode),
+    /// Die Übertragungsart der Sitzung wurde nicht gewechselt, mit dem Fehler
+    TransmissionModeRejected(String),
+    /// Übernimmt Modbus Adresse und Platine eines gefundenen Sensors
+    SelectScanDevice {
+        /// Modbus Slave ID
+        slave: u8,
+        /// Name der Platine
+        platine: String,
+    },
+    /// Startet die Live Ansicht für alle gefundenen Sensoren
+    /// ScanDialog -> Gui -> ModbusMaster
+    ConnectLine(Vec<(u8, String)>),
+    /// Beendet die Live Ansicht aller Sensoren (LineDialog -> Gui -> ModbusMaster)
+    StopLine,
+    /// Zeigt einen Zustand der Live Ansicht aller Sensoren an, z.B. einen Fehler
+    LineState(String),
+    /// Zeigt den Dialog für den Mitschnitt
+    ShowTrafficDialog,
+    /// Schaltet den Mitschnitt ein oder aus (TrafficDialog -> Gui -> ModbusMaster)
+    SetTrafficRecording(bool),
+    /// Neue Einträge des Mitschnitts
+    Traffic(Vec<TrafficRecord>),
+    /// Zeigt den Dialog für das Mithören am Bus
+    ShowSnifferDialog,
+    /// Startet das Mithören (SnifferDialog -> Gui -> Sniffer)
+    StartSniffer,
+    /// Der Modbus Master hat die Schnittstelle freigegeben, startet den Sniffer
+    RunSniffer(String),
+    /// Das Mithören konnte nicht gestartet werden, mit dem Fehler
+    SnifferNotStarted(String),
+    /// Beendet das Mithören (SnifferDialog -> Gui -> Sniffer)
+    StopSniffer,
+    /// Zeigt den Dialog mit der Statistik der Kommunikation
+    ShowDiagnosticsDialog,
+    /// Setzt die Statistik zurück (DiagnosticsDialog -> Gui -> ModbusMaster)
+    ResetStatistics,
+    /// Prüft den Adapter an der gewählten Schnittstelle
+    CheckAdapter,
+    /// Der Modbus Master hat die Schnittstelle freigegeben, startet die Prüfung
+    RunAdapterCheck(String),
+    /// Die Prüfung des Adapters ist beendet
+    AdapterChecked(Report),
+    /// Die Statistik der Kommunikation hat sich geändert
+    Statistics(Statistics),
+    /// Ereignis des Sniffers
+    Sniffer {
+        /// Nummer des Starts, Ereignisse früherer Starts werden ignoriert
+        session: u32,
+        /// Ereignis
+        event: SnifferEvent,
+    },
 }
 /// Contexte für die Status Bar
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -132,11 +329,16 @@ fn ui_init(app: &gtk::Application) {
     // GUI Channel
     let (gui_tx, mut gui_rx) = mpsc::channel(0);
     // Modbus Master Thread
-    let modbus_master = ModbusMaster::new(gui_tx.clone());
-    // Modbus Master Channel
-    let modbus_master_tx = modbus_master.tx;
+    let modbus_master = ModbusMaster::new();
+    // Ereignisse des Modbus Masters (Live Ansicht, Bus Scan) an die GUI weiterleiten
+    glib::MainContext::default().spawn_local(forward_modbus_master_events(
+        modbus_master.subscribe(),
+        gui_tx.clone(),
+    ));
+    // Einstellungen des Benutzers
+    let settings = Settings::load();
     // Serial Interface Thread
-    let _serial_interface = SerialInterface::new(gui_tx.clone());
+    let serial_interface = SerialInterface::new(gui_tx.clone(), &settings);
     // Platine
     // Der Callback 'Auswahl Platine' setzt die verwendete Platine 'gui_platine' sowie
     // die Schreib und Schreib/Lese TreeStores 'rreg_store' und 'rwreg_store'
@@ -170,6 +372,7 @@ fn ui_init(app: &gtk::Application) {
     let spin_button_new_modbus_address: gtk::SpinButton =
         build!(builder, "spin_button_new_modbus_address");
     let check_button_mcs: gtk::CheckButton = build!(builder, "check_button_mcs");
+    let combo_box_text_addressing: gtk::ComboBoxText = build!(builder, "combo_box_text_addressing");
     let button_new_modbus_address: gtk::Button = build!(builder, "button_new_modbus_address");
     let button_reset: gtk::Button = build!(builder, "button_reset");
     let button_sensor_working_mode: gtk::Button = build!(builder, "button_sensor_working_mode");
@@ -194,6 +397,9 @@ fn ui_init(app: &gtk::Application) {
             .iter()
             .cloned()
             .collect();
+    // Zusammenfassung der Statistik rechts in der Statusleiste
+    let label_statistics = gtk::Label::new(None);
+    statusbar_application.pack_end(&label_statistics, false, true, 0);
 
     // Combo boxes
     // ComboBox Hardware Version
@@ -209,6 +415,21 @@ fn ui_init(app: &gtk::Application) {
     let menu_item_quit: gtk::MenuItem = build!(builder, "menu_item_quit");
     let menu_item_about: gtk::MenuItem = build!(builder, "menu_item_about");
     let menu_item_help: gtk::MenuItem = build!(builder, "menu_item_help");
+    let menu_item_scan: gtk::MenuItem = build!(builder, "menu_item_scan");
+    let menu_item_add_endpoint: gtk::MenuItem = build!(builder, "menu_item_add_endpoint");
+    let menu_item_remove_endpoint: gtk::MenuItem = build!(builder, "menu_item_remove_endpoint");
+    let menu_item_detect_line_settings: gtk::MenuItem =
+        build!(builder, "menu_item_detect_line_settings");
+    let menu_item_traffic: gtk::MenuItem = build!(builder, "menu_item_traffic");
+    let menu_item_sniffer: gtk::MenuItem = build!(builder, "menu_item_sniffer");
+    let menu_item_diagnostics: gtk::MenuItem = build!(builder, "menu_item_diagnostics");
+    let menu_item_adapter_check: gtk::MenuItem = build!(builder, "menu_item_adapter_check");
+    let menu_item_change_line_settings: gtk::MenuItem =
+        build!(builder, "menu_item_change_line_settings");
+    let menu_item_restart: gtk::MenuItem = build!(builder, "menu_item_restart");
+    let menu_item_factory_reset: gtk::MenuItem = build!(builder, "menu_item_factory_reset");
+    let check_menu_item_verify: gtk::CheckMenuItem = build!(builder, "check_menu_item_verify");
+    let check_menu_item_ascii: gtk::CheckMenuItem = build!(builder, "check_menu_item_ascii");
     let about_dialog: gtk::AboutDialog = build!(builder, "about_dialog");
     let about_dialog_button_ok: gtk::Button = build!(builder, "about_dialog_button_ok");
     about_dialog.set_program_name(PKG_NAME);
@@ -268,7 +489,12 @@ fn ui_init(app: &gtk::Application) {
     // Begin Callbacks
     //
 
-    let combo_box_text_ports_changed_signal = combo_box_text_ports.connect_changed(move |_| {});
+    // Callback: Auswahl Schnittstelle
+    let combo_box_text_ports_changed_signal = combo_box_text_ports.connect_changed(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::SerialPortSelected);
+        }
+    ));
 
     // Callback: Reset Button
     //
@@ -309,10 +535,12 @@ fn ui_init(app: &gtk::Application) {
     // Callback: Speichern der neuen Modbus ID
     button_new_modbus_address.connect_clicked(clone!(
         @strong check_button_mcs,
+        @strong check_menu_item_verify,
+        @strong combo_box_text_addressing,
         @strong combo_box_text_ports_map,
         @strong combo_box_text_ports,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_new_modbus_address,
         @strong spin_button_modbus_address
@@ -329,6 +557,14 @@ fn ui_init(app: &gtk::Application) {
 
             // get MCS Konfig
             let mcs_config = check_button_mcs.get_active();
+            // Geschriebene Werte zurück lesen?
+            let verify = check_menu_item_verify.get_active();
+            // Einziger Sensor am Bus?
+            let addressing = match combo_box_text_addressing.get_active_id().as_deref() {
+                Some("first_responding") => Some(Addressing::FirstResponding),
+                Some("broadcast") => Some(Addressing::Broadcast),
+                _ => None,
+            };
 
             match platine.lock() {
                 Ok(platine) => {
@@ -355,34 +591,29 @@ fn ui_init(app: &gtk::Application) {
                             let reg_protection = platine.reg_protection();
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            if mcs_config {
-                                match modbus_master_tx.clone()
-                                .try_send(ModbusMasterMessage::SetNewMcsBusId {
-                                    tty_path,
-                                    slave,
-                                    new_slave_id,
-                                    reg_protection
-                                })
-                                {
-                                    Ok(_) => {}
-                                    Err(error) => {
-                                        show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                    }
+                            let kind = if mcs_config { "MCS BUS Adresse" } else { "Modbus Adresse" };
+                            if let Some(addressing) = addressing {
+                                if addressing == Addressing::FirstResponding {
+                                    show_info(&gui_tx, "Suche den Sensor am Bus ...");
                                 }
+                                let request = modbus_master.set_single_device_address(tty_path, addressing, mcs_config, new_slave_id, reg_protection, verify);
+                                spawn_request(&gui_tx, request, move |gui_tx, result| match result {
+                                    Ok(Some(slave)) => show_info(gui_tx, &format!("Sensor unter Modbus Adresse {} gefunden, {}: <b>{}</b> gespeichert.", slave, kind, &new_slave_id)),
+                                    Ok(None) => show_info(gui_tx, &format!("{}: <b>{}</b> per Broadcast gespeichert.", kind, &new_slave_id)),
+                                    Err(error) => show_warning(gui_tx, &format!("Konnte {} '{}' nicht speichern:\r\n{}", kind, &new_slave_id, error)),
+                                });
+                            } else if mcs_config {
+                                let request = modbus_master.set_new_mcs_bus_id(tty_path, slave, new_slave_id, reg_protection, verify);
+                                spawn_request(&gui_tx, request, move |gui_tx, result| match result {
+                                    Ok(_) => show_info(gui_tx, &format!("MCS BUS Adresse: <b>{}</b> gespeichert.", &new_slave_id)),
+                                    Err(error) => show_warning(gui_tx, &format!("Konnte MCS Adresse '{}' nicht speichern:\r\n{}", &new_slave_id, error)),
+                                });
                             } else {
-                                match modbus_master_tx.clone()
-                                .try_send(ModbusMasterMessage::SetNewModbusId {
-                                    tty_path,
-                                    slave,
-                                    new_slave_id,
-                                    reg_protection
-                                })
-                                {
-                                    Ok(_) => {}
-                                    Err(error) => {
-                                        show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                    }
-                                }
+                                let request = modbus_master.set_new_modbus_id(tty_path, slave, new_slave_id, reg_protection, verify);
+                                spawn_request(&gui_tx, request, move |gui_tx, result| match result {
+                                    Ok(_) => show_info(gui_tx, &format!("Modbus Adresse: <b>{}</b> gespeichert.", &new_slave_id)),
+                                    Err(error) => show_warning(gui_tx, &format!("Konnte Modbus Adresse '{}' nicht speichern:\r\n{}", &new_slave_id, error)),
+                                });
                             }
                         },
                         // keine Platine gewählt
@@ -402,7 +633,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_hw_version,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |button| {
@@ -443,19 +674,8 @@ fn ui_init(app: &gtk::Application) {
                                 info!("tty_path: {:?}, slave: {:?}", &tty_path, &slave);
 
                                 // Sende Nachricht an Modbus Master und werte diese aus
-                                match modbus_master_tx.clone()
-                                .try_send(ModbusMasterMessage::Connect(
-                                    tty_path,
-                                    slave,
-                                    rregs,
-                                    rwregs,
-                                    reg_protection
-                                )) {
-                                    Ok(_) => {}
-                                    Err(error) => {
-                                        show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                    }
-                                }
+                                let request = modbus_master.connect(tty_path, slave, rregs, rwregs, reg_protection);
+                                spawn_request(&gui_tx, request, show_unreachable);
                                 // disable gui elemente
                                 let _ = gui_tx.clone().try_send(GuiMessage::DisableUiElements);
                             }
@@ -471,13 +691,7 @@ fn ui_init(app: &gtk::Application) {
             // Beende Live Ansicht
             } else {
                 // Sende Nachricht an Modbus Master und werte diese aus
-                match modbus_master_tx.clone()
-                .try_send(ModbusMasterMessage::Disconnect) {
-                    Ok(_) => {}
-                    Err(error) => {
-                        show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                    }
-                }
+                spawn_request(&gui_tx, modbus_master.disconnect(), show_unreachable);
                 // enable gui elemente
                 let _ = gui_tx.clone().try_send(GuiMessage::EnableUiElements);
             }
@@ -490,7 +704,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |_| {
@@ -524,18 +738,8 @@ fn ui_init(app: &gtk::Application) {
                             debug!("tty_path: {:?}, slave: {:?}", &tty_path, &slave);
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            match modbus_master_tx.clone()
-                            .try_send(ModbusMasterMessage::Nullgas {
-                                tty_path,
-                                slave,
-                                reg_protection,
-                                sensor_num: 1,
-                            }) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                }
-                            }
+                            let request = modbus_master.nullgas(tty_path, slave, reg_protection, 1);
+                            spawn_request(&gui_tx, request, show_nullgas_result);
                         },
                         None => {
                             show_error(&gui_tx, "Es wurde keine Platine ausgewählt!");
@@ -555,7 +759,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |_| {
@@ -589,18 +793,8 @@ fn ui_init(app: &gtk::Application) {
                             debug!("tty_path: {:?}, slave: {:?}", &tty_path, &slave);
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            match modbus_master_tx.clone()
-                            .try_send(ModbusMasterMessage::Nullgas {
-                                tty_path,
-                                slave,
-                                reg_protection,
-                                sensor_num: 1,
-                            }) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                }
-                            }
+                            let request = modbus_master.nullgas(tty_path, slave, reg_protection, 1);
+                            spawn_request(&gui_tx, request, show_nullgas_result);
                         },
                         None => {
                             show_error(&gui_tx, "Es wurde keine Platine ausgewählt!");
@@ -620,7 +814,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |_| {
@@ -654,18 +848,8 @@ fn ui_init(app: &gtk::Application) {
                             debug!("tty_path: {:?}, slave: {:?}", &tty_path, &slave);
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            match modbus_master_tx.clone()
-                            .try_send(ModbusMasterMessage::Nullgas {
-                                tty_path,
-                                slave,
-                                reg_protection,
-                                sensor_num: 2,
-                            }) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                }
-                            }
+                            let request = modbus_master.nullgas(tty_path, slave, reg_protection, 2);
+                            spawn_request(&gui_tx, request, show_nullgas_result);
                         },
                         None => {
                             show_error(&gui_tx, "Es wurde keine Platine ausgewählt!");
@@ -685,7 +869,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |_| {
@@ -719,18 +903,8 @@ fn ui_init(app: &gtk::Application) {
                             info!("tty_path: {:?}, slave: {:?}", &tty_path, &slave);
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            match modbus_master_tx.clone()
-                            .try_send(ModbusMasterMessage::Messgas{
-                                tty_path,
-                                slave,
-                                reg_protection,
-                                sensor_num: 1,
-                            }) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                }
-                            }
+                            let request = modbus_master.messgas(tty_path, slave, reg_protection, 1);
+                            spawn_request(&gui_tx, request, show_messgas_result);
                         },
                         None => {
                             show_error(&gui_tx, "Es wurde keine Platine ausgewählt!");
@@ -750,7 +924,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |_| {
@@ -784,18 +958,8 @@ fn ui_init(app: &gtk::Application) {
                             info!("tty_path: {:?}, slave: {:?}", &tty_path, &slave);
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            match modbus_master_tx.clone()
-                            .try_send(ModbusMasterMessage::Messgas{
-                                tty_path,
-                                slave,
-                                reg_protection,
-                                sensor_num: 1,
-                            }) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                }
-                            }
+                            let request = modbus_master.messgas(tty_path, slave, reg_protection, 1);
+                            spawn_request(&gui_tx, request, show_messgas_result);
                         },
                         None => {
                             show_error(&gui_tx, "Es wurde keine Platine ausgewählt!");
@@ -815,7 +979,7 @@ fn ui_init(app: &gtk::Application) {
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong platine,
         @strong spin_button_modbus_address
         => move |_| {
@@ -848,18 +1012,8 @@ fn ui_init(app: &gtk::Application) {
                             let slave = spin_button_modbus_address.get_value() as u8;
 
                             // Sende Nachricht an Modbus Master und werte diese aus
-                            match modbus_master_tx.clone()
-                            .try_send(ModbusMasterMessage::Messgas{
-                                tty_path,
-                                slave,
-                                reg_protection,
-                                sensor_num: 2,
-                            }) {
-                                Ok(_) => {}
-                                Err(error) => {
-                                    show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                }
-                            }
+                            let request = modbus_master.messgas(tty_path, slave, reg_protection, 2);
+                            spawn_request(&gui_tx, request, show_messgas_result);
                         },
                         None => {
                             show_error(&gui_tx, "Es wurde keine Platine ausgewählt!");
@@ -891,12 +1045,13 @@ fn ui_init(app: &gtk::Application) {
         @strong button_nullpunkt,
         @strong button_sensor_working_mode,
         @strong check_button_mcs,
+        @strong combo_box_text_addressing,
         @strong combo_box_text_hw_version,
         @strong combo_box_text_ports,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx,
         @strong label_sensor1_value_si,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong notebook_sensor,
         @strong platine,
         @strong rreg_store,
@@ -1118,6 +1273,7 @@ fn ui_init(app: &gtk::Application) {
                             button_new_modbus_address.set_sensitive(true);
                             button_sensor_working_mode.set_sensitive(true);
                             check_button_mcs.set_sensitive(true);
+                            combo_box_text_addressing.set_sensitive(true);
                             combo_box_text_sensor_working_mode.set_sensitive(true);
                             spin_button_new_modbus_address.set_sensitive(true);
                         }
@@ -1130,10 +1286,11 @@ fn ui_init(app: &gtk::Application) {
 
     // Callback: Button Arbeitsweise
     button_sensor_working_mode.connect_clicked(clone!(
+        @strong check_menu_item_verify,
         @strong platine,
         @strong combo_box_text_ports,
         @strong combo_box_text_ports_map,
-        @strong modbus_master_tx,
+        @strong modbus_master,
         @strong spin_button_modbus_address,
         @strong combo_box_text_sensor_working_mode,
         @strong gui_tx
@@ -1174,18 +1331,12 @@ fn ui_init(app: &gtk::Application) {
                                     let working_mode: u16 = working_mode.first().unwrap_or(&"0").parse::<u16>().unwrap_or(0);
 
                                     // Sende Nachricht an Modbus Master
-                                    match modbus_master_tx.clone()
-                                    .try_send(ModbusMasterMessage::SetNewWorkingMode {
-                                        tty_path,
-                                        slave,
-                                        working_mode,
-                                        reg_protection
-                                    }) {
-                                        Ok(_) => {}
-                                        Err(error) => {
-                                            show_error(&gui_tx, &format!("Modbus Master konnte nicht erreicht werden: {}!", error));
-                                        }
-                                    }
+                                    let verify = check_menu_item_verify.get_active();
+                                    let request = modbus_master.set_new_working_mode(tty_path, slave, working_mode, reg_protection, verify);
+                                    spawn_request(&gui_tx, request, |gui_tx, result| match result {
+                                        Ok(_) => show_info(gui_tx, "Arbeitsweise erfolgreich gesetzt"),
+                                        Err(error) => show_warning(gui_tx, &format!("Konnte Arbeitsweise nicht festlegen:\r\n{}", error)),
+                                    });
                                 },
                                 None => {
                                     show_error(&gui_tx, "Bitte Arbeitsweise auswählen!");
@@ -1250,6 +1401,119 @@ fn ui_init(app: &gtk::Application) {
         };
     });
 
+    // Bus Scan Dialog
+    let scan_dialog = ScanDialog::new(&application_window, &gui_tx);
+
+    // Callback: Menu Bus Scan
+    menu_item_scan.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::ShowScanDialog);
+        }
+    ));
+
+    // Fortschritt der Erkennung von Baudrate und Parität
+    let detect_dialog = DetectDialog::new(&application_window, &gui_tx);
+
+    // Live Ansicht aller Sensoren
+    let line_dialog = LineDialog::new(&application_window, &gui_tx);
+
+    // Mitschnitt Dialog
+    let traffic_dialog = TrafficDialog::new(&application_window, &gui_tx);
+
+    // Callback: Menu Modbus Mitschnitt
+    menu_item_traffic.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::ShowTrafficDialog);
+        }
+    ));
+
+    // Mithören Dialog
+    let sniffer_dialog = SnifferDialog::new(&application_window, &gui_tx);
+
+    // Callback: Menu Bus mithören
+    menu_item_sniffer.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::ShowSnifferDialog);
+        }
+    ));
+
+    // Diagnose Dialog
+    let diagnostics_dialog = DiagnosticsDialog::new(&application_window, &gui_tx);
+
+    // Callback: Menu Diagnose
+    menu_item_diagnostics.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::ShowDiagnosticsDialog);
+        }
+    ));
+
+    // Callback: Menu Adapter prüfen
+    menu_item_adapter_check.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::CheckAdapter);
+        }
+    ));
+
+    // Callback: Menu Schnittstelle hinzufügen
+    menu_item_add_endpoint.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::ShowAddEndpoint);
+        }
+    ));
+
+    // Callback: Menu Schnittstelle entfernen
+    menu_item_remove_endpoint.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::RemoveEndpoint);
+        }
+    ));
+
+    // Callback: Menu Schnittstellenparameter ändern
+    menu_item_change_line_settings.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::ShowChangeLineSettings);
+        }
+    ));
+
+    // Callback: Menu Modbus ASCII
+    check_menu_item_ascii.connect_toggled(clone!(
+        @strong gui_tx => move |check_menu_item| {
+            let transmission_mode = if check_menu_item.get_active() {
+                TransmissionMode::Ascii
+            } else {
+                TransmissionMode::Rtu
+            };
+            let _ = gui_tx
+                .clone()
+                .try_send(GuiMessage::SetTransmissionMode(transmission_mode));
+        }
+    ));
+
+    // Callback: Menu Sensor neu starten
+    menu_item_restart.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx
+                .clone()
+                .try_send(GuiMessage::RestartSensor { factory_reset: false });
+        }
+    ));
+
+    // Callback: Menu Grunddaten laden
+    menu_item_factory_reset.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx
+                .clone()
+                .try_send(GuiMessage::RestartSensor { factory_reset: true });
+        }
+    ));
+
+    // Callback: Menu Baudrate erkennen
+    menu_item_detect_line_settings.connect_activate(clone!(
+        @strong gui_tx => move |_| {
+            let _ = gui_tx.clone().try_send(GuiMessage::DetectLineSettings);
+        }
+    ));
+
     // Callback: Menu About Ok
     about_dialog_button_ok.connect_clicked(clone!(
         @strong about_dialog => move |_| {
@@ -1303,6 +1567,10 @@ fn ui_init(app: &gtk::Application) {
         combo_box_text_ports_changed_signal,
         combo_box_text_ports_map,
         combo_box_text_ports,
+        serial_ports: RefCell::new(vec![]),
+        busy_ports: RefCell::new(BTreeMap::new()),
+        serial_interface,
+        settings,
         infobar_error,
         infobar_info,
         infobar_question,
@@ -1315,6 +1583,8 @@ fn ui_init(app: &gtk::Application) {
         label_sensor1_value_value,
         label_sensor1_value_si,
         label_sensor2_value_value,
+        label_statistics,
+        notebook_sensor,
         platine,
         revealer_infobar_error,
         revealer_infobar_info,
@@ -1326,6 +1596,8 @@ fn ui_init(app: &gtk::Application) {
         statusbar_contexts,
         toggle_button_connect,
         check_button_mcs,
+        combo_box_text_addressing,
+        spin_button_modbus_address: spin_button_modbus_address.clone(),
         spin_button_new_modbus_address,
         button_new_modbus_address,
         combo_box_text_hw_version,
@@ -1337,7 +1609,15 @@ fn ui_init(app: &gtk::Application) {
         button_duo_sensor1_messgas,
         button_duo_sensor2_nullpunkt,
         button_duo_sensor2_messgas,
-        modbus_master_tx: modbus_master_tx.clone(),
+        check_menu_item_verify,
+        check_menu_item_ascii,
+        scan_dialog,
+        detect_dialog,
+        traffic_dialog,
+        sniffer_dialog,
+        diagnostics_dialog,
+        line_dialog,
+        modbus_master: modbus_master.clone(),
     };
 
     application_window.show_all();
@@ -1347,6 +1627,13 @@ fn ui_init(app: &gtk::Application) {
         use futures::stream::StreamExt;
 
         async move {
+            // Schnittstellenparameter der Sitzung, werden auch vom Sniffer verwendet
+            let mut session_line_settings = LineSettings::default();
+            // Laufender Sniffer und Nummer seines Starts
+            let mut sniffer: Option<Sniffer> = None;
+            let mut sniffer_session = 0;
+            let mut sniffer_error = None;
+
             while let Some(event) = gui_rx.next().await {
                 match event {
                     GuiMessage::DisableUiElements => {
@@ -1404,34 +1691,550 @@ fn ui_init(app: &gtk::Application) {
                             }
                         };
                         let reg_protection: u16 = gui.platine_reg_protection();
-                        let _ = modbus_master_tx.clone().try_send(
-                            ModbusMasterMessage::UpdateRegister {
-                                tty_path,
-                                slave,
-                                reg_nr,
+                        // Baudrate und Modbus Mode nicht direkt schreiben, der
+                        // Sensor wäre danach nicht mehr erreichbar
+                        if line_change::is_line_register(reg_protection, reg_nr) {
+                            match line_change::with_register_value(
+                                session_line_settings,
                                 reg_protection,
+                                reg_nr,
                                 new_value,
-                            },
+                            ) {
+                                Some(line_settings) => {
+                                    let _ = gui_tx
+                                        .clone()
+                                        .try_send(GuiMessage::ChangeLineSettings(line_settings));
+                                }
+                                None => gui.show_infobar_error(&format!(
+                                    "Wert {} wird für Register {} nicht unterstützt!",
+                                    new_value, reg_nr
+                                )),
+                            }
+                            continue;
+                        }
+                        let verify = gui.check_menu_item_verify.get_active();
+                        let request = gui.modbus_master.update_register(
+                            tty_path,
+                            slave,
+                            reg_nr,
+                            reg_protection,
+                            new_value,
+                            verify,
                         );
+                        spawn_request(&gui_tx, request, |gui_tx, result| match result {
+                            Ok(_) => show_info(gui_tx, "Register erfolgreich aktualisiert"),
+                            Err(error) => show_warning(
+                                gui_tx,
+                                &format!("Konnte Register nicht aktualisieren:\r\n{}", error),
+                            ),
+                        });
                         debug!("ModbusMaster Update One Register:");
                     }
                     GuiMessage::UpdateSensorValues(results) => {
                         debug!("Update sensor values with: {:?}", &results);
                         // gui.update_rreg_sensor_values(results);
                     }
-                    GuiMessage::UpdateSerialPorts(ports) => {
+                    GuiMessage::SerialPortSelected => {
+                        gui.port_selected();
+                    }
+                    GuiMessage::UpdateSerialPorts {
+                        ports,
+                        added,
+                        removed,
+                    } => {
                         debug!("Update Serial Ports with: {:?}", &ports);
-                        gui.update_serial_ports(ports);
+                        // Die Optionen folgen den Adaptern auch unter einem neuen Pfad
+                        let port_options = port_options(&ports, &gui.settings.port_options);
+                        spawn_request(
+                            &gui_tx,
+                            gui.modbus_master.set_port_options(port_options),
+                            show_unreachable,
+                        );
+                        gui.update_serial_ports(ports, added, removed);
+                    }
+                    GuiMessage::UpdateBusyPorts(busy_ports) => {
+                        debug!("Update busy ports with: {:?}", &busy_ports);
+                        gui.update_busy_ports(busy_ports);
+                    }
+                    GuiMessage::UpdateRregs { slave, values } => {
+                        debug!("Update Rregs of slave {} with: {:?}", slave, &values);
+                        gui.line_dialog.update(slave, &values);
+                        // Das Hauptfenster zeigt nur den gewählten Sensor
+                        if gui.is_selected_slave(slave) {
+                            gui.update_rreg_sensor_values(&values);
+                            gui.update_rreg_store(&values);
+                        }
+                    }
+                    GuiMessage::UpdateRwregs { slave, values } => {
+                        debug!("Update Rwregs of slave {} with: {:?}", slave, &values);
+                        if gui.is_selected_slave(slave) {
+                            gui.update_rwreg_sensor_values(&values);
+                            gui.update_rwreg_store(&values);
+                        }
+                    }
+                    GuiMessage::ShowScanDialog => {
+                        gui.scan_dialog.show();
+                    }
+                    GuiMessage::ModbusMasterScan { slaves, baud_rates } => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.show_infobar_error("Keine gültige Schnittstelle gewählt");
+                                continue;
+                            }
+                        };
+                        // Live Ansicht beenden, der Scan benötigt die Schnittstelle
+                        gui.toggle_button_connect.set_active(false);
+                        let request = gui.modbus_master.scan(tty_path, slaves, baud_rates);
+                        spawn_request(&gui_tx, request, |gui_tx, result| match result {
+                            Ok(_) => {
+                                let _ = gui_tx.clone().try_send(GuiMessage::ScanStarted);
+                            }
+                            Err(error) => show_warning(
+                                gui_tx,
+                                &format!("Bus Scan konnte nicht gestartet werden:\r\n{}", error),
+                            ),
+                        });
+                    }
+                    GuiMessage::ScanStarted => {
+                        gui.scan_dialog.started();
+                    }
+                    GuiMessage::ModbusMasterCancelScan => {
+                        spawn_request(&gui_tx, gui.modbus_master.cancel_scan(), show_unreachable);
                     }
-                    GuiMessage::UpdateRregs(results) => {
-                        debug!("Update Rregs with: {:?}", &results);
-                        gui.update_rreg_sensor_values(&results);
-                        gui.update_rreg_store(&results);
+                    GuiMessage::UpdateScanProgress { done, total } => {
+                        gui.scan_dialog.update_progress(done, total);
                     }
-                    GuiMessage::UpdateRwregs(results) => {
-                        debug!("Update Rwregs with: {:?}", &results);
-                        gui.update_rwreg_sensor_values(&results);
-                        gui.update_rwreg_store(&results);
+                    GuiMessage::ScanDeviceFound(device) => {
+                        debug!("Scan device found: {:?}", &device);
+                        gui.scan_dialog.add_device(&device);
+                    }
+                    GuiMessage::ScanFinished => {
+                        gui.scan_dialog.finished();
+                    }
+                    GuiMessage::ConnectLine(devices) => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.show_infobar_error("Keine gültige Schnittstelle gewählt");
+                                continue;
+                            }
+                        };
+                        // Die Live Ansicht des Hauptfensters wird ersetzt
+                        gui.toggle_button_connect.set_active(false);
+                        gui.line_dialog.clear();
+                        let mut targets = vec![];
+                        for (slave, name) in devices {
+                            match platine_from_name(&name) {
+                                Ok(platine) => {
+                                    gui.line_dialog.add_slave(slave, &name, platine.rregs());
+                                    targets.push(PollTarget::new(
+                                        slave,
+                                        platine.vec_rregs(),
+                                        platine.vec_rwregs(),
+                                        platine.reg_protection(),
+                                    ));
+                                }
+                                Err(error) => warn!("Slave {}: {}", slave, error),
+                            }
+                        }
+                        if targets.is_empty() {
+                            gui.show_infobar_warning("Kein Sensor mit bekannter Platine gefunden");
+                            continue;
+                        }
+                        gui.line_dialog.show();
+                        let request = gui.modbus_master.connect_line(tty_path, targets);
+                        spawn_request(&gui_tx, request, |gui_tx, result| {
+                            if let Err(error) = result {
+                                let _ = gui_tx
+                                    .clone()
+                                    .try_send(GuiMessage::LineState(error.to_string()));
+                            }
+                        });
+                    }
+                    GuiMessage::StopLine => {
+                        spawn_request(&gui_tx, gui.modbus_master.disconnect(), show_unreachable);
+                    }
+                    GuiMessage::LineState(state) => {
+                        gui.line_dialog.set_state(&state);
+                    }
+                    GuiMessage::ShowTrafficDialog => {
+                        gui.traffic_dialog.show();
+                    }
+                    GuiMessage::SetTrafficRecording(enabled) => {
+                        let request = gui.modbus_master.set_traffic_recording(enabled);
+                        spawn_request(&gui_tx, request, show_unreachable);
+                    }
+                    GuiMessage::Traffic(records) => {
+                        gui.traffic_dialog.add_records(records);
+                    }
+                    GuiMessage::ShowDiagnosticsDialog => {
+                        gui.diagnostics_dialog.show();
+                    }
+                    GuiMessage::CheckAdapter => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.show_infobar_error("Keine gültige Schnittstelle gewählt");
+                                continue;
+                            }
+                        };
+                        if let Err(error) = endpoint::require_serial(&tty_path) {
+                            gui.show_infobar_error(&error.to_string());
+                            continue;
+                        }
+                        if sniffer.is_some() {
+                            gui.show_infobar_warning(
+                                "Während des Mithörens kann der Adapter nicht geprüft werden",
+                            );
+                            continue;
+                        }
+                        // Live Ansicht beenden und Schnittstelle freigeben, die
+                        // Prüfung benötigt die Schnittstelle allein
+                        gui.toggle_button_connect.set_active(false);
+                        spawn_request(
+                            &gui_tx,
+                            gui.modbus_master.disconnect(),
+                            move |gui_tx, result| match result {
+                                Ok(_) => {
+                                    let _ = gui_tx
+                                        .clone()
+                                        .try_send(GuiMessage::RunAdapterCheck(tty_path));
+                                }
+                                Err(error) => show_error(gui_tx, &error.to_string()),
+                            },
+                        );
+                    }
+                    GuiMessage::RunAdapterCheck(tty_path) => {
+                        // Das Mithören kann inzwischen gestartet worden sein
+                        if sniffer.is_some() {
+                            continue;
+                        }
+                        let slave = spin_button_modbus_address.get_value() as u8;
+                        gui.show_infobar_info(&format!(
+                            "Prüfe Adapter {} ...",
+                            gui.port_label().unwrap_or_else(|| tty_path.clone())
+                        ));
+                        let check = adapter_check::run(tty_path, session_line_settings, slave);
+                        let gui_tx = gui_tx.clone();
+                        glib::MainContext::default().spawn_local(async move {
+                            let report = check.await;
+                            let _ = gui_tx.clone().try_send(GuiMessage::AdapterChecked(report));
+                        });
+                    }
+                    GuiMessage::AdapterChecked(report) => {
+                        info!("Adapter check:\n{}", report);
+                        gui.revealer_infobar_info.set_reveal_child(false);
+                        show_adapter_report(&application_window, &report);
+                    }
+                    GuiMessage::ResetStatistics => {
+                        spawn_request(
+                            &gui_tx,
+                            gui.modbus_master.reset_statistics(),
+                            show_unreachable,
+                        );
+                    }
+                    GuiMessage::Statistics(statistics) => {
+                        gui.update_statistics(statistics);
+                    }
+                    GuiMessage::ShowSnifferDialog => {
+                        gui.sniffer_dialog.show();
+                    }
+                    GuiMessage::StartSniffer => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.sniffer_dialog
+                                    .stopped(Some("Keine gültige Schnittstelle gewählt"));
+                                continue;
+                            }
+                        };
+                        // Mithören ist nur an seriellen Schnittstellen möglich
+                        if let Err(error) = endpoint::require_serial(&tty_path) {
+                            gui.sniffer_dialog.stopped(Some(&error.to_string()));
+                            continue;
+                        }
+                        // Live Ansicht beenden und Schnittstelle freigeben, der
+                        // Sniffer benötigt die Schnittstelle allein
+                        gui.toggle_button_connect.set_active(false);
+                        spawn_request(
+                            &gui_tx,
+                            gui.modbus_master.disconnect(),
+                            move |gui_tx, result| {
+                                let message = match result {
+                                    Ok(_) => GuiMessage::RunSniffer(tty_path),
+                                    Err(error) => GuiMessage::SnifferNotStarted(error.to_string()),
+                                };
+                                let _ = gui_tx.clone().try_send(message);
+                            },
+                        );
+                    }
+                    GuiMessage::SnifferNotStarted(error) => {
+                        gui.sniffer_dialog.stopped(Some(&error));
+                    }
+                    GuiMessage::RunSniffer(tty_path) => {
+                        if sniffer.is_some() {
+                            continue;
+                        }
+                        let platine_name = match gui.platine.lock() {
+                            Ok(platine) => {
+                                platine.as_ref().map(|platine| platine.name().to_string())
+                            }
+                            Err(_) => None,
+                        };
+                        sniffer_session += 1;
+                        sniffer_error = None;
+                        let (events_tx, events_rx) = mpsc::unbounded();
+                        sniffer = Some(Sniffer::start(
+                            &tty_path,
+                            session_line_settings,
+                            platine_name,
+                            events_tx,
+                        ));
+                        glib::MainContext::default().spawn_local(forward_sniffer_events(
+                            sniffer_session,
+                            events_rx,
+                            gui_tx.clone(),
+                        ));
+                        gui.sniffer_dialog
+                            .started(&gui.port_label().unwrap_or(tty_path));
+                    }
+                    GuiMessage::StopSniffer => {
+                        // Der Sniffer Thread meldet sich mit `SnifferEvent::Stopped` ab
+                        if let Some(sniffer) = sniffer.as_ref() {
+                            sniffer.stop();
+                        }
+                    }
+                    GuiMessage::Sniffer { session, event } => {
+                        if session != sniffer_session {
+                            continue;
+                        }
+                        match event {
+                            SnifferEvent::Record(record) => gui.sniffer_dialog.add_record(record),
+                            SnifferEvent::Failed(error) => sniffer_error = Some(error),
+                            SnifferEvent::Stopped => {
+                                sniffer = None;
+                                gui.sniffer_dialog.stopped(sniffer_error.take().as_deref());
+                            }
+                        }
+                    }
+                    GuiMessage::ConnectionState {
+                        state,
+                        reason,
+                        last_poll,
+                    } => {
+                        gui.line_dialog.set_state(&format!("{}: {}", state, reason));
+                        gui.update_connection_state(state, &reason, last_poll);
+                    }
+                    GuiMessage::DetectLineSettings => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.show_infobar_error("Keine gültige Schnittstelle gewählt");
+                                continue;
+                            }
+                        };
+                        // Einziger Sensor am Bus? Einen Broadcast beantworten die Sensoren nicht
+                        let slave = match gui.combo_box_text_addressing.get_active_id().as_deref() {
+                            Some("first_responding") | Some("broadcast") => None,
+                            _ => Some(spin_button_modbus_address.get_value() as u8),
+                        };
+                        // Live Ansicht beenden, die Erkennung benötigt die Schnittstelle
+                        gui.toggle_button_connect.set_active(false);
+                        let text = match slave {
+                            Some(slave) => {
+                                format!("Suche Baudrate/ Parität von Modbus Adresse {} ...", slave)
+                            }
+                            None => "Suche Baudrate/ Parität des Sensors am Bus ...".to_string(),
+                        };
+                        let request = gui.modbus_master.detect_line_settings(tty_path, slave);
+                        spawn_request(&gui_tx, request, move |gui_tx, result| match result {
+                            Ok(_) => {
+                                let _ = gui_tx.clone().try_send(GuiMessage::DetectStarted(text));
+                            }
+                            Err(error) => show_warning(
+                                gui_tx,
+                                &format!(
+                                    "Schnittstellenparameter konnten nicht ermittelt werden:\r\n{}",
+                                    error
+                                ),
+                            ),
+                        });
+                    }
+                    GuiMessage::DetectStarted(text) => {
+                        gui.detect_dialog.started(&text);
+                    }
+                    GuiMessage::ModbusMasterCancelDetect => {
+                        spawn_request(&gui_tx, gui.modbus_master.cancel_detect(), show_unreachable);
+                    }
+                    GuiMessage::UpdateDetectProgress { done, total } => {
+                        gui.detect_dialog.update_progress(done, total);
+                    }
+                    GuiMessage::DetectFinished(found) => {
+                        gui.detect_dialog.finished();
+                        if let Some((slave, line_settings)) = found {
+                            let _ = gui_tx.clone().try_send(GuiMessage::LineSettingsDetected {
+                                slave,
+                                line_settings,
+                            });
+                        }
+                    }
+                    GuiMessage::LineSettingsDetected {
+                        slave,
+                        line_settings,
+                    } => {
+                        let dialog = gtk::MessageDialog::new(
+                            Some(&application_window),
+                            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+                            gtk::MessageType::Question,
+                            gtk::ButtonsType::YesNo,
+                            &format!(
+                                "Der Sensor mit Modbus Adresse {} antwortet mit {}.\n\nVerbindung auf diese Einstellungen umstellen?",
+                                slave, line_settings
+                            ),
+                        );
+                        let response = dialog.run();
+                        dialog.close();
+                        if response == gtk::ResponseType::Yes {
+                            spin_button_modbus_address.set_value(slave.into());
+                            let request = gui.modbus_master.set_line_settings(line_settings);
+                            spawn_request(&gui_tx, request, move |gui_tx, result| match result {
+                                Ok(_) => {
+                                    let _ = gui_tx
+                                        .clone()
+                                        .try_send(GuiMessage::LineSettingsChanged(line_settings));
+                                }
+                                Err(error) => show_error(gui_tx, &format!("{}!", error)),
+                            });
+                        }
+                    }
+                    GuiMessage::ShowChangeLineSettings => {
+                        if let Some(line_settings) =
+                            ask_line_settings(&application_window, session_line_settings)
+                        {
+                            let _ = gui_tx
+                                .clone()
+                                .try_send(GuiMessage::ChangeLineSettings(line_settings));
+                        }
+                    }
+                    GuiMessage::ShowAddEndpoint => {
+                        if let Some(endpoint) = ask_endpoint(&application_window) {
+                            gui.add_endpoint(&endpoint.to_string());
+                        }
+                    }
+                    GuiMessage::RemoveEndpoint => match gui.get_tty_path() {
+                        Some(tty_path) => gui.remove_endpoint(&tty_path),
+                        None => gui.show_infobar_error("Keine gültige Schnittstelle gewählt"),
+                    },
+                    GuiMessage::ChangeLineSettings(line_settings) => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.show_infobar_error("Keine gültige Schnittstelle gewählt");
+                                continue;
+                            }
+                        };
+                        let slave = spin_button_modbus_address.get_value() as u8;
+                        let reg_protection = gui.platine_reg_protection();
+                        gui.show_infobar_info(&format!(
+                            "Stelle Modbus Adresse {} auf {} um ...",
+                            slave, line_settings
+                        ));
+                        let request = gui.modbus_master.change_line_settings(
+                            tty_path,
+                            slave,
+                            reg_protection,
+                            line_settings,
+                        );
+                        spawn_request(&gui_tx, request, move |gui_tx, result| match result {
+                            Ok(_) => {
+                                let _ = gui_tx
+                                    .clone()
+                                    .try_send(GuiMessage::LineSettingsChanged(line_settings));
+                            }
+                            Err(error @ ModbusMasterError::LineSettingsLost { .. }) => {
+                                show_error(gui_tx, &error.to_string())
+                            }
+                            Err(error) => show_warning(
+                                gui_tx,
+                                &format!("Schnittstellenparameter nicht geändert:\r\n{}", error),
+                            ),
+                        });
+                    }
+                    GuiMessage::LineSettingsChanged(line_settings) => {
+                        session_line_settings = line_settings;
+                        // Löst `SetTransmissionMode` aus, das nun nichts mehr ändert
+                        gui.check_menu_item_ascii
+                            .set_active(line_settings.transmission_mode == TransmissionMode::Ascii);
+                        gui.show_infobar_info(&format!(
+                            "Schnittstellenparameter: <b>{}</b>",
+                            line_settings
+                        ));
+                        gui.log_status(
+                            StatusBarContext::PortOperation,
+                            &format!("Schnittstellenparameter: {}", line_settings),
+                        );
+                    }
+                    GuiMessage::SetTransmissionMode(transmission_mode) => {
+                        // Auch beim Abgleich des Menüs nach der Erkennung
+                        if transmission_mode == session_line_settings.transmission_mode {
+                            continue;
+                        }
+                        let line_settings = LineSettings {
+                            transmission_mode,
+                            ..session_line_settings
+                        };
+                        let request = gui.modbus_master.set_line_settings(line_settings);
+                        spawn_request(&gui_tx, request, move |gui_tx, result| {
+                            let message = match result {
+                                Ok(_) => GuiMessage::LineSettingsChanged(line_settings),
+                                Err(error) => {
+                                    GuiMessage::TransmissionModeRejected(format!("{}!", error))
+                                }
+                            };
+                            let _ = gui_tx.clone().try_send(message);
+                        });
+                    }
+                    GuiMessage::TransmissionModeRejected(error) => {
+                        // Menü wieder auf die Übertragungsart der Sitzung setzen
+                        gui.check_menu_item_ascii.set_active(
+                            session_line_settings.transmission_mode == TransmissionMode::Ascii,
+                        );
+                        gui.show_infobar_error(&error);
+                    }
+                    GuiMessage::RestartSensor { factory_reset } => {
+                        let tty_path = match gui.get_tty_path() {
+                            Some(tty_path) => tty_path,
+                            None => {
+                                gui.show_infobar_error("Keine gültige Schnittstelle gewählt");
+                                continue;
+                            }
+                        };
+                        let slave = spin_button_modbus_address.get_value() as u8;
+                        if !confirm_restart(&application_window, slave, factory_reset) {
+                            continue;
+                        }
+                        let reg_protection = gui.platine_reg_protection();
+                        let code = gui.platine_restart_code(factory_reset);
+                        let request =
+                            gui.modbus_master
+                                .restart(tty_path, slave, reg_protection, code);
+                        spawn_request(&gui_tx, request, move |gui_tx, result| {
+                            show_restart_result(gui_tx, slave, factory_reset, result)
+                        });
+                    }
+                    GuiMessage::SelectScanDevice {
+                        slave,
+                        platine: platine_name,
+                    } => {
+                        spin_button_modbus_address.set_value(slave.into());
+                        if let Some((id, _name, _desc)) = platine::HW_VERSIONS
+                            .iter()
+                            .find(|(_id, name, _desc)| *name == platine_name)
+                        {
+                            gui.combo_box_text_hw_version
+                                .set_active_id(Some(&id.to_string()));
+                        }
                     }
                 }
             }
@@ -1443,6 +2246,59 @@ fn ui_init(app: &gtk::Application) {
 }
 
 impl Gui {
+    /// Zeigt den Zustand der Verbindung in der Statusleiste an
+    ///
+    /// Sind die angezeigten Werte veraltet werden sie ausgegraut. Ist die
+    /// Verbindung verloren, z.B. weil der Adapter abgezogen wurde, wird die
+    /// Live Ansicht beendet.
+    fn update_connection_state(
+        &self,
+        state: ConnectionState,
+        reason: &str,
+        last_poll: Option<DateTime<Local>>,
+    ) {
+        let mut message = format!("Live Ansicht: {} ({})", state, reason);
+        match state {
+            ConnectionState::Connecting | ConnectionState::Online => {
+                self.set_values_stale(false);
+            }
+            ConnectionState::Degraded | ConnectionState::Lost => {
+                if let Some(last_poll) = last_poll {
+                    message.push_str(&format!(
+                        ", letzte Werte von {}",
+                        last_poll.format("%H:%M:%S")
+                    ));
+                }
+                self.set_values_stale(true);
+            }
+            ConnectionState::Offline => {}
+        }
+        self.log_status(StatusBarContext::PortOperation, &message);
+
+        if state == ConnectionState::Lost {
+            self.show_infobar_warning(&format!(
+                "Verbindung verloren, Live Ansicht beendet:\r\n{}",
+                reason
+            ));
+            // Der Callback des Buttons beendet die Live Ansicht
+            self.toggle_button_connect.set_active(false);
+        }
+    }
+
+    /// Graut die angezeigten Werte aus oder hebt das Ausgrauen auf
+    fn set_values_stale(&self, stale: bool) {
+        self.label_sensor_value_value.set_sensitive(!stale);
+        self.label_sensor1_value_value.set_sensitive(!stale);
+        self.label_sensor1_value_si.set_sensitive(!stale);
+        self.label_sensor2_value_value.set_sensitive(!stale);
+        // Die Register Tabellen, die erste Seite enthält die Bedienelemente
+        for page in 1..self.notebook_sensor.get_n_pages() {
+            if let Some(child) = self.notebook_sensor.get_nth_page(Some(page)) {
+                child.set_sensitive(!stale);
+            }
+        }
+    }
+
     /// Disable UI elements
     ///
     /// Helper function disable User Interface elements
@@ -1461,6 +2317,7 @@ impl Gui {
             self.button_new_modbus_address.set_sensitive(false);
             self.button_sensor_working_mode.set_sensitive(false);
             self.check_button_mcs.set_sensitive(false);
+            self.combo_box_text_addressing.set_sensitive(false);
             self.combo_box_text_sensor_working_mode.set_sensitive(false);
             self.spin_button_new_modbus_address.set_sensitive(false);
         }
@@ -1486,6 +2343,7 @@ impl Gui {
             self.button_new_modbus_address.set_sensitive(true);
             self.button_sensor_working_mode.set_sensitive(true);
             self.check_button_mcs.set_sensitive(true);
+            self.combo_box_text_addressing.set_sensitive(true);
             self.combo_box_text_sensor_working_mode.set_sensitive(true);
             self.spin_button_new_modbus_address.set_sensitive(true);
         }
@@ -1505,6 +2363,7 @@ impl Gui {
             &self.combo_box_text_ports,
             &self.combo_box_text_ports_changed_signal,
         );
+        self.port_selected();
         // activate combo field and connect button
         self.combo_box_text_ports.set_sensitive(true);
         if let Ok(platine) = &self.platine.lock() {
@@ -1514,19 +2373,92 @@ impl Gui {
         }
     }
 
-    #[allow(clippy::comparison_chain)]
+    // Übernimmt die gewählte Schnittstelle, mit dem Namen des Adapters, für die Exporte
+    fn port_selected(&self) {
+        let port = self.port_label();
+        debug!("Selected port: {:?}", port);
+        self.traffic_dialog.set_port(port.clone());
+        self.diagnostics_dialog.set_port(port);
+    }
+
+    /// Anzeige der gewählten Schnittstelle, mit dem Namen des Adapters
+    fn port_label(&self) -> Option<String> {
+        let tty_path = self.get_tty_path()?;
+        let label = self
+            .serial_ports
+            .borrow()
+            .iter()
+            .find(|port| port.path == tty_path)
+            .map(PortInfo::to_string);
+        Some(label.unwrap_or(tty_path))
+    }
+
+    /// Fügt eine Schnittstelle hinzu und speichert sie in den Einstellungen
+    fn add_endpoint(&self, endpoint: &str) {
+        if let Err(error) = Settings::add_endpoint(endpoint) {
+            self.show_infobar_error(&format!(
+                "Schnittstelle konnte nicht gespeichert werden: {}",
+                error
+            ));
+            return;
+        }
+        let mut endpoints = self.serial_interface.endpoints();
+        if !endpoints.iter().any(|e| e == endpoint) {
+            endpoints.push(endpoint.to_string());
+        }
+        self.serial_interface.set_endpoints(endpoints);
+        self.show_infobar_info(&format!("Schnittstelle {} hinzugefügt", endpoint));
+    }
+
+    /// Entfernt eine von Hand hinzugefügte Schnittstelle
+    fn remove_endpoint(&self, endpoint: &str) {
+        let mut endpoints = self.serial_interface.endpoints();
+        if !endpoints.iter().any(|e| e == endpoint) {
+            self.show_infobar_warning(&format!(
+                "{} wurde nicht von Hand hinzugefügt und kann nicht entfernt werden",
+                endpoint
+            ));
+            return;
+        }
+        if let Err(error) = Settings::remove_endpoint(endpoint) {
+            self.show_infobar_error(&format!(
+                "Schnittstelle konnte nicht entfernt werden: {}",
+                error
+            ));
+            return;
+        }
+        endpoints.retain(|e| e != endpoint);
+        self.serial_interface.set_endpoints(endpoints);
+        self.show_infobar_info(&format!("Schnittstelle {} entfernt", endpoint));
+    }
+
     /// Update verfügbare serielle Schnittstellen
     ///
-    /// Diese Funktion wird unter Anderem vom `SerialThread` aufgerufen wenn
-    /// sich die Anzahl der gefunden seriellen Schnittstellen ändert.
-    fn update_serial_ports(&self, ports: Vec<String>) {
+    /// Diese Funktion wird vom `SerialInterface` Thread aufgerufen wenn
+    /// Schnittstellen hinzugekommen oder entfernt worden sind. Angezeigt werden
+    /// die Schnittstellen mit den Angaben des USB Adapters.
+    fn update_serial_ports(
+        &self,
+        ports: Vec<PortInfo>,
+        added: Vec<PortInfo>,
+        removed: Vec<PortInfo>,
+    ) {
         debug!(
-            "ports: {:?}, active port: {:?}",
+            "ports: {:?}, added: {:?}, removed: {:?}, active port: {:?}",
             &ports,
+            &added,
+            &removed,
             self.combo_box_text_ports.get_active()
         );
-        let active_port = self.combo_box_text_ports.get_active().unwrap_or(0);
-        let old_num_ports = self.combo_box_text_ports_map.borrow().len();
+        // Die bisher gewählte Schnittstelle mit den Angaben ihres Adapters
+        let active_port = self.get_tty_path().and_then(|tty_path| {
+            self.serial_ports
+                .borrow()
+                .iter()
+                .find(|port| port.path == tty_path)
+                .cloned()
+        });
+        self.serial_ports.replace(ports.clone());
         // Update the port listing and other UI elements
         self.combo_box_text_ports.remove_all();
         self.combo_box_text_ports_map.borrow_mut().clear();
@@ -1537,73 +2469,105 @@ impl Gui {
                 .append(None, "Keine Schnittstelle gefunden");
             self.combo_box_text_ports.set_active(Some(0));
 
-            let _ = self
-                .modbus_master_tx
-                .clone()
-                .try_send(ModbusMasterMessage::Disconnect);
+            // Die Anfrage ist bereits gesendet, auf das Ergebnis wird nicht gewartet
+            glib::MainContext::default().spawn_local(self.modbus_master.disconnect().map(|_| ()));
             self.toggle_button_connect.set_active(false);
             self.toggle_button_connect.set_sensitive(false);
 
             // Disable UI elements
             self.disable_ui_elements();
-        // one or more serial ports found
-        } else {
-            for (i, p) in (0u32..).zip(ports.clone().into_iter()) {
-                self.combo_box_text_ports.append(None, &p);
-                self.combo_box_text_ports_map.borrow_mut().insert(p, i);
-            }
-            let num_ports = self.combo_box_text_ports_map.borrow().len();
-            // lost one or more serial ports
-            if num_ports < old_num_ports {
-                debug!(
-                    "lost one or more serial ports: active_port:{:?}, num_ports:{:?}, old_num_ports:{:?}",
-                    active_port, num_ports, old_num_ports
-                );
-                // Restore selected serial interface
-                let active_port = if active_port > 0 {
-                    active_port - 1
-                } else {
-                    active_port
-                };
-                self.select_port(active_port);
+            return;
+        }
 
-                // Statusbar message
-                self.log_status(
-                    StatusBarContext::PortOperation,
-                    &format!(
-                        "Schnittstelle verloren! Aktuelle Schnittstellen: {:?}",
-                        ports
-                    ),
-                );
-            // Additional serial port found
-            } else if num_ports > old_num_ports {
-                debug!(
-                    "Port gefunden: active_port:{:?} num_ports:{:?} old_num_ports:{:?}",
-                    active_port, num_ports, old_num_ports
-                );
-                // Enable UI elements
-                self.enable_ui_elements();
+        for (i, port) in (0u32..).zip(ports.iter()) {
+            self.combo_box_text_ports
+                .append(None, &self.port_entry(port));
+            self.combo_box_text_ports_map
+                .borrow_mut()
+                .insert(port.path.clone(), i);
+        }
+        // Bevorzugte Adapter werden auch unter einem neuen Pfad gefunden
+        let selected = select_port(
+            &ports,
+            &added,
+            active_port.as_ref(),
+            &self.settings.favourite_adapters,
+        )
+        .unwrap_or(0) as u32;
+
+        // lost one or more serial ports
+        if !removed.is_empty() {
+            debug!("lost serial ports: {:?}", &removed);
+            // Statusbar message
+            self.log_status(
+                StatusBarContext::PortOperation,
+                &format!("Schnittstelle verloren: {}", port_labels(&removed)),
+            );
+        }
+        // Additional serial port found
+        if !added.is_empty() {
+            debug!("found serial ports: {:?}", &added);
+            // Enable UI elements
+            self.enable_ui_elements();
+
+            // Statusbar message
+            self.log_status(
+                StatusBarContext::PortOperation,
+                &format!("Neue Schnittstelle gefunden: {}", port_labels(&added)),
+            );
+        }
+        self.select_port(selected);
+    }
 
-                // Restore selected serial interface
-                self.select_port(num_ports as u32 - 1);
+    /// Zeigt an welche Schnittstellen von anderen Programmen gesperrt sind
+    ///
+    /// Die Einträge im Auswahlfeld werden neu geschrieben, die Auswahl bleibt.
+    fn update_busy_ports(&self, busy_ports: BTreeMap<String, LockHolder>) {
+        self.busy_ports.replace(busy_ports);
+        let ports = self.serial_ports.borrow();
+        if ports.is_empty() {
+            return;
+        }
+        let active = self.combo_box_text_ports.get_active();
+        signal::signal_handler_block(
+            &self.combo_box_text_ports,
+            &self.combo_box_text_ports_changed_signal,
+        );
+        self.combo_box_text_ports.remove_all();
+        for port in ports.iter() {
+            self.combo_box_text_ports
+                .append(None, &self.port_entry(port));
+        }
+        self.combo_box_text_ports.set_active(active);
+        signal::signal_handler_unblock(
+            &self.combo_box_text_ports,
+            &self.combo_box_text_ports_changed_signal,
+        );
+    }
 
-                // Statusbar message
-                self.log_status(
-                    StatusBarContext::PortOperation,
-                    &format!("Neue Schnittstelle gefunden: {:?}", ports),
-                );
-            // same serial ports as last time
-            } else if num_ports == old_num_ports {
-                debug!(
-                    "no new serial ports found: active_port:{:?} num_ports:{:?} old_num_ports:{:?}",
-                    active_port, num_ports, old_num_ports
-                );
-                // Restore selected serial interface
-                self.select_port(active_port);
-            }
+    // Eintrag im Auswahlfeld, gesperrte Schnittstellen mit dem Inhaber der Sperre
+    fn port_entry(&self, port: &PortInfo) -> String {
+        match self.busy_ports.borrow().get(&port.path) {
+            Some(holder) => format!("{} (belegt von {})", port, holder),
+            None => port.to_string(),
         }
     }
 
+    /// Zeigt die Statistik der Kommunikation an
+    ///
+    /// Die Zusammenfassung steht rechts in der Statusleiste, die Zähler pro
+    /// Slave im Diagnose Dialog. Der Mitschnitt übernimmt die Statistik für
+    /// seinen CSV Export.
+    fn update_statistics(&self, statistics: Statistics) {
+        if statistics.is_empty() {
+            self.label_statistics.set_text("");
+        } else {
+            self.label_statistics.set_text(&statistics.to_string());
+        }
+        self.traffic_dialog.set_statistics(statistics.clone());
+        self.diagnostics_dialog.update(statistics);
+    }
+
     /// Zeigt Status Nachrichten am unteren Bildschirmrand
     ///
     /// # Parameters
@@ -1670,52 +2634,52 @@ impl Gui {
                 match platine.name() {
                 "Sensor-MB-CO2_O2_REV1_0" => {
                     // Update Konzentration Messzelle 1
-                    if let Some((_, value)) = result.get(2) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 2) {
                         self.label_sensor1_value_value.set_text(&value.to_string());
                     }
                     // Update Konzentration Messzelle 2
-                    if let Some((_, value)) = result.get(6) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 6) {
                         self.label_sensor2_value_value.set_text(&(value * 10).to_string());
                     }
                 },
                 "Sensor-MB-NAP5x_REV1_0" => {
                     // Update Konzentration Messzelle 1
-                    if let Some((_, value)) = result.get(2) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 2) {
                         self.label_sensor_value_value.set_text(&value.to_string());
                     }
                 },
                 "Sensor-MB-NAP5xx_REV1_0" => {
                     // Update Konzentration Messzelle 1
-                    if let Some((_, value)) = result.get(2) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 2) {
                         self.label_sensor1_value_value.set_text(&value.to_string());
                     }
                     // Update Konzentration Messzelle 2
-                    if let Some((_, value)) = result.get(6) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 6) {
                         self.label_sensor2_value_value.set_text(&value.to_string());
                     }
                 },
                 "Sensor-MB-NE4_REV1_0" => {
                     // Update Konzentration Messzelle 1
-                    if let Some((_, value)) = result.get(2) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 2) {
                         self.label_sensor_value_value.set_text(&value.to_string());
                     }
                 },
                 "Sensor-MB-NE4-V1.0" => {
                     // Update Konzentration Messzelle 1
-                    if let Some((_, value)) = result.get(2) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 2) {
                         self.label_sensor_value_value.set_text(&value.to_string());
                     }
                 },
                 "Sensor-MB-SP42A_REV1_0" => {
                     // Update Konzentration Messzelle 1
-                    if let Some((_, value)) = result.get(2) {
+                    if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 2) {
                         self.label_sensor_value_value.set_text(&value.to_string());
                     }
                 },
                 _ => self.show_infobar_error("Nicht unterstützte Platine, Sensorwerte konnten nicht aktualisiert werden."),
                 };
                 // Update Arbeitsweise
-                if let Some((_, value)) = result.get(1) {
+                if let Some((_, value)) = result.iter().find(|(reg, _)| *reg == 1) {
                     self.combo_box_text_sensor_working_mode
                         .set_active_id(Some(&format!("{}", value)));
                 }
@@ -1814,6 +2778,24 @@ impl Gui {
         reg_protection
     }
 
+    /// Neustart bzw. Grunddaten Code der Platine
+    fn platine_restart_code(&self, factory_reset: bool) -> u16 {
+        let platine = match self.platine.lock() {
+            Ok(platine) => platine,
+            Err(_) => return default_restart_code(factory_reset),
+        };
+        match platine.as_ref() {
+            Some(platine) if factory_reset => platine.factory_reset_code(),
+            Some(platine) => platine.restart_code(),
+            None => default_restart_code(factory_reset),
+        }
+    }
+
+    /// Ist `slave` der im Hauptfenster gewählte Sensor?
+    fn is_selected_slave(&self, slave: u8) -> bool {
+        self.spin_button_modbus_address.get_value() as u8 == slave
+    }
+
     /// Liefert die Schnittstelle in einem Result
     fn get_tty_path(&self) -> Option<String> {
         // tty path
@@ -1830,6 +2812,24 @@ impl Gui {
     }
 } // Ende Gui Implementation
 
+// Schnittstellen für die Statusleiste, durch Komma getrennt
+fn port_labels(ports: &[PortInfo]) -> String {
+    ports
+        .iter()
+        .map(PortInfo::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Neustart bzw. Grunddaten Code wenn keine Platine gewählt ist
+fn default_restart_code(factory_reset: bool) -> u16 {
+    if factory_reset {
+        platine::DEFAULT_FACTORY_RESET_CODE
+    } else {
+        platine::DEFAULT_RESTART_CODE
+    }
+}
+
 // Lösche Notebook alle bis auf den ersten Tab
 //
 // Diese Funktion löscht so lange die Tabs bis nur noch einer
@@ -1888,6 +2888,170 @@ pub fn set_rwreg_store(
     }
 }
 
+/// Wartet im Main Thread auf das Ergebnis einer Anfrage an den Modbus Master
+///
+/// Die Anfrage ist beim Aufruf bereits gesendet. Der Callback wird mit dem
+/// Ergebnis aufgerufen sobald der Modbus Master geantwortet hat.
+pub fn spawn_request<T, F>(
+    gui_tx: &mpsc::Sender<GuiMessage>,
+    request: impl Future<Output = Result<T, ModbusMasterError>> + 'static,
+    callback: F,
+) where
+    T: 'static,
+    F: FnOnce(&mpsc::Sender<GuiMessage>, Result<T, ModbusMasterError>) + 'static,
+{
+    let gui_tx = gui_tx.clone();
+    glib::MainContext::default().spawn_local(async move {
+        let result = request.await;
+        callback(&gui_tx, result);
+    });
+}
+
+// Callback für `spawn_request`, zeigt nur an wenn der Modbus Master nicht erreichbar ist
+fn show_unreachable(gui_tx: &mpsc::Sender<GuiMessage>, result: Result<(), ModbusMasterError>) {
+    if let Err(error) = result {
+        show_error(gui_tx, &format!("{}!", error));
+    }
+}
+
+// Callback für `spawn_request`, Ergebnis Nullgas
+fn show_nullgas_result(gui_tx: &mpsc::Sender<GuiMessage>, result: Result<(), ModbusMasterError>) {
+    match result {
+        Ok(_) => show_info(gui_tx, "Nullpunkt erfolgreich gesetzt"),
+        Err(error) => show_error(
+            gui_tx,
+            &format!("Nullgas konnte nicht gesetzt werden: {}", error),
+        ),
+    }
+}
+
+// Callback für `spawn_request`, Ergebnis Messgas
+fn show_messgas_result(gui_tx: &mpsc::Sender<GuiMessage>, result: Result<(), ModbusMasterError>) {
+    match result {
+        Ok(_) => show_info(gui_tx, "Endwert Messgas erfolgreich gesetzt"),
+        Err(error) => show_error(
+            gui_tx,
+            &format!("Messgas konnte nicht gesetzt werden: {}", error),
+        ),
+    }
+}
+
+// Callback für `spawn_request`, Ergebnis Neustart
+fn show_restart_result(
+    gui_tx: &mpsc::Sender<GuiMessage>,
+    slave: u8,
+    factory_reset: bool,
+    result: Result<(), ModbusMasterError>,
+) {
+    match result {
+        Ok(_) if factory_reset => show_warning(gui_tx, &format!(
+            "Sensor mit Modbus Adresse {} lädt die Grunddaten und startet neu.\r\nModbus Adresse und Schnittstellenparameter prüfen!",
+            slave
+        )),
+        Ok(_) => show_info(
+            gui_tx,
+            &format!("Sensor mit Modbus Adresse {} startet neu", slave),
+        ),
+        Err(error) => show_error(
+            gui_tx,
+            &format!("Sensor konnte nicht neu gestartet werden:\r\n{}", error),
+        ),
+    }
+}
+
+// Leitet die Ereignisse des Sniffers als `GuiMessage` an die GUI weiter
+async fn forward_sniffer_events(
+    session: u32,
+    mut events: UnboundedReceiver<SnifferEvent>,
+    mut gui_tx: mpsc::Sender<GuiMessage>,
+) {
+    use futures::{sink::SinkExt, stream::StreamExt};
+
+    while let Some(event) = events.next().await {
+        if gui_tx
+            .send(GuiMessage::Sniffer { session, event })
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+// Leitet die Ereignisse des Modbus Masters als `GuiMessage` an die GUI weiter
+async fn forward_modbus_master_events(
+    mut events: UnboundedReceiver<ModbusMasterEvent>,
+    mut gui_tx: mpsc::Sender<GuiMessage>,
+) {
+    use futures::{sink::SinkExt, stream::StreamExt};
+
+    while let Some(event) = events.next().await {
+        let message = match event {
+            ModbusMasterEvent::Rregs { slave, values } => GuiMessage::UpdateRregs { slave, values },
+            ModbusMasterEvent::Rwregs { slave, values } => {
+                GuiMessage::UpdateRwregs { slave, values }
+            }
+            ModbusMasterEvent::ConnectionState {
+                state,
+                reason,
+                last_poll,
+            } => GuiMessage::ConnectionState {
+                state,
+                reason,
+                last_poll,
+            },
+            ModbusMasterEvent::ScanProgress { done, total } => {
+                GuiMessage::UpdateScanProgress { done, total }
+            }
+            ModbusMasterEvent::ScanDeviceFound(device) => GuiMessage::ScanDeviceFound(device),
+            ModbusMasterEvent::ScanFinished { found, cancelled } => {
+                if cancelled {
+                    show_info(
+                        &gui_tx,
+                        &format!("Bus Scan abgebrochen, {} Sensor(en) gefunden", found),
+                    );
+                } else {
+                    show_info(
+                        &gui_tx,
+                        &format!("Bus Scan beendet, {} Sensor(en) gefunden", found),
+                    );
+                }
+                GuiMessage::ScanFinished
+            }
+            ModbusMasterEvent::ScanFailed(error) => {
+                show_warning(&gui_tx, &format!("Bus Scan fehlgeschlagen:\r\n{}", error));
+                GuiMessage::ScanFinished
+            }
+            ModbusMasterEvent::DetectProgress { done, total } => {
+                GuiMessage::UpdateDetectProgress { done, total }
+            }
+            ModbusMasterEvent::DetectFinished { found, cancelled } => {
+                if cancelled {
+                    show_info(&gui_tx, "Erkennung der Baudrate/ Parität abgebrochen");
+                } else if found.is_none() {
+                    show_warning(&gui_tx, "Kein Sensor antwortet mit einer Baudrate/ Parität");
+                }
+                GuiMessage::DetectFinished(found)
+            }
+            ModbusMasterEvent::DetectFailed(error) => {
+                show_warning(
+                    &gui_tx,
+                    &format!(
+                        "Schnittstellenparameter konnten nicht ermittelt werden:\r\n{}",
+                        error
+                    ),
+                );
+                GuiMessage::DetectFinished(None)
+            }
+            ModbusMasterEvent::Traffic(records) => GuiMessage::Traffic(records),
+            ModbusMasterEvent::Statistics(statistics) => GuiMessage::Statistics(statistics),
+        };
+        if gui_tx.send(message).await.is_err() {
+            break;
+        }
+    }
+}
+
 /// Info Infobar für Aufruf in Callbacks
 ///
 /// In den Callbacks steht die Ui Struktur noch nicht zur Verfügung. So dass
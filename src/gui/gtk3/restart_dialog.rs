@@ -0,0 +1,66 @@
+use glib::clone;
+use gtk::prelude::*;
+
+/// Lässt den Benutzer den Neustart eines Sensors bestätigen
+///
+/// Der Neustart muss ausdrücklich bestätigt werden, vorausgewählt ist
+/// "Abbrechen". Beim Laden der Grunddaten gehen alle Einstellungen des Sensors
+/// verloren, hier muss der Benutzer zusätzlich die Modbus Adresse des Sensors
+/// eintippen bevor er bestätigen kann.
+pub fn confirm_restart(parent: &gtk::ApplicationWindow, slave: u8, factory_reset: bool) -> bool {
+    let (title, button, message) = if factory_reset {
+        (
+            "Grunddaten laden",
+            "Grunddaten laden",
+            format!(
+                "Der Sensor mit Modbus Adresse {} lädt die Grunddaten und startet neu.\n\nAlle Einstellungen, auch Kalibrierung, Modbus Adresse und\nSchnittstellenparameter, werden auf die Werkseinstellungen zurückgesetzt!\n\nZur Bestätigung die Modbus Adresse des Sensors eingeben:",
+                slave
+            ),
+        )
+    } else {
+        (
+            "Sensor neu starten",
+            "Neu starten",
+            format!(
+                "Der Sensor mit Modbus Adresse {} startet neu.\n\nWährend des Neustarts liefert der Sensor keine Messwerte,\ndie Live Ansicht verbindet sich danach erneut.",
+                slave
+            ),
+        )
+    };
+
+    let dialog = gtk::Dialog::with_buttons(
+        Some(title),
+        Some(parent),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[
+            ("Abbrechen", gtk::ResponseType::Cancel),
+            (button, gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Cancel);
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+
+    let label = gtk::Label::new(Some(&message));
+    label.set_xalign(0.0);
+    content_area.pack_start(&label, false, true, 0);
+
+    if factory_reset {
+        dialog.set_response_sensitive(gtk::ResponseType::Accept, false);
+        let entry = gtk::Entry::new();
+        entry.connect_changed(clone!(
+            @weak dialog
+            => move |entry| {
+                let confirmed = entry.get_text().trim() == slave.to_string();
+                dialog.set_response_sensitive(gtk::ResponseType::Accept, confirmed);
+            }
+        ));
+        content_area.pack_start(&entry, false, true, 0);
+    }
+
+    dialog.show_all();
+    let response = dialog.run();
+    dialog.close();
+
+    response == gtk::ResponseType::Accept
+}
@@ -105,11 +105,22 @@ impl RregStore {
     }
 
     /// Update TreeStore
+    ///
+    /// Es werden nur die Zeilen der übergebenen Register aktualisiert, der
+    /// Modbus Master sendet nur geänderte Werte.
     pub fn update_treestore(&self, values: &[(u16, u16)]) {
         if let Some(iter) = self.store.get_iter_first() {
-            for (_reg_nr, value) in values {
-                self.store.set_value(&iter, 2, &(*value as u32).to_value());
-                self.store.iter_next(&iter);
+            loop {
+                let reg_nr = self.store.get_value(&iter, 0).get_some::<u32>().ok();
+                if let Some((_, value)) = values
+                    .iter()
+                    .find(|(reg, _)| Some(u32::from(*reg)) == reg_nr)
+                {
+                    self.store.set_value(&iter, 2, &(*value as u32).to_value());
+                }
+                if !self.store.iter_next(&iter) {
+                    break;
+                }
             }
         }
     }
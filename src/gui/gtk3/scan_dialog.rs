@@ -0,0 +1,279 @@
+use crate::{
+    gui::gtk3::GuiMessage,
+    modbus_master::{scanner::BAUD_RATES, BusDevice},
+    platine,
+};
+use futures::channel::mpsc::Sender;
+use glib::clone;
+use gtk::prelude::*;
+
+// Anzeige einer nicht erkannten Platine
+const UNKNOWN_PLATINE: &str = "unbekannt";
+
+/// Dialog für den Bus Scan
+///
+/// Der Dialog wird nicht über die Glade Datei erstellt, sondern hier im Code.
+/// Start und Abbruch werden als `GuiMessage` an die GUI gesendet, die daraus
+/// die Nachrichten an den Modbus Master bildet.
+#[derive(Debug)]
+pub struct ScanDialog {
+    dialog: gtk::Dialog,
+    progress_bar: gtk::ProgressBar,
+    store: gtk::ListStore,
+    button_start: gtk::Button,
+    button_cancel: gtk::Button,
+}
+
+impl ScanDialog {
+    /// Erstellt den Dialog
+    pub fn new(parent: &gtk::ApplicationWindow, gui_tx: &Sender<GuiMessage>) -> Self {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Bus Scan"),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Schließen", gtk::ResponseType::Close)],
+        );
+        dialog.set_default_size(600, 400);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+
+        // Adressbereich
+        let hbox_slaves = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let spin_button_from = gtk::SpinButton::with_range(1.0, 247.0, 1.0);
+        let spin_button_to = gtk::SpinButton::with_range(1.0, 247.0, 1.0);
+        spin_button_to.set_value(247.0);
+        hbox_slaves.pack_start(
+            &gtk::Label::new(Some("Modbus Adressen von")),
+            false,
+            true,
+            0,
+        );
+        hbox_slaves.pack_start(&spin_button_from, false, true, 0);
+        hbox_slaves.pack_start(&gtk::Label::new(Some("bis")), false, true, 0);
+        hbox_slaves.pack_start(&spin_button_to, false, true, 0);
+        content_area.pack_start(&hbox_slaves, false, true, 0);
+
+        // Baudraten
+        let hbox_baud_rates = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        hbox_baud_rates.pack_start(&gtk::Label::new(Some("Baudraten")), false, true, 0);
+        let mut check_buttons_baud_rate = vec![];
+        for (_value, baud_rate) in BAUD_RATES {
+            let check_button = gtk::CheckButton::with_label(&baud_rate.to_string());
+            check_button.set_active(*baud_rate == 9600);
+            hbox_baud_rates.pack_start(&check_button, false, true, 0);
+            check_buttons_baud_rate.push((*baud_rate, check_button));
+        }
+        content_area.pack_start(&hbox_baud_rates, false, true, 0);
+
+        // Start/ Abbruch
+        let hbox_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let button_start = gtk::Button::with_label("Scan starten");
+        button_start
+            .get_style_context()
+            .add_class("suggested-action");
+        let button_cancel = gtk::Button::with_label("Abbrechen");
+        button_cancel.set_sensitive(false);
+        let button_line = gtk::Button::with_label("Live Ansicht aller Sensoren");
+        button_line.set_tooltip_text(Some(
+            "Liest alle gefundenen Sensoren mit bekannter Platine abwechselnd",
+        ));
+        hbox_buttons.pack_start(&button_line, false, true, 0);
+        hbox_buttons.pack_end(&button_cancel, false, true, 0);
+        hbox_buttons.pack_end(&button_start, false, true, 0);
+        content_area.pack_start(&hbox_buttons, false, true, 0);
+
+        let progress_bar = gtk::ProgressBar::new();
+        progress_bar.set_show_text(true);
+        content_area.pack_start(&progress_bar, false, true, 0);
+
+        // Gefundene Sensoren
+        let store = gtk::ListStore::new(&[
+            // Modbus Adresse
+            glib::Type::U32,
+            // Baudrate
+            glib::Type::U32,
+            // Platine
+            glib::Type::String,
+            // Arbeitsweise
+            glib::Type::U32,
+            // Softwaredatum
+            glib::Type::String,
+        ]);
+        let treeview = gtk::TreeView::with_model(&store);
+        treeview.set_grid_lines(gtk::TreeViewGridLines::Horizontal);
+        for (i, title) in [
+            "Modbus Adresse",
+            "Baudrate",
+            "Platine",
+            "Arbeitsweise",
+            "Softwaredatum",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            treeview.append_column(&column);
+        }
+        treeview.set_tooltip_text(Some(
+            "Doppelklick übernimmt Modbus Adresse und Platine des Sensors",
+        ));
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.add(&treeview);
+        content_area.pack_start(&scrolled_window, true, true, 0);
+
+        //
+        // Callbacks
+        //
+
+        // Callback: Button "Scan starten"
+        button_start.connect_clicked(clone!(
+            @strong gui_tx,
+            @strong progress_bar,
+            @strong spin_button_from,
+            @strong spin_button_to,
+            @strong store
+            => move |_| {
+                let from = spin_button_from.get_value() as u8;
+                let to = spin_button_to.get_value() as u8;
+                let baud_rates: Vec<i32> = check_buttons_baud_rate
+                    .iter()
+                    .filter(|(_baud_rate, check_button)| check_button.get_active())
+                    .map(|(baud_rate, _check_button)| *baud_rate)
+                    .collect();
+                if from > to || baud_rates.is_empty() {
+                    progress_bar.set_text(Some("Bitte Adressbereich und Baudrate wählen!"));
+                    return;
+                }
+
+                store.clear();
+                progress_bar.set_fraction(0.0);
+                progress_bar.set_text(None);
+                let _ = gui_tx.clone().try_send(GuiMessage::ModbusMasterScan {
+                    slaves: from..=to,
+                    baud_rates,
+                });
+            }
+        ));
+
+        // Callback: Button "Abbrechen"
+        button_cancel.connect_clicked(clone!(
+            @strong gui_tx
+            => move |_| {
+                let _ = gui_tx.clone().try_send(GuiMessage::ModbusMasterCancelScan);
+            }
+        ));
+
+        // Callback: Button "Live Ansicht aller Sensoren"
+        button_line.connect_clicked(clone!(
+            @strong gui_tx,
+            @strong store
+            => move |_| {
+                let mut devices: Vec<(u8, String)> = vec![];
+                if let Some(iter) = store.get_iter_first() {
+                    loop {
+                        let slave = store.get_value(&iter, 0).get_some::<u32>().unwrap_or(0) as u8;
+                        let platine = store
+                            .get_value(&iter, 2)
+                            .get::<String>()
+                            .unwrap_or(None)
+                            .unwrap_or_default();
+                        // Ein Sensor kann unter mehreren Baudraten gefunden werden
+                        if platine != UNKNOWN_PLATINE && !devices.iter().any(|(s, _)| *s == slave) {
+                            devices.push((slave, platine));
+                        }
+                        if !store.iter_next(&iter) {
+                            break;
+                        }
+                    }
+                }
+                let _ = gui_tx.clone().try_send(GuiMessage::ConnectLine(devices));
+            }
+        ));
+
+        // Callback: Doppelklick auf einen gefundenen Sensor
+        treeview.connect_row_activated(clone!(
+            @strong gui_tx
+            => move |treeview, path, _column| {
+                if let Some(model) = treeview.get_model() {
+                    if let Some(iter) = model.get_iter(path) {
+                        let slave = model.get_value(&iter, 0).get_some::<u32>().unwrap_or(0);
+                        let platine = model
+                            .get_value(&iter, 2)
+                            .get::<String>()
+                            .unwrap_or(None)
+                            .unwrap_or_default();
+                        let _ = gui_tx.clone().try_send(GuiMessage::SelectScanDevice {
+                            slave: slave as u8,
+                            platine,
+                        });
+                    }
+                }
+            }
+        ));
+
+        // Der Dialog wird nur versteckt, ein laufender Scan läuft weiter
+        dialog.connect_response(|dialog, _response| dialog.hide());
+        dialog.connect_delete_event(|dialog, _event| {
+            dialog.hide();
+            gtk::Inhibit(true)
+        });
+
+        ScanDialog {
+            dialog,
+            progress_bar,
+            store,
+            button_start,
+            button_cancel,
+        }
+    }
+
+    /// Zeigt den Dialog an
+    pub fn show(&self) {
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Setzt die Buttons bei Start des Scans
+    pub fn started(&self) {
+        self.button_start.set_sensitive(false);
+        self.button_cancel.set_sensitive(true);
+    }
+
+    /// Aktualisiert den Fortschritt
+    pub fn update_progress(&self, done: usize, total: usize) {
+        if total > 0 {
+            self.progress_bar.set_fraction(done as f64 / total as f64);
+        }
+        self.progress_bar
+            .set_text(Some(&format!("{} / {} Adressen", done, total)));
+    }
+
+    /// Fügt einen gefundenen Sensor in die Liste ein
+    pub fn add_device(&self, device: &BusDevice) {
+        let software_date = device
+            .software_date
+            .map(platine::format_software_date)
+            .unwrap_or_default();
+        self.store.insert_with_values(
+            None,
+            &[0, 1, 2, 3, 4],
+            &[
+                &u32::from(device.slave),
+                &(device.baud_rate as u32),
+                &device.platine.unwrap_or(UNKNOWN_PLATINE),
+                &u32::from(device.working_mode),
+                &software_date,
+            ],
+        );
+    }
+
+    /// Setzt die Buttons nach Ende des Scans
+    pub fn finished(&self) {
+        self.button_start.set_sensitive(true);
+        self.button_cancel.set_sensitive(false);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    gui::gtk3::{
+        traffic_dialog::{export, write_report},
+        GuiMessage,
+    },
+    modbus_master::{
+        traffic::{self, Direction},
+        ErrorKind, Statistics, TrafficRecord,
+    },
+};
+use futures::channel::mpsc::Sender;
+use glib::{clone, signal};
+use gtk::prelude::*;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+// Maximale Anzahl Einträge, ältere Einträge werden verworfen
+const MAX_RECORDS: usize = 10_000;
+
+/// Dialog für das passive Mithören am Bus
+///
+/// Zeigt die vom [`Sniffer`](crate::sniffer::Sniffer) dekodierten Frames live
+/// an. Wie beim [`TrafficDialog`](super::TrafficDialog) können die Einträge
+/// als CSV oder pcap Datei exportiert werden. Aus den Antworten wird eine
+/// Statistik pro Slave gebildet, Timeouts sind beim Mithören nicht erkennbar.
+#[derive(Debug)]
+pub struct SnifferDialog {
+    dialog: gtk::Dialog,
+    toggle_button_listen: gtk::ToggleButton,
+    toggle_button_listen_signal: glib::SignalHandlerId,
+    label_status: gtk::Label,
+    store: gtk::ListStore,
+    records: Rc<RefCell<VecDeque<TrafficRecord>>>,
+    statistics: Rc<RefCell<Statistics>>,
+    // Schnittstelle für den CSV Export
+    port: Rc<RefCell<Option<String>>>,
+}
+
+impl SnifferDialog {
+    /// Erstellt den Dialog
+    pub fn new(parent: &gtk::ApplicationWindow, gui_tx: &Sender<GuiMessage>) -> Self {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Bus mithören"),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Schließen", gtk::ResponseType::Close)],
+        );
+        dialog.set_default_size(800, 500);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+
+        // Mithören ein/ aus, Leeren, Export
+        let hbox_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let toggle_button_listen = gtk::ToggleButton::with_label("Mithören");
+        let button_clear = gtk::Button::with_label("Leeren");
+        let button_export_csv = gtk::Button::with_label("CSV exportieren ...");
+        let button_export_pcap = gtk::Button::with_label("pcap exportieren ...");
+        hbox_buttons.pack_start(&toggle_button_listen, false, true, 0);
+        hbox_buttons.pack_end(&button_export_pcap, false, true, 0);
+        hbox_buttons.pack_end(&button_export_csv, false, true, 0);
+        hbox_buttons.pack_end(&button_clear, false, true, 0);
+        content_area.pack_start(&hbox_buttons, false, true, 0);
+
+        let label_status = gtk::Label::new(Some(
+            "Während des Mithörens ist die Live Ansicht beendet, es wird nichts gesendet.",
+        ));
+        label_status.set_xalign(0.0);
+        content_area.pack_start(&label_status, false, true, 0);
+
+        // Einträge
+        let store = gtk::ListStore::new(&[
+            // Zeit
+            glib::Type::String,
+            // Richtung
+            glib::Type::String,
+            // Slave
+            glib::Type::U32,
+            // Beschreibung
+            glib::Type::String,
+            // Laufzeit
+            glib::Type::String,
+            // Fehler
+            glib::Type::String,
+        ]);
+        let treeview = gtk::TreeView::with_model(&store);
+        treeview.set_grid_lines(gtk::TreeViewGridLines::Horizontal);
+        for (i, title) in [
+            "Zeit",
+            "Richtung",
+            "Slave",
+            "Beschreibung",
+            "Laufzeit [ms]",
+            "Fehler",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            treeview.append_column(&column);
+        }
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.add(&treeview);
+        content_area.pack_start(&scrolled_window, true, true, 0);
+
+        let records = Rc::new(RefCell::new(VecDeque::new()));
+        let statistics = Rc::new(RefCell::new(Statistics::default()));
+        let port = Rc::new(RefCell::new(None));
+
+        //
+        // Callbacks
+        //
+
+        // Callback: Mithören starten/ beenden
+        let toggle_button_listen_signal = toggle_button_listen.connect_toggled(clone!(
+            @strong gui_tx
+            => move |toggle_button| {
+                let message = if toggle_button.get_active() {
+                    GuiMessage::StartSniffer
+                } else {
+                    GuiMessage::StopSniffer
+                };
+                let _ = gui_tx.clone().try_send(message);
+            }
+        ));
+
+        // Callback: Button "Leeren"
+        button_clear.connect_clicked(clone!(
+            @strong store,
+            @strong records,
+            @strong statistics
+            => move |_| {
+                store.clear();
+                records.borrow_mut().clear();
+                statistics.borrow_mut().reset();
+            }
+        ));
+
+        // Callback: Button "CSV exportieren"
+        button_export_csv.connect_clicked(clone!(
+            @strong dialog,
+            @strong records,
+            @strong statistics,
+            @strong port,
+            @strong label_status
+            => move |_| {
+                export(&dialog, &records, &label_status, "mithoeren.csv", |records, file| {
+                    write_report(records, &statistics.borrow(), port.borrow().as_deref(), file)
+                });
+            }
+        ));
+
+        // Callback: Button "pcap exportieren"
+        button_export_pcap.connect_clicked(clone!(
+            @strong dialog,
+            @strong records,
+            @strong label_status
+            => move |_| {
+                export(&dialog, &records, &label_status, "mithoeren.pcap", |records, file| {
+                    traffic::write_pcap(records, file)
+                });
+            }
+        ));
+
+        // Der Dialog wird nur versteckt, das Mithören läuft weiter
+        dialog.connect_response(|dialog, _response| dialog.hide());
+        dialog.connect_delete_event(|dialog, _event| {
+            dialog.hide();
+            gtk::Inhibit(true)
+        });
+
+        SnifferDialog {
+            dialog,
+            toggle_button_listen,
+            toggle_button_listen_signal,
+            label_status,
+            store,
+            records,
+            statistics,
+            port,
+        }
+    }
+
+    /// Zeigt den Dialog an
+    pub fn show(&self) {
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Das Mithören läuft auf der Schnittstelle `port`
+    pub fn started(&self, port: &str) {
+        self.set_listening(true);
+        self.label_status
+            .set_text(&format!("Höre auf {} mit ...", port));
+        *self.port.borrow_mut() = Some(port.to_string());
+    }
+
+    /// Das Mithören ist beendet, `error` ist der Grund falls es abgebrochen wurde
+    pub fn stopped(&self, error: Option<&str>) {
+        self.set_listening(false);
+        match error {
+            Some(error) => self
+                .label_status
+                .set_text(&format!("Mithören abgebrochen: {}", error)),
+            None => self.label_status.set_text("Mithören beendet"),
+        }
+    }
+
+    /// Fügt einen neuen Eintrag hinzu
+    pub fn add_record(&self, record: TrafficRecord) {
+        if record.direction == Direction::Response {
+            self.statistics.borrow_mut().record(
+                record.slave,
+                record.latency,
+                record.error.as_deref().map(ErrorKind::classify),
+            );
+        }
+        let mut records = self.records.borrow_mut();
+        if records.len() == MAX_RECORDS {
+            records.pop_front();
+            if let Some(iter) = self.store.get_iter_first() {
+                self.store.remove(&iter);
+            }
+        }
+        self.store.insert_with_values(
+            None,
+            &[0, 1, 2, 3, 4, 5],
+            &[
+                &record.timestamp.format("%H:%M:%S%.3f").to_string(),
+                &record.direction.to_string(),
+                &u32::from(record.slave),
+                &record.description.clone().unwrap_or_default(),
+                &record
+                    .latency
+                    .map(|latency| latency.as_millis().to_string())
+                    .unwrap_or_default(),
+                &record.error.clone().unwrap_or_default(),
+            ],
+        );
+        records.push_back(record);
+    }
+
+    // Setzt den Toggle Button ohne den Callback auszulösen
+    fn set_listening(&self, listening: bool) {
+        signal::signal_handler_block(
+            &self.toggle_button_listen,
+            &self.toggle_button_listen_signal,
+        );
+        self.toggle_button_listen.set_active(listening);
+        signal::signal_handler_unblock(
+            &self.toggle_button_listen,
+            &self.toggle_button_listen_signal,
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    gui::gtk3::GuiMessage,
+    modbus_master::{statistics, traffic, Statistics, TrafficRecord},
+};
+use futures::channel::mpsc::Sender;
+use glib::clone;
+use gtk::prelude::*;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+};
+
+// Maximale Anzahl Einträge, ältere Einträge werden verworfen
+const MAX_RECORDS: usize = 10_000;
+
+/// Dialog für den Mitschnitt des Modbus Verkehrs
+///
+/// Wie der [`ScanDialog`](super::ScanDialog) wird der Dialog im Code erstellt.
+/// Die Einträge werden hier gesammelt und können als CSV oder pcap Datei
+/// exportiert werden, z.B. für eine Support Anfrage. Der CSV Export enthält
+/// außerdem die Schnittstelle und die Statistik der Sitzung.
+#[derive(Debug)]
+pub struct TrafficDialog {
+    dialog: gtk::Dialog,
+    store: gtk::ListStore,
+    records: Rc<RefCell<VecDeque<TrafficRecord>>>,
+    statistics: Rc<RefCell<Statistics>>,
+    // Schnittstelle für den CSV Export
+    port: Rc<RefCell<Option<String>>>,
+}
+
+impl TrafficDialog {
+    /// Erstellt den Dialog
+    pub fn new(parent: &gtk::ApplicationWindow, gui_tx: &Sender<GuiMessage>) -> Self {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Modbus Mitschnitt"),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Schließen", gtk::ResponseType::Close)],
+        );
+        dialog.set_default_size(800, 500);
+        let content_area = dialog.get_content_area();
+        content_area.set_spacing(6);
+
+        // Mitschnitt ein/ aus, Leeren, Export
+        let hbox_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let check_button_record = gtk::CheckButton::with_label("Mitschnitt");
+        let button_clear = gtk::Button::with_label("Leeren");
+        let button_export_csv = gtk::Button::with_label("CSV exportieren ...");
+        let button_export_pcap = gtk::Button::with_label("pcap exportieren ...");
+        hbox_buttons.pack_start(&check_button_record, false, true, 0);
+        hbox_buttons.pack_end(&button_export_pcap, false, true, 0);
+        hbox_buttons.pack_end(&button_export_csv, false, true, 0);
+        hbox_buttons.pack_end(&button_clear, false, true, 0);
+        content_area.pack_start(&hbox_buttons, false, true, 0);
+
+        let label_status = gtk::Label::new(None);
+        label_status.set_xalign(0.0);
+        content_area.pack_start(&label_status, false, true, 0);
+
+        // Einträge
+        let store = gtk::ListStore::new(&[
+            // Zeit
+            glib::Type::String,
+            // Richtung
+            glib::Type::String,
+            // Slave
+            glib::Type::U32,
+            // Funktion
+            glib::Type::String,
+            // Register
+            glib::Type::String,
+            // Werte
+            glib::Type::String,
+            // Laufzeit
+            glib::Type::String,
+            // Fehler
+            glib::Type::String,
+        ]);
+        let treeview = gtk::TreeView::with_model(&store);
+        treeview.set_grid_lines(gtk::TreeViewGridLines::Horizontal);
+        for (i, title) in [
+            "Zeit",
+            "Richtung",
+            "Slave",
+            "Funktion",
+            "Register",
+            "Werte",
+            "Laufzeit [ms]",
+            "Fehler",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            let renderer = gtk::CellRendererText::new();
+            column.pack_end(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            treeview.append_column(&column);
+        }
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.add(&treeview);
+        content_area.pack_start(&scrolled_window, true, true, 0);
+
+        let records = Rc::new(RefCell::new(VecDeque::new()));
+        let statistics = Rc::new(RefCell::new(Statistics::default()));
+        let port = Rc::new(RefCell::new(None));
+
+        //
+        // Callbacks
+        //
+
+        // Callback: Mitschnitt ein-/ ausschalten
+        check_button_record.connect_toggled(clone!(
+            @strong gui_tx
+            => move |check_button| {
+                let _ = gui_tx
+                    .clone()
+                    .try_send(GuiMessage::SetTrafficRecording(check_button.get_active()));
+            }
+        ));
+
+        // Callback: Button "Leeren"
+        button_clear.connect_clicked(clone!(
+            @strong store,
+            @strong records,
+            @strong label_status
+            => move |_| {
+                store.clear();
+                records.borrow_mut().clear();
+                label_status.set_text("");
+            }
+        ));
+
+        // Callback: Button "CSV exportieren"
+        button_export_csv.connect_clicked(clone!(
+            @strong dialog,
+            @strong records,
+            @strong statistics,
+            @strong port,
+            @strong label_status
+            => move |_| {
+                export(&dialog, &records, &label_status, "mitschnitt.csv", |records, file| {
+                    write_report(records, &statistics.borrow(), port.borrow().as_deref(), file)
+                });
+            }
+        ));
+
+        // Callback: Button "pcap exportieren"
+        button_export_pcap.connect_clicked(clone!(
+            @strong dialog,
+            @strong records,
+            @strong label_status
+            => move |_| {
+                export(&dialog, &records, &label_status, "mitschnitt.pcap", |records, file| {
+                    traffic::write_pcap(records, file)
+                });
+            }
+        ));
+
+        // Der Dialog wird nur versteckt, der Mitschnitt läuft weiter
+        dialog.connect_response(|dialog, _response| dialog.hide());
+        dialog.connect_delete_event(|dialog, _event| {
+            dialog.hide();
+            gtk::Inhibit(true)
+        });
+
+        TrafficDialog {
+            dialog,
+            store,
+            records,
+            statistics,
+            port,
+        }
+    }
+
+    /// Zeigt den Dialog an
+    pub fn show(&self) {
+        self.dialog.show_all();
+        self.dialog.present();
+    }
+
+    /// Übernimmt die aktuelle Statistik für den CSV Export
+    pub fn set_statistics(&self, statistics: Statistics) {
+        *self.statistics.borrow_mut() = statistics;
+    }
+
+    /// Übernimmt die gewählte Schnittstelle für den CSV Export
+    pub fn set_port(&self, port: Option<String>) {
+        *self.port.borrow_mut() = port;
+    }
+
+    /// Fügt neue Einträge hinzu
+    pub fn add_records(&self, new_records: Vec<TrafficRecord>) {
+        let mut records = self.records.borrow_mut();
+        for record in new_records {
+            if records.len() == MAX_RECORDS {
+                records.pop_front();
+                if let Some(iter) = self.store.get_iter_first() {
+                    self.store.remove(&iter);
+                }
+            }
+            self.store.insert_with_values(
+                None,
+                &[0, 1, 2, 3, 4, 5, 6, 7],
+                &[
+                    &record.timestamp.format("%H:%M:%S%.3f").to_string(),
+                    &record.direction.to_string(),
+                    &u32::from(record.slave),
+                    &format!("0x{:02X}", record.function),
+                    &record.registers(),
+                    &record.values(),
+                    &record
+                        .latency
+                        .map(|latency| latency.as_millis().to_string())
+                        .unwrap_or_default(),
+                    &record.error.clone().unwrap_or_default(),
+                ],
+            );
+            records.push_back(record);
+        }
+    }
+}
+
+// Fragt nach einer Datei und schreibt die Einträge mit `write` hinein
+//
+// Wird auch vom `SnifferDialog` verwendet.
+pub(super) fn export<F>(
+    parent: &gtk::Dialog,
+    records: &RefCell<VecDeque<TrafficRecord>>,
+    label_status: &gtk::Label,
+    file_name: &str,
+    write: F,
+) where
+    F: Fn(&[TrafficRecord], File) -> io::Result<()>,
+{
+    if let Some(path) = choose_export_file(parent, "Mitschnitt exportieren", file_name) {
+        let records: Vec<TrafficRecord> = records.borrow().iter().cloned().collect();
+        let result = File::create(&path).and_then(|file| write(&records, file));
+        match result {
+            Ok(_) => label_status.set_text(&format!(
+                "{} Einträge nach {} exportiert",
+                records.len(),
+                path.display()
+            )),
+            Err(error) => label_status.set_text(&format!("Export fehlgeschlagen: {}", error)),
+        }
+    }
+}
+
+// Fragt nach der Datei für einen Export
+pub(super) fn choose_export_file(
+    parent: &gtk::Dialog,
+    title: &str,
+    file_name: &str,
+) -> Option<PathBuf> {
+    let file_chooser = gtk::FileChooserDialog::with_buttons(
+        Some(title),
+        Some(parent),
+        gtk::FileChooserAction::Save,
+        &[
+            ("Abbrechen", gtk::ResponseType::Cancel),
+            ("Speichern", gtk::ResponseType::Accept),
+        ],
+    );
+    file_chooser.set_do_overwrite_confirmation(true);
+    file_chooser.set_current_name(file_name);
+
+    let path = match file_chooser.run() {
+        gtk::ResponseType::Accept => file_chooser.get_filename(),
+        _ => None,
+    };
+    file_chooser.close();
+    path
+}
+
+// Schreibt die Schnittstelle, die Einträge und darunter die Statistik als CSV
+pub(super) fn write_report(
+    records: &[TrafficRecord],
+    statistics: &Statistics,
+    port: Option<&str>,
+    mut file: File,
+) -> io::Result<()> {
+    write_port(port, &mut file)?;
+    traffic::write_csv(records, &mut file)?;
+    if !statistics.is_empty() {
+        writeln!(file)?;
+        statistics::write_csv(statistics, file)?;
+    }
+    Ok(())
+}
+
+// Schreibt die Schnittstelle, mit dem Namen des Adapters, vor einen CSV Export
+//
+// Wird auch vom `DiagnosticsDialog` verwendet.
+pub(super) fn write_port(port: Option<&str>, file: &mut File) -> io::Result<()> {
+    if let Some(port) = port {
+        let mut wtr = csv::Writer::from_writer(&mut *file);
+        wtr.write_record(&["Schnittstelle", port])?;
+        wtr.flush()?;
+        writeln!(file)?;
+    }
+    Ok(())
+}
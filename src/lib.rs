@@ -5,6 +5,8 @@
 #[macro_use]
 extern crate log;
 
+pub mod adapter_check;
+
 /// GUI Komponenten
 pub mod gui {
     pub mod gtk3;
@@ -16,3 +18,7 @@ pub mod platine;
 pub mod modbus_master;
 
 pub mod serial_interface;
+
+pub mod settings;
+
+pub mod sniffer;
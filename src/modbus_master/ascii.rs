@@ -0,0 +1,184 @@
+//! Modbus ASCII
+//!
+//! libmodbus kennt nur RTU und TCP, ältere Anlagen verwenden aber die ASCII
+//! Übertragung. Jeder Frame beginnt mit ':', Slave ID, PDU und die Prüfsumme
+//! (LRC) folgen als Hex Zeichen, CR LF beendet den Frame. Pausen zwischen den
+//! Zeichen sind erlaubt, die Frames werden deshalb an Start- und Endezeichen
+//! erkannt und nicht an den Pausen wie bei RTU.
+//!
+//! Der [`NativePort`](super::native::NativePort) sendet und empfängt damit die
+//! Anfragen des Modbus Masters, der [`Sniffer`](crate::sniffer::Sniffer)
+//! zerlegt damit die mitgehörten Zeichen.
+use crate::modbus_master::rtu::{EMBBADCRC, EMBBADDATA, ETIMEDOUT};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    time,
+};
+
+// Erlaubte Pause zwischen zwei Zeichen eines Frames, laut Spezifikation bis zu 1s
+const CHAR_TIMEOUT: Duration = Duration::from_secs(1);
+/// Längster Frame: ':', Slave ID, 253 Bytes PDU und LRC als Hex Zeichen, CR LF
+pub const MAX_FRAME_LEN: usize = 1 + 2 * 255 + 2;
+
+/// Prüfsumme (LRC), das Zweierkomplement der Summe aller Bytes
+///
+/// Über Slave ID, PDU und LRC gebildet ist die Prüfsumme 0.
+pub fn lrc(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg()
+}
+
+/// Erstellt den Frame für Slave ID und PDU
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::modbus_master::ascii;
+///
+/// // Slave 1 liest Input Register 2
+/// let frame = ascii::encode(1, &[0x04, 0x00, 0x02, 0x00, 0x01]);
+/// assert_eq!(frame, b":010400020001F8\r\n");
+/// ```
+pub fn encode(slave: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut data = vec![slave];
+    data.extend_from_slice(pdu);
+    data.push(lrc(&data));
+
+    let mut frame = Vec::with_capacity(2 * data.len() + 3);
+    frame.push(b':');
+    for byte in data {
+        frame.extend_from_slice(format!("{:02X}", byte).as_bytes());
+    }
+    frame.extend_from_slice(b"\r\n");
+    frame
+}
+
+/// Liefert die Bytes eines vollständigen Frames, Slave ID, PDU und LRC
+///
+/// `None` wenn Startzeichen, Endezeichen oder Hex Zeichen fehlerhaft sind.
+/// Die LRC wird nicht geprüft.
+pub fn decode(frame: &[u8]) -> Option<Vec<u8>> {
+    if frame.len() < 3 || frame[0] != b':' || !frame.ends_with(b"\r\n") {
+        return None;
+    }
+    let hex = &frame[1..frame.len() - 2];
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            let high = char::from(pair[0]).to_digit(16)?;
+            let low = char::from(pair[1]).to_digit(16)?;
+            Some((high << 4 | low) as u8)
+        })
+        .collect()
+}
+
+/// Fügt ein empfangenes Zeichen an `frame` an, `true` wenn der Frame vollständig ist
+///
+/// Ein ':' beginnt immer einen neuen Frame, Zeichen vor dem ersten ':' und zu
+/// lange Frames werden verworfen.
+pub fn push(frame: &mut Vec<u8>, byte: u8) -> bool {
+    if byte == b':' {
+        frame.clear();
+    } else if frame.is_empty() {
+        return false;
+    }
+    if frame.len() >= MAX_FRAME_LEN {
+        frame.clear();
+        return false;
+    }
+    frame.push(byte);
+    frame.ends_with(b"\r\n")
+}
+
+// Empfängt den nächsten Frame
+//
+// Bis zum Beginn des Frames gilt die Antwortzeit, danach die Pause zwischen
+// zwei Zeichen. Ein Echo der Anfrage lässt sich nicht erkennen, die Antwort auf
+// 0x06 ist identisch mit der Anfrage.
+pub(crate) async fn receive(
+    port: &mut (impl AsyncRead + Unpin),
+    timeout: Duration,
+) -> io::Result<Vec<u8>> {
+    let deadline = Instant::now() + timeout;
+    let mut frame = vec![];
+    let mut buf = [0u8; 64];
+    loop {
+        let timeout = if frame.is_empty() {
+            deadline.saturating_duration_since(Instant::now())
+        } else {
+            CHAR_TIMEOUT
+        };
+        let read = match time::timeout(timeout, port.read(&mut buf)).await {
+            Ok(read) => read?,
+            Err(_) => return Err(io::Error::from_raw_os_error(ETIMEDOUT as i32)),
+        };
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        for byte in &buf[..read] {
+            if push(&mut frame, *byte) {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+// Prüft Start- und Endezeichen, Hex Zeichen und LRC eines empfangenen Frames,
+// liefert Slave ID und PDU
+pub(crate) fn check(frame: &[u8]) -> Result<Vec<u8>, i64> {
+    let mut bytes = decode(frame).ok_or(EMBBADDATA)?;
+    if bytes.len() < 3 {
+        return Err(EMBBADDATA);
+    }
+    if lrc(&bytes) != 0 {
+        return Err(EMBBADCRC);
+    }
+    bytes.pop();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode() {
+        let frame = encode(247, &[0x06, 0x00, 0x4f, 0x26, 0x94]);
+        assert_eq!(frame, b":F706004F2694FA\r\n".to_vec());
+        let bytes = decode(&frame).unwrap();
+        assert_eq!(bytes, vec![0xf7, 0x06, 0x00, 0x4f, 0x26, 0x94, 0xfa]);
+        assert_eq!(lrc(&bytes), 0);
+        assert_eq!(decode(b":F7060\r\n"), None);
+        assert_eq!(decode(b":F7XX\r\n"), None);
+        assert_eq!(decode(b"F706\r\n"), None);
+    }
+
+    #[test]
+    fn push_frames() {
+        let mut frame = vec![];
+        let complete = b"\x00\x7f:01:0104"
+            .iter()
+            .chain(b"00020001F8\r\n")
+            .map(|byte| push(&mut frame, *byte))
+            .collect::<Vec<_>>();
+        assert_eq!(complete.iter().filter(|complete| **complete).count(), 1);
+        assert_eq!(frame, b":010400020001F8\r\n".to_vec());
+    }
+
+    #[test]
+    fn check_frames() {
+        // Slave 1 antwortet mit Input Register 2 = 35
+        let response = encode(1, &[0x04, 0x02, 0x00, 0x23]);
+        assert_eq!(check(&response), Ok(vec![0x01, 0x04, 0x02, 0x00, 0x23]));
+        assert_eq!(check(b":01\r\n"), Err(EMBBADDATA));
+
+        let mut corrupt = response;
+        corrupt[4] = b'5';
+        assert_eq!(check(&corrupt), Err(EMBBADCRC));
+    }
+}
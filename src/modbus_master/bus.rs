@@ -0,0 +1,476 @@
+use crate::modbus_master::{
+    statistics::{ErrorKind, Statistics},
+    traffic::{
+        TrafficRecorder, READ_HOLDING_REGISTERS, READ_INPUT_REGISTERS, WRITE_MULTIPLE_REGISTERS,
+        WRITE_SINGLE_REGISTER,
+    },
+    transport::Transport,
+    Endpoint, LineSettings, ModbusMasterError, PortOptions, LOCK_TIMEOUT,
+};
+use crate::serial_interface::PortLock;
+use chrono::{DateTime, Local};
+use libmodbus::{prelude::Error as LibModbusError, Timeout};
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Code der gesperrte Register freigibt
+pub const UNLOCK_CODE: u16 = 9876;
+/// So lange gilt eine Platine nach dem Entsperren als entsperrt
+///
+/// Wie lange die Platine tatsächlich entsperrt bleibt ist nicht dokumentiert,
+/// der Wert ist bewusst vorsichtig gewählt. Ein Code zum Sperren ist ebenfalls
+/// nicht dokumentiert, die Platine wird deshalb nie aktiv gesperrt.
+pub const UNLOCK_WINDOW: Duration = Duration::from_secs(10);
+
+/// Die vom Modbus Master geöffnete Schnittstelle
+///
+/// Es gibt genau einen `Bus`, er gehört dem Thread des Modbus Masters. Der
+/// Modbus Context bzw. der [`NativePort`](super::native::NativePort)
+/// wird beim ersten Zugriff geöffnet und für alle weiteren Zugriffe
+/// wiederverwendet. Ändern sich Schnittstelle oder Parameter wird
+/// die Schnittstelle neu geöffnet. Solange eine serielle Schnittstelle geöffnet
+/// ist, ist sie mit einem [`PortLock`] für andere Programme gesperrt.
+///
+/// Die [`PortOptions`] der Schnittstelle werden beim Öffnen eingestellt, ihre
+/// Mindestpause zwischen zwei Anfragen hält der [`Link`] ein.
+///
+/// Alle Anfragen laufen über einen [`Link`], so werden sie bei eingeschaltetem
+/// Mitschnitt im [`TrafficRecorder`] festgehalten und in der [`Statistics`]
+/// gezählt.
+#[derive(Default)]
+pub struct Bus {
+    // Parameter der seriellen Schnittstelle dieser Sitzung
+    line_settings: LineSettings,
+    // Schnittstelle und Parameter mit denen `transport` geöffnet wurde
+    endpoint: Option<(String, LineSettings)>,
+    transport: Option<Transport>,
+    // Sperre der geöffneten seriellen Schnittstelle
+    port_lock: Option<PortLock>,
+    // Optionen pro Schnittstelle
+    port_options: HashMap<String, PortOptions>,
+    // Mindestpause zwischen zwei Anfragen an der geöffneten Schnittstelle
+    frame_gap: Duration,
+    // Ende der letzten Anfrage
+    last_frame: Option<Instant>,
+    // Zuletzt gesetzte Modbus Slave ID
+    slave: u8,
+    // Entsperrte Platinen pro Slave
+    unlocked: HashMap<u8, Unlocked>,
+    // Mitschnitt
+    traffic: TrafficRecorder,
+    // Statistik pro Slave
+    statistics: Statistics,
+    // Wurde die Statistik seit dem letzten Abholen geändert?
+    statistics_changed: bool,
+}
+
+/// Zugriff auf den geöffneten Modbus Context
+///
+/// Bietet die vom Modbus Master verwendeten Funktionen des Modbus Contexts an
+/// und hält jede Anfrage mit Antwort, Laufzeit und Fehler im Mitschnitt und
+/// in der Statistik fest.
+pub struct Link<'a> {
+    transport: &'a mut Transport,
+    frame_gap: Duration,
+    last_frame: &'a mut Option<Instant>,
+    slave: &'a mut u8,
+    traffic: &'a mut TrafficRecorder,
+    statistics: &'a mut Statistics,
+    statistics_changed: &'a mut bool,
+}
+
+// Eine entsperrte Platine
+#[derive(Clone, Copy, Debug)]
+struct Unlocked {
+    reg_protection: u16,
+    since: Instant,
+}
+
+impl Unlocked {
+    // Ist die Platine mit diesem Schreibschutz Register vermutlich noch entsperrt?
+    fn covers(&self, reg_protection: u16, now: Instant) -> bool {
+        self.reg_protection == reg_protection
+            && now.saturating_duration_since(self.since) < UNLOCK_WINDOW
+    }
+}
+
+impl Bus {
+    /// Erstellt einen geschlossenen Bus mit den Standard Parametern (9600 8N1)
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Parameter der seriellen Schnittstelle dieser Sitzung
+    pub fn line_settings(&self) -> LineSettings {
+        self.line_settings
+    }
+
+    /// Setzt die Parameter der seriellen Schnittstelle dieser Sitzung
+    ///
+    /// Die Schnittstelle wird beim nächsten Zugriff mit den neuen Parametern geöffnet.
+    pub fn set_line_settings(&mut self, line_settings: LineSettings) {
+        self.line_settings = line_settings;
+    }
+
+    /// Setzt die Optionen der Schnittstellen
+    ///
+    /// Ändern sich die Optionen der geöffneten Schnittstelle, wird sie beim
+    /// nächsten Zugriff mit den neuen Optionen geöffnet.
+    pub fn set_port_options(&mut self, port_options: HashMap<String, PortOptions>) {
+        let changed = match &self.endpoint {
+            Some((tty_path, _)) => self.port_options.get(tty_path) != port_options.get(tty_path),
+            None => false,
+        };
+        self.port_options = port_options;
+        if changed {
+            self.close();
+        }
+    }
+
+    /// Mitschnitt des Modbus Verkehrs
+    pub fn traffic(&mut self) -> &mut TrafficRecorder {
+        &mut self.traffic
+    }
+
+    /// Statistik der Sitzung
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    /// Setzt die Statistik zurück
+    pub fn reset_statistics(&mut self) {
+        self.statistics.reset();
+        self.statistics_changed = true;
+    }
+
+    /// Liefert die Statistik falls sie seit dem letzten Aufruf geändert wurde
+    pub fn take_changed_statistics(&mut self) -> Option<Statistics> {
+        if self.statistics_changed {
+            self.statistics_changed = false;
+            Some(self.statistics.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Liefert den Modbus Context, öffnet die Schnittstelle falls nötig
+    pub fn open(&mut self, tty_path: &str) -> Result<Link<'_>, ModbusMasterError> {
+        let line_settings = self.line_settings;
+        self.open_with(tty_path, &line_settings)
+    }
+
+    /// Wie `open`, aber mit abweichenden Parametern
+    ///
+    /// Wird vom Bus Scanner verwendet der verschiedene Baudraten probiert.
+    pub fn open_with(
+        &mut self,
+        tty_path: &str,
+        line_settings: &LineSettings,
+    ) -> Result<Link<'_>, ModbusMasterError> {
+        let endpoint = (tty_path.to_string(), *line_settings);
+        if self.endpoint.as_ref() != Some(&endpoint) {
+            self.close();
+        }
+
+        if self.transport.is_none() {
+            debug!("Bus open: {} ({})", tty_path, line_settings);
+            let target = Endpoint::parse(tty_path)?;
+            let port_lock = if target.is_serial() {
+                let port_lock =
+                    PortLock::acquire(tty_path).map_err(|holder| ModbusMasterError::PortBusy {
+                        tty_path: tty_path.to_string(),
+                        holder,
+                    })?;
+                Some(port_lock)
+            } else {
+                None
+            };
+            let options = self.port_options.get(tty_path).copied().unwrap_or_default();
+            self.transport = Some(Transport::open(&target, line_settings, &options)?);
+            self.port_lock = port_lock;
+            self.frame_gap = options.frame_gap;
+            self.endpoint = Some(endpoint);
+        }
+
+        self.link().ok_or(ModbusMasterError::NotConnected)
+    }
+
+    /// Liefert den Modbus Context für `slave` und entsperrt die Platine falls nötig
+    ///
+    /// Jede Platine wird nur einmal pro Sitzung entsperrt, der Zustand gilt pro
+    /// Slave. So entsperrt die Live Ansicht mehrerer Sensoren nicht bei jedem
+    /// Wechsel des Slaves erneut. Erneut entsperrt wird erst wenn das
+    /// [`UNLOCK_WINDOW`] abgelaufen sein könnte, sich das Schreibschutz Register
+    /// ändert oder nach einem Fehler (`forget_unlock`).
+    pub fn unlock(
+        &mut self,
+        tty_path: &str,
+        slave: u8,
+        reg_protection: u16,
+    ) -> Result<Link<'_>, ModbusMasterError> {
+        let now = Instant::now();
+        let unlocked = self
+            .unlocked
+            .get(&slave)
+            .map_or(false, |unlocked| unlocked.covers(reg_protection, now));
+
+        let mut link = self.open(tty_path)?;
+        link.set_slave(slave)?;
+        if !unlocked {
+            debug!("Bus unlock: slave {}", slave);
+            link.write_register(reg_protection, UNLOCK_CODE)?;
+            thread::sleep(Duration::from_millis(LOCK_TIMEOUT));
+            self.unlocked.insert(
+                slave,
+                Unlocked {
+                    reg_protection,
+                    since: now,
+                },
+            );
+        }
+
+        self.link().ok_or(ModbusMasterError::NotConnected)
+    }
+
+    /// Die Platine von `slave` gilt wieder als gesperrt, der nächste Zugriff
+    /// entsperrt erneut
+    ///
+    /// Wird aufgerufen wenn ein Schreibzugriff fehlschlägt, z.B. weil die Sperre
+    /// früher als erwartet wieder aktiv wurde, und nach einem Neustart.
+    pub fn forget_unlock(&mut self, slave: u8) {
+        self.unlocked.remove(&slave);
+    }
+
+    /// Alle Platinen gelten wieder als gesperrt
+    ///
+    /// Z.B. nach einem Broadcast, dessen Empfänger nicht bekannt sind.
+    pub fn forget_all_unlocks(&mut self) {
+        self.unlocked.clear();
+    }
+
+    /// Schließt die Schnittstelle
+    ///
+    /// Wird nach Fehlern aufgerufen, der nächste Zugriff öffnet die
+    /// Schnittstelle dann neu und entsperrt die Platine erneut.
+    pub fn close(&mut self) {
+        if self.transport.take().is_some() {
+            debug!("Bus close: {:?}", self.endpoint);
+        }
+        self.port_lock = None;
+        self.endpoint = None;
+        self.unlocked.clear();
+    }
+
+    // Zugriff auf den geöffneten Modbus Context
+    fn link(&mut self) -> Option<Link<'_>> {
+        let frame_gap = self.frame_gap;
+        let last_frame = &mut self.last_frame;
+        let slave = &mut self.slave;
+        let traffic = &mut self.traffic;
+        let statistics = &mut self.statistics;
+        let statistics_changed = &mut self.statistics_changed;
+        self.transport.as_mut().map(|transport| Link {
+            transport,
+            frame_gap,
+            last_frame,
+            slave,
+            traffic,
+            statistics,
+            statistics_changed,
+        })
+    }
+}
+
+impl<'a> Link<'a> {
+    // Hält die Mindestpause seit der letzten Anfrage ein, liefert Zeitpunkt und
+    // Beginn der neuen Anfrage
+    fn begin(&mut self) -> (DateTime<Local>, Instant) {
+        if let Some(last_frame) = *self.last_frame {
+            let elapsed = last_frame.elapsed();
+            if elapsed < self.frame_gap {
+                thread::sleep(self.frame_gap - elapsed);
+            }
+        }
+        (Local::now(), Instant::now())
+    }
+
+    // Hält eine Anfrage im Mitschnitt und in der Statistik fest, ihr Ende gilt
+    // als Beginn der Mindestpause
+    #[allow(clippy::too_many_arguments)]
+    fn record(
+        &mut self,
+        timestamp: DateTime<Local>,
+        latency: Duration,
+        function: u8,
+        address: u16,
+        count: u16,
+        written: &[u16],
+        response: Result<&[u16], &LibModbusError>,
+    ) {
+        self.statistics.record(
+            *self.slave,
+            Some(latency),
+            response.err().map(ErrorKind::of),
+        );
+        *self.statistics_changed = true;
+        *self.last_frame = Some(Instant::now());
+        let exchange = self.transport.take_exchange();
+        self.traffic.record(
+            timestamp,
+            latency,
+            *self.slave,
+            function,
+            address,
+            count,
+            written,
+            response.map_err(|error| format!("{:?}", error)),
+            exchange,
+        );
+    }
+
+    /// Setzt die Modbus Slave ID für alle folgenden Anfragen
+    pub fn set_slave(&mut self, slave: u8) -> Result<(), LibModbusError> {
+        self.transport.set_slave(slave)?;
+        *self.slave = slave;
+        Ok(())
+    }
+
+    /// Setzt die Wartezeit auf eine Antwort
+    pub fn set_response_timeout(&mut self, timeout: Timeout) -> Result<(), LibModbusError> {
+        self.transport.set_response_timeout(timeout)
+    }
+
+    /// Liest Holding Register (0x03)
+    pub fn read_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        let (timestamp, started) = self.begin();
+        let result = self.transport.read_registers(address, num, dest);
+        let response = match result {
+            Ok(read) => Ok(&dest[..usize::from(read).min(dest.len())]),
+            Err(ref error) => Err(error),
+        };
+        self.record(
+            timestamp,
+            started.elapsed(),
+            READ_HOLDING_REGISTERS,
+            address,
+            num,
+            &[],
+            response,
+        );
+        result
+    }
+
+    /// Liest Input Register (0x04)
+    pub fn read_input_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        let (timestamp, started) = self.begin();
+        let result = self.transport.read_input_registers(address, num, dest);
+        let response = match result {
+            Ok(read) => Ok(&dest[..usize::from(read).min(dest.len())]),
+            Err(ref error) => Err(error),
+        };
+        self.record(
+            timestamp,
+            started.elapsed(),
+            READ_INPUT_REGISTERS,
+            address,
+            num,
+            &[],
+            response,
+        );
+        result
+    }
+
+    /// Schreibt ein Register (0x06)
+    pub fn write_register(&mut self, address: u16, value: u16) -> Result<(), LibModbusError> {
+        let (timestamp, started) = self.begin();
+        let result = self.transport.write_register(address, value);
+        let written = [value];
+        let response = match result {
+            Ok(_) => Ok(&written[..]),
+            Err(ref error) => Err(error),
+        };
+        self.record(
+            timestamp,
+            started.elapsed(),
+            WRITE_SINGLE_REGISTER,
+            address,
+            1,
+            &written,
+            response,
+        );
+        result
+    }
+
+    /// Schreibt mehrere Register (0x10)
+    pub fn write_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        src: &[u16],
+    ) -> Result<u16, LibModbusError> {
+        let (timestamp, started) = self.begin();
+        let result = self.transport.write_registers(address, num, src);
+        let response = match result {
+            Ok(_) => Ok(&[][..]),
+            Err(ref error) => Err(error),
+        };
+        self.record(
+            timestamp,
+            started.elapsed(),
+            WRITE_MULTIPLE_REGISTERS,
+            address,
+            num,
+            src,
+            response,
+        );
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unlocked(since: Instant) -> Unlocked {
+        Unlocked {
+            reg_protection: 79,
+            since,
+        }
+    }
+
+    #[test]
+    fn unlock_covers_same_board() {
+        let now = Instant::now();
+        assert!(unlocked(now).covers(79, now));
+        assert!(!unlocked(now).covers(49, now));
+    }
+
+    #[test]
+    fn unlock_expires() {
+        let now = Instant::now();
+        assert!(unlocked(now).covers(79, now + UNLOCK_WINDOW / 2));
+        assert!(!unlocked(now).covers(79, now + UNLOCK_WINDOW));
+    }
+
+    #[test]
+    fn unlock_per_slave() {
+        let mut bus = Bus::new();
+        let now = Instant::now();
+        bus.unlocked.insert(1, unlocked(now));
+        bus.unlocked.insert(2, unlocked(now));
+        bus.forget_unlock(1);
+        assert!(!bus.unlocked.contains_key(&1));
+        assert!(bus.unlocked[&2].covers(79, now));
+    }
+}
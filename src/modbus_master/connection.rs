@@ -0,0 +1,183 @@
+use crate::modbus_master::{ModbusMasterError, ModbusMasterEvent, Subscribers};
+use chrono::{DateTime, Local};
+use std::fmt;
+
+/// Zustand der Verbindung zu einem Sensor während der Live Ansicht
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConnectionState {
+    /// Keine Live Ansicht
+    Offline,
+    /// Live Ansicht gestartet, es wurde noch kein Register erfolgreich gelesen
+    Connecting,
+    /// Die Register werden gelesen
+    Online,
+    /// Register konnten nicht gelesen werden, die angezeigten Werte sind veraltet
+    Degraded,
+    /// Die Schnittstelle ist nicht mehr verfügbar, die Live Ansicht wurde beendet
+    Lost,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionState::Offline => write!(f, "Offline"),
+            ConnectionState::Connecting => write!(f, "Verbinde"),
+            ConnectionState::Online => write!(f, "Online"),
+            ConnectionState::Degraded => write!(f, "Gestört"),
+            ConnectionState::Lost => write!(f, "Verbindung verloren"),
+        }
+    }
+}
+
+/// Zustandsautomat der Verbindung
+///
+/// Jeder Zustandswechsel wird mit Grund und Zeitpunkt des letzten erfolgreich
+/// gelesenen Registers als [`ModbusMasterEvent::ConnectionState`] gesendet.
+#[derive(Debug)]
+pub struct Connection {
+    state: ConnectionState,
+    last_poll: Option<DateTime<Local>>,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Connection {
+            state: ConnectionState::Offline,
+            last_poll: None,
+        }
+    }
+}
+
+impl Connection {
+    /// Aktueller Zustand
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Zeitpunkt an dem zuletzt ein Register erfolgreich gelesen wurde
+    pub fn last_poll(&self) -> Option<DateTime<Local>> {
+        self.last_poll
+    }
+
+    /// Die Live Ansicht wurde gestartet
+    pub fn connect(&mut self, subscribers: &mut Subscribers) {
+        self.last_poll = None;
+        self.transition(
+            ConnectionState::Connecting,
+            "Live Ansicht gestartet".to_string(),
+            subscribers,
+        );
+    }
+
+    /// Die Live Ansicht wurde beendet
+    pub fn disconnect(&mut self, subscribers: &mut Subscribers) {
+        self.transition(
+            ConnectionState::Offline,
+            "Live Ansicht beendet".to_string(),
+            subscribers,
+        );
+    }
+
+    /// Der Sensor startet neu, die Live Ansicht verbindet sich danach erneut
+    pub fn restart(&mut self, subscribers: &mut Subscribers) {
+        self.transition(
+            ConnectionState::Connecting,
+            "Sensor startet neu".to_string(),
+            subscribers,
+        );
+    }
+
+    /// Ein Register wurde erfolgreich gelesen
+    pub fn poll_succeeded(&mut self, subscribers: &mut Subscribers) {
+        self.last_poll = Some(Local::now());
+        self.transition(
+            ConnectionState::Online,
+            "Register werden gelesen".to_string(),
+            subscribers,
+        );
+    }
+
+    /// Ein Register konnte nicht gelesen werden
+    pub fn poll_failed(&mut self, error: &ModbusMasterError, subscribers: &mut Subscribers) {
+        self.transition(ConnectionState::Degraded, error.to_string(), subscribers);
+    }
+
+    /// Die Schnittstelle konnte nicht geöffnet werden
+    pub fn lost(&mut self, error: &ModbusMasterError, subscribers: &mut Subscribers) {
+        self.transition(ConnectionState::Lost, error.to_string(), subscribers);
+    }
+
+    // Wechselt den Zustand, gleiche Zustände werden nicht erneut gesendet
+    fn transition(
+        &mut self,
+        state: ConnectionState,
+        reason: String,
+        subscribers: &mut Subscribers,
+    ) {
+        if !is_transition(self.state, state) {
+            return;
+        }
+        debug!("Connection: {:?} -> {:?} ({})", self.state, state, reason);
+
+        self.state = state;
+        subscribers.publish(ModbusMasterEvent::ConnectionState {
+            state,
+            reason,
+            last_poll: self.last_poll,
+        });
+    }
+}
+
+// Ist der Wechsel von `from` nach `to` ein Zustandswechsel der gemeldet wird?
+//
+// Aus `Lost` geht es nur über eine neue Live Ansicht (`Connecting`) oder
+// deren Ende (`Offline`) heraus.
+fn is_transition(from: ConnectionState, to: ConnectionState) -> bool {
+    use ConnectionState::*;
+
+    match (from, to) {
+        (from, to) if from == to => false,
+        (Lost, Online) | (Lost, Degraded) => false,
+        (Offline, Online) | (Offline, Degraded) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ConnectionState::*;
+
+    #[test]
+    fn same_state_is_no_transition() {
+        assert!(!is_transition(Online, Online));
+        assert!(!is_transition(Degraded, Degraded));
+    }
+
+    #[test]
+    fn live_view() {
+        assert!(is_transition(Offline, Connecting));
+        assert!(is_transition(Connecting, Online));
+        assert!(is_transition(Connecting, Degraded));
+        assert!(is_transition(Online, Degraded));
+        assert!(is_transition(Degraded, Online));
+        assert!(is_transition(Degraded, Lost));
+        assert!(is_transition(Online, Offline));
+        // Neustart des Sensors
+        assert!(is_transition(Online, Connecting));
+    }
+
+    #[test]
+    fn lost_needs_reconnect() {
+        assert!(!is_transition(Lost, Online));
+        assert!(!is_transition(Lost, Degraded));
+        assert!(is_transition(Lost, Connecting));
+        assert!(is_transition(Lost, Offline));
+    }
+
+    #[test]
+    fn offline_ignores_polls() {
+        assert!(!is_transition(Offline, Online));
+        assert!(!is_transition(Offline, Degraded));
+    }
+}
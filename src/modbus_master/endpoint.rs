@@ -0,0 +1,182 @@
+use crate::modbus_master::{LineSettings, ModbusMasterError};
+use libmodbus::{Modbus, ModbusTCPPI};
+use std::fmt;
+
+/// Standard Port für Modbus TCP
+pub const MODBUS_TCP_PORT: u16 = 502;
+
+/// Wohin der Modbus Master verbindet
+///
+/// Der Modbus Master erhält die Schnittstelle als Text (`tty_path`). Neben
+/// seriellen Schnittstellen sind auch Pseudo-Terminals, z.B. eines Simulators,
+/// und Netzwerk Gateways möglich:
+///
+/// | Text                  | Endpunkt                                  |
+/// | --------------------- | ----------------------------------------- |
+/// | `/dev/ttyUSB0`, `COM3`| serielle Schnittstelle                    |
+/// | `/dev/pts/3`          | Pseudo-Terminal                           |
+/// | `tcp://host:502`      | Modbus TCP Gateway, Port optional (502)   |
+/// | `rtu+tcp://host:port` | Gateway das RTU Frames über TCP weitergibt|
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::modbus_master::Endpoint;
+///
+/// let endpoint = Endpoint::parse("tcp://192.168.1.10").unwrap();
+/// assert_eq!(endpoint.to_string(), "tcp://192.168.1.10:502");
+/// assert!(Endpoint::parse("/dev/ttyUSB0").unwrap().is_serial());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Endpoint {
+    /// Serielle Schnittstelle oder Pseudo-Terminal
+    Serial(String),
+    /// Modbus TCP Gateway
+    Tcp {
+        /// Hostname oder IP Adresse
+        host: String,
+        /// TCP Port
+        port: u16,
+    },
+    /// Gateway das RTU Frames unverändert über TCP weitergibt
+    RtuOverTcp {
+        /// Hostname oder IP Adresse
+        host: String,
+        /// TCP Port
+        port: u16,
+    },
+}
+
+impl Endpoint {
+    /// Erkennt den Endpunkt am Text der Schnittstelle
+    pub fn parse(text: &str) -> Result<Self, ModbusMasterError> {
+        let text = text.trim();
+        let invalid = || ModbusMasterError::InvalidEndpoint(text.to_string());
+
+        let (scheme, authority) = match text.find("://") {
+            Some(pos) => (&text[..pos], &text[pos + 3..]),
+            None if text.is_empty() => return Err(invalid()),
+            None => return Ok(Endpoint::Serial(text.to_string())),
+        };
+        let default_port = match scheme {
+            "tcp" => Some(MODBUS_TCP_PORT),
+            // Für RTU über TCP gibt es keinen Standard Port
+            "rtu+tcp" => None,
+            _ => return Err(invalid()),
+        };
+        let (host, port) = split_host_port(authority.trim_end_matches('/')).ok_or_else(invalid)?;
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None => default_port.ok_or_else(invalid)?,
+        };
+
+        Ok(match scheme {
+            "tcp" => Endpoint::Tcp { host, port },
+            _ => Endpoint::RtuOverTcp { host, port },
+        })
+    }
+
+    /// Ist der Endpunkt eine serielle Schnittstelle?
+    ///
+    /// Nur hier sind Schnittstellenparameter und Mithören möglich.
+    pub fn is_serial(&self) -> bool {
+        matches!(self, Endpoint::Serial(_))
+    }
+
+    /// Erstellt den Modbus Context für den Endpunkt
+    ///
+    /// `line_settings` gelten nur für serielle Schnittstellen.
+    pub fn open(&self, line_settings: &LineSettings) -> Result<Modbus, ModbusMasterError> {
+        match self {
+            Endpoint::Serial(tty_path) => Ok(line_settings.open(tty_path)?),
+            Endpoint::Tcp { host, port } => Ok(Modbus::new_tcp_pi(host, &port.to_string())?),
+            // libmodbus kennt kein RTU über TCP, das übernimmt der `NativePort`
+            Endpoint::RtuOverTcp { .. } => {
+                Err(ModbusMasterError::UnsupportedEndpoint(self.to_string()))
+            }
+        }
+    }
+}
+
+/// Prüft ob `tty_path` eine serielle Schnittstelle ist
+///
+/// Für Aufgaben die Schnittstellenparameter ändern, z.B. die automatische
+/// Erkennung der Baudrate.
+pub fn require_serial(tty_path: &str) -> Result<(), ModbusMasterError> {
+    if Endpoint::parse(tty_path)?.is_serial() {
+        Ok(())
+    } else {
+        Err(ModbusMasterError::SerialOnly(tty_path.to_string()))
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Serial(tty_path) => write!(f, "{}", tty_path),
+            Endpoint::Tcp { host, port } => write!(f, "tcp://{}:{}", host_name(host), port),
+            Endpoint::RtuOverTcp { host, port } => {
+                write!(f, "rtu+tcp://{}:{}", host_name(host), port)
+            }
+        }
+    }
+}
+
+// Trennt `host:port`, IPv6 Adressen stehen in eckigen Klammern (`[::1]:502`)
+fn split_host_port(authority: &str) -> Option<(String, Option<&str>)> {
+    let (host, rest) = if authority.starts_with('[') {
+        let end = authority.find(']')?;
+        (&authority[1..end], &authority[end + 1..])
+    } else {
+        match authority.rfind(':') {
+            Some(pos) => (&authority[..pos], &authority[pos..]),
+            None => (authority, ""),
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match rest {
+        "" => None,
+        _ if rest.starts_with(':') => Some(&rest[1..]),
+        _ => return None,
+    };
+
+    Some((host.to_string(), port))
+}
+
+// IPv6 Adressen wieder in eckigen Klammern
+fn host_name(host: &str) -> String {
+    if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_endpoints() {
+        assert_eq!(
+            Endpoint::parse("/dev/pts/3").unwrap(),
+            Endpoint::Serial("/dev/pts/3".to_string())
+        );
+        assert_eq!(
+            Endpoint::parse("rtu+tcp://gateway:4001").unwrap(),
+            Endpoint::RtuOverTcp {
+                host: "gateway".to_string(),
+                port: 4001
+            }
+        );
+        assert_eq!(
+            Endpoint::parse("tcp://[::1]").unwrap().to_string(),
+            "tcp://[::1]:502"
+        );
+        assert!(Endpoint::parse("rtu+tcp://gateway").is_err());
+        assert!(Endpoint::parse("tcp://:502").is_err());
+        assert!(Endpoint::parse("tcp://host:port").is_err());
+        assert!(Endpoint::parse("udp://host:502").is_err());
+    }
+}
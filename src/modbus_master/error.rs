@@ -1,3 +1,4 @@
+use crate::{modbus_master::LineSettings, serial_interface::LockHolder};
 use libmodbus::prelude::Error as LibModbusError;
 use std::{fmt, io};
 
@@ -24,6 +25,79 @@ pub enum ModbusMasterError {
         /// Libmodbus Error
         source: LibModbusError,
     },
+    /// Fehler bei der Modbus Kommunikation, ein Register konnte nicht geschrieben werden
+    WriteRegister {
+        /// Register Nummer
+        reg_nr: u16,
+        /// Libmodbus Error
+        source: LibModbusError,
+    },
+    /// Ein Register konnte nicht geschrieben werden, die vorherigen Werte wurden wiederhergestellt
+    RolledBack {
+        /// Register Nummer
+        reg_nr: u16,
+        /// Libmodbus Error
+        source: LibModbusError,
+    },
+    /// Ein Register konnte nicht geschrieben werden und die vorherigen Werte
+    /// konnten nicht wiederhergestellt werden
+    RollbackFailed {
+        /// Register Nummer
+        reg_nr: u16,
+        /// Libmodbus Error
+        source: LibModbusError,
+    },
+    /// Der zurück gelesene Wert stimmt nicht mit dem geschriebenen Wert überein
+    VerifyFailed {
+        /// Register Nummer
+        reg_nr: u16,
+        /// Geschriebener Wert
+        expected: u16,
+        /// Zurück gelesener Wert
+        actual: u16,
+    },
+    /// Die Schnittstelle ist nicht geöffnet
+    NotConnected,
+    /// Der Modbus Master konnte nicht erreicht werden
+    Unreachable,
+    /// Es läuft bereits ein Bus Scan
+    ScanRunning,
+    /// Es läuft bereits eine Erkennung der Schnittstellenparameter
+    DetectRunning,
+    /// Die Schnittstellenparameter können nicht am Sensor eingestellt werden
+    UnsupportedLineSettings(LineSettings),
+    /// Der Sensor antwortet nicht mit den neuen Schnittstellenparametern, die
+    /// alten Parameter wurden wiederhergestellt
+    LineSettingsReverted {
+        /// Neue Parameter
+        new: LineSettings,
+        /// Wiederhergestellte Parameter
+        old: LineSettings,
+    },
+    /// Der Sensor antwortet weder mit den neuen noch mit den alten Parametern
+    LineSettingsLost {
+        /// Neue Parameter
+        new: LineSettings,
+        /// Alte Parameter
+        old: LineSettings,
+    },
+    /// Auf keiner Adresse hat ein Sensor geantwortet
+    NoDeviceFound,
+    /// Die neue Modbus Adresse liegt nicht im gültigen Bereich 1 bis 247
+    InvalidSlaveId(u16),
+    /// Der Text ist keine gültige Schnittstelle bzw. kein gültiger Endpunkt
+    InvalidEndpoint(String),
+    /// Der Endpunkt kann nicht geöffnet werden
+    UnsupportedEndpoint(String),
+    /// Die Funktion ist nur an einer seriellen Schnittstelle möglich
+    SerialOnly(String),
+    /// Die Schnittstelle ist von einem anderen Programm gesperrt
+    PortBusy {
+        /// Schnittstelle
+        tty_path: String,
+        /// Inhaber der Sperre
+        holder: LockHolder,
+    },
 }
 
 impl fmt::Display for ModbusMasterError {
@@ -44,6 +118,84 @@ impl fmt::Display for ModbusMasterError {
                 "Modbus Fehler beim Lesen der Schreib/Lese Input Registers {}",
                 reg_nr
             ),
+            ModbusMasterError::WriteRegister { reg_nr, ref source } => write!(
+                f,
+                "Modbus Fehler beim Schreiben des Registers {}: {:?}",
+                reg_nr, source
+            ),
+            ModbusMasterError::RolledBack { reg_nr, ref source } => write!(
+                f,
+                "Modbus Fehler beim Schreiben des Registers {}: {:?}\r\nDie vorherigen Werte wurden wiederhergestellt.",
+                reg_nr, source
+            ),
+            ModbusMasterError::RollbackFailed { reg_nr, ref source } => write!(
+                f,
+                "Modbus Fehler beim Schreiben des Registers {}: {:?}\r\nDie vorherigen Werte konnten nicht wiederhergestellt werden!",
+                reg_nr, source
+            ),
+            ModbusMasterError::VerifyFailed {
+                reg_nr,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Register {} wurde nicht übernommen (geschrieben: {}, gelesen: {})",
+                reg_nr, expected, actual
+            ),
+            ModbusMasterError::NotConnected => write!(f, "Schnittstelle nicht geöffnet"),
+            ModbusMasterError::Unreachable => {
+                write!(f, "Modbus Master konnte nicht erreicht werden")
+            }
+            ModbusMasterError::ScanRunning => write!(f, "Es läuft bereits ein Bus Scan"),
+            ModbusMasterError::DetectRunning => {
+                write!(f, "Es läuft bereits eine Erkennung der Schnittstellenparameter")
+            }
+            ModbusMasterError::UnsupportedLineSettings(line_settings) => write!(
+                f,
+                "Die Schnittstellenparameter {} werden vom Sensor nicht unterstützt",
+                line_settings
+            ),
+            ModbusMasterError::LineSettingsReverted { new, old } => write!(
+                f,
+                "Der Sensor antwortet nicht mit {}, die Schnittstellenparameter {} wurden wiederhergestellt",
+                new, old
+            ),
+            ModbusMasterError::LineSettingsLost { new, old } => write!(
+                f,
+                "Der Sensor antwortet weder mit {} noch mit {}, bitte 'Baudrate erkennen ...' verwenden!",
+                new, old
+            ),
+            ModbusMasterError::NoDeviceFound => {
+                write!(f, "Auf keiner Modbus Adresse antwortet ein Sensor")
+            }
+            ModbusMasterError::InvalidSlaveId(slave_id) => write!(
+                f,
+                "Ungültige Modbus Adresse {}, erlaubt sind 1 bis 247",
+                slave_id
+            ),
+            ModbusMasterError::InvalidEndpoint(ref endpoint) => write!(
+                f,
+                "Ungültige Schnittstelle: {} (z.B. /dev/ttyUSB0, tcp://host:502 oder rtu+tcp://host:port)",
+                endpoint
+            ),
+            ModbusMasterError::UnsupportedEndpoint(ref endpoint) => write!(
+                f,
+                "RTU über TCP wird mit libmodbus nicht unterstützt: {}",
+                endpoint
+            ),
+            ModbusMasterError::SerialOnly(ref endpoint) => write!(
+                f,
+                "{} ist keine serielle Schnittstelle, Schnittstellenparameter und Mithören sind nicht möglich",
+                endpoint
+            ),
+            ModbusMasterError::PortBusy {
+                ref tty_path,
+                ref holder,
+            } => write!(
+                f,
+                "Die Schnittstelle {} wird bereits von {} verwendet",
+                tty_path, holder
+            ),
         }
     }
 }
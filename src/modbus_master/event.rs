@@ -0,0 +1,98 @@
+use crate::modbus_master::{
+    BusDevice, ConnectionState, LineSettings, ModbusMasterError, Statistics, TrafficRecord,
+};
+use chrono::{DateTime, Local};
+use futures::channel::mpsc::UnboundedSender;
+use std::sync::Arc;
+
+/// Ereignisse die der Modbus Master an alle Abonnenten sendet
+///
+/// Die Werte der Live Ansicht und der Fortschritt von Bus Scan und Erkennung
+/// der Schnittstellenparameter werden nicht
+/// als Antwort auf eine Anfrage geliefert, sondern laufend über diese
+/// Ereignisse. Abonniert wird mit [`ModbusMaster::subscribe`](super::ModbusMaster::subscribe).
+#[derive(Clone, Debug)]
+pub enum ModbusMasterEvent {
+    /// Ein Durchlauf über die Lese-Register eines Sensors ist abgeschlossen
+    Rregs {
+        /// Modbus Slave ID des Sensors
+        slave: u8,
+        /// Geänderte Werte (Register Nummer, Wert)
+        values: Vec<(u16, u16)>,
+    },
+    /// Ein Durchlauf über die Schreib.-/ Lese-Register eines Sensors ist abgeschlossen
+    Rwregs {
+        /// Modbus Slave ID des Sensors
+        slave: u8,
+        /// Geänderte Werte (Register Nummer, Wert)
+        values: Vec<(u16, u16)>,
+    },
+    /// Der Zustand der Verbindung hat sich geändert
+    ConnectionState {
+        /// Neuer Zustand
+        state: ConnectionState,
+        /// Grund des Zustandswechsels
+        reason: String,
+        /// Zeitpunkt an dem zuletzt ein Register erfolgreich gelesen wurde
+        last_poll: Option<DateTime<Local>>,
+    },
+    /// Fortschritt des Bus Scans
+    ScanProgress {
+        /// Anzahl abgefragter Adressen
+        done: usize,
+        /// Anzahl aller Adressen
+        total: usize,
+    },
+    /// Der Bus Scan hat einen Sensor gefunden
+    ScanDeviceFound(BusDevice),
+    /// Der Bus Scan ist beendet
+    ScanFinished {
+        /// Anzahl gefundener Sensoren
+        found: usize,
+        /// Wurde der Scan abgebrochen?
+        cancelled: bool,
+    },
+    /// Der Bus Scan ist mit einem Fehler abgebrochen
+    ScanFailed(Arc<ModbusMasterError>),
+    /// Fortschritt der Erkennung der Schnittstellenparameter
+    DetectProgress {
+        /// Anzahl probierter Kombinationen aus Parametern und Adresse
+        done: usize,
+        /// Anzahl aller Kombinationen
+        total: usize,
+    },
+    /// Die Erkennung der Schnittstellenparameter ist beendet
+    DetectFinished {
+        /// Slave ID und Parameter unter denen der Sensor geantwortet hat,
+        /// `None` wenn er mit keinen Parametern antwortet
+        found: Option<(u8, LineSettings)>,
+        /// Wurde die Erkennung abgebrochen?
+        cancelled: bool,
+    },
+    /// Die Erkennung der Schnittstellenparameter ist mit einem Fehler abgebrochen
+    DetectFailed(Arc<ModbusMasterError>),
+    /// Neue Einträge des Mitschnitts (Anfragen und Antworten)
+    Traffic(Vec<TrafficRecord>),
+    /// Die Statistik der Kommunikation hat sich geändert
+    Statistics(Statistics),
+}
+
+/// Abonnenten der Ereignisse
+///
+/// Abonnenten deren Empfänger nicht mehr existiert werden beim nächsten
+/// Ereignis entfernt.
+#[derive(Debug, Default)]
+pub struct Subscribers(Vec<UnboundedSender<ModbusMasterEvent>>);
+
+impl Subscribers {
+    /// Fügt einen Abonnenten hinzu
+    pub fn add(&mut self, subscriber: UnboundedSender<ModbusMasterEvent>) {
+        self.0.push(subscriber);
+    }
+
+    /// Sendet ein Ereignis an alle Abonnenten
+    pub fn publish(&mut self, event: ModbusMasterEvent) {
+        self.0
+            .retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+    }
+}
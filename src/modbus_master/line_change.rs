@@ -0,0 +1,242 @@
+//! Schnittstellenparameter eines Sensors ändern
+//!
+//! Baudrate (Rwreg 81) und Modbus Mode (Rwreg 82) übernimmt der Sensor sofort,
+//! danach antwortet er nur noch mit den neuen Parametern. Beide Register werden
+//! deshalb in einer Anfrage geschrieben, anschließend wird die Schnittstelle mit
+//! den neuen Parametern geöffnet und geprüft ob der Sensor antwortet. Antwortet
+//! er nicht, werden die alten Werte zurück geschrieben.
+use crate::modbus_master::{
+    bus::Bus, endpoint, line_settings::MODBUS_MODES, scanner::BAUD_RATES, LineSettings,
+    ModbusMasterError,
+};
+use std::thread;
+use std::time::Duration;
+
+// Wartezeit bis der Sensor mit den neuen Parametern arbeitet
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+// So oft wird geprüft ob der Sensor antwortet
+const VERIFY_ATTEMPTS: usize = 3;
+
+/// Werte von Rwreg 81 (Baudrate) und Rwreg 82 (Modbus Mode) für die Parameter
+///
+/// `None` wenn der Sensor die Parameter nicht unterstützt.
+pub fn register_values(line_settings: &LineSettings) -> Option<(u16, u16)> {
+    if line_settings.data_bits != 8 {
+        return None;
+    }
+    let (baud_value, _) = BAUD_RATES
+        .iter()
+        .find(|(_value, baud_rate)| *baud_rate == line_settings.baud_rate)?;
+    let (mode_value, _, _) = MODBUS_MODES.iter().find(|(_value, parity, stop_bits)| {
+        *parity == line_settings.parity && *stop_bits == line_settings.stop_bits
+    })?;
+    Some((*baud_value, *mode_value))
+}
+
+/// Register Nummer der Baudrate, der Modbus Mode folgt direkt danach
+///
+/// Rwreg 81 bzw. 51 bei der ersten NE4 Platine (Schreibschutz Register 49).
+pub fn baud_rate_register(reg_protection: u16) -> u16 {
+    if reg_protection == 79 {
+        81
+    } else {
+        51
+    }
+}
+
+/// Ist `reg_nr` die Baudrate oder der Modbus Mode?
+pub fn is_line_register(reg_protection: u16, reg_nr: u16) -> bool {
+    let baud_rate_register = baud_rate_register(reg_protection);
+    reg_nr == baud_rate_register || reg_nr == baud_rate_register + 1
+}
+
+/// Parameter nach dem Schreiben von `value` in die Baudrate bzw. den Modbus Mode
+///
+/// `None` wenn der Wert unbekannt ist.
+pub fn with_register_value(
+    line_settings: LineSettings,
+    reg_protection: u16,
+    reg_nr: u16,
+    value: u16,
+) -> Option<LineSettings> {
+    if reg_nr == baud_rate_register(reg_protection) {
+        let (_, baud_rate) = BAUD_RATES.iter().find(|(v, _)| *v == value)?;
+        Some(LineSettings {
+            baud_rate: *baud_rate,
+            ..line_settings
+        })
+    } else if reg_nr == baud_rate_register(reg_protection) + 1 {
+        let (_, parity, stop_bits) = MODBUS_MODES.iter().find(|(v, _, _)| *v == value)?;
+        Some(LineSettings {
+            parity: *parity,
+            stop_bits: *stop_bits,
+            ..line_settings
+        })
+    } else {
+        None
+    }
+}
+
+/// Stellt den Sensor und die Sitzung auf neue Schnittstellenparameter um
+///
+/// Antwortet der Sensor danach nicht, werden die alten Werte zurück geschrieben
+/// und die Sitzung behält ihre Parameter. Der Fehler sagt dann ob der Sensor
+/// mit den alten Parametern wieder erreichbar ist.
+pub fn change(
+    bus: &mut Bus,
+    tty_path: &str,
+    slave: u8,
+    reg_protection: u16,
+    new: LineSettings,
+) -> Result<(), ModbusMasterError> {
+    info!(
+        "Change line settings: tty_path: {}, slave: {}, new: {}",
+        tty_path, slave, new
+    );
+    endpoint::require_serial(tty_path)?;
+
+    let old = bus.line_settings();
+    let values = register_values(&new).ok_or(ModbusMasterError::UnsupportedLineSettings(new))?;
+    let values = [values.0, values.1];
+    let reg_nr = baud_rate_register(reg_protection);
+
+    // Alte Werte merken
+    let mut old_values = [0u16; 2];
+    bus.unlock(tty_path, slave, reg_protection)?
+        .read_registers(reg_nr, 2, &mut old_values)
+        .map_err(|source| ModbusMasterError::ReadHoldingRegister { reg_nr, source })?;
+
+    // Beide Register in einer Anfrage, mit dem ersten wäre der Sensor sonst
+    // für das zweite nicht mehr erreichbar. Der Sensor stellt eventuell schon
+    // vor der Antwort um, eine fehlende Antwort ist deshalb kein Fehler.
+    if let Err(error) = bus
+        .unlock(tty_path, slave, reg_protection)?
+        .write_registers(reg_nr, 2, &values)
+    {
+        debug!("Change line settings: no response to write: {:?}", error);
+    }
+
+    switch(bus, new);
+    if answers(bus, tty_path, slave, reg_protection, &values) {
+        info!("Change line settings: sensor answers with {}", new);
+        return Ok(());
+    }
+
+    // Zurück schreiben, zuerst mit den neuen Parametern falls der Sensor
+    // umgestellt hat aber nicht richtig antwortet
+    warn!(
+        "Change line settings: sensor does not answer with {}, revert",
+        new
+    );
+    if let Ok(mut modbus) = bus.unlock(tty_path, slave, reg_protection) {
+        let _ = modbus.write_registers(reg_nr, 2, &old_values);
+    }
+    switch(bus, old);
+    if answers(bus, tty_path, slave, reg_protection, &old_values) {
+        Err(ModbusMasterError::LineSettingsReverted { new, old })
+    } else {
+        Err(ModbusMasterError::LineSettingsLost { new, old })
+    }
+}
+
+// Öffnet die Schnittstelle beim nächsten Zugriff mit `line_settings`
+fn switch(bus: &mut Bus, line_settings: LineSettings) {
+    bus.close();
+    bus.set_line_settings(line_settings);
+    thread::sleep(SETTLE_TIME);
+}
+
+// Antwortet der Sensor mit den erwarteten Werten?
+//
+// Nach dem Umstellen ist die Schnittstelle neu geöffnet und die Platine gilt
+// als gesperrt. Manche Firmware gibt Baudrate und Modbus Mode nur entsperrt
+// heraus, deshalb wird vor dem Lesen entsperrt.
+fn answers(
+    bus: &mut Bus,
+    tty_path: &str,
+    slave: u8,
+    reg_protection: u16,
+    expected: &[u16],
+) -> bool {
+    let reg_nr = baud_rate_register(reg_protection);
+    for attempt in 1..=VERIFY_ATTEMPTS {
+        let mut values = vec![0u16; expected.len()];
+        let result = bus
+            .unlock(tty_path, slave, reg_protection)
+            .and_then(|mut modbus| {
+                modbus.read_registers(reg_nr, expected.len() as u16, &mut values)?;
+                Ok(())
+            });
+        match result {
+            Ok(_) if values == expected => return true,
+            Ok(_) => debug!(
+                "Change line settings: attempt {}: read {:?}, expected {:?}",
+                attempt, values, expected
+            ),
+            Err(error) => {
+                debug!("Change line settings: attempt {}: {}", attempt, error);
+                bus.close();
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_values_of_line_settings() {
+        assert_eq!(register_values(&LineSettings::default()), Some((1, 0)));
+        let settings = LineSettings {
+            baud_rate: 38400,
+            parity: 'E',
+            ..Default::default()
+        };
+        assert_eq!(register_values(&settings), Some((3, 3)));
+    }
+
+    #[test]
+    fn line_settings_from_register_value() {
+        let settings = with_register_value(LineSettings::default(), 79, 81, 2).unwrap();
+        assert_eq!(settings.to_string(), "19200 8N1");
+        let settings = with_register_value(settings, 79, 82, 1).unwrap();
+        assert_eq!(settings.to_string(), "19200 8O1");
+        assert!(with_register_value(settings, 79, 82, 5).is_none());
+        assert!(is_line_register(49, 52));
+        assert!(!is_line_register(79, 80));
+    }
+
+    #[test]
+    fn modbus_modes_from_description() {
+        // Beschreibung RA-GAS Sensor-MB, RWreg_82
+        let modes = [
+            (0, "9600 8N1"),
+            (1, "9600 8O1"),
+            (2, "9600 8O2"),
+            (3, "9600 8E1"),
+            (4, "9600 8E2"),
+        ];
+        for &(value, expected) in modes.iter() {
+            let settings = with_register_value(LineSettings::default(), 79, 82, value).unwrap();
+            assert_eq!(settings.to_string(), expected);
+            assert_eq!(register_values(&settings), Some((1, value)));
+        }
+    }
+
+    #[test]
+    fn unsupported_line_settings() {
+        let settings = LineSettings {
+            baud_rate: 115200,
+            ..Default::default()
+        };
+        assert_eq!(register_values(&settings), None);
+        let settings = LineSettings {
+            parity: 'N',
+            stop_bits: 2,
+            ..Default::default()
+        };
+        assert_eq!(register_values(&settings), None);
+    }
+}
@@ -0,0 +1,144 @@
+use libmodbus::{prelude::Error as LibModbusError, Modbus, ModbusRTU};
+use std::fmt;
+
+/// Modbus Mode der Sensoren (Rwreg 82)
+///
+/// Die Erkennung der Schnittstellenparameter probiert genau diese Modi.
+/// Tupple (Wert Rwreg 82, Parität, Stopbits), immer mit 8 Datenbits. Quelle ist
+/// die Erläuterung zu "RWreg_82" in
+/// `resources/27-10-2020_Beschreibung_RA-GAS Sensor-MB.pdf` (gleichlautend in
+/// der Ausgabe vom 25-05-2020):
+///
+/// | Wert | Datenbits | Stopbits | Parität  |
+/// |------|-----------|----------|----------|
+/// | 0    | 8         | 1        | keine    |
+/// | 1    | 8         | 1        | ungerade |
+/// | 2    | 8         | 2        | ungerade |
+/// | 3    | 8         | 1        | gerade   |
+/// | 4    | 8         | 2        | gerade   |
+pub const MODBUS_MODES: &[(u16, char, i32)] = &[
+    (0, 'N', 1),
+    (1, 'O', 1),
+    (2, 'O', 2),
+    (3, 'E', 1),
+    (4, 'E', 2),
+];
+
+/// Datenbits, Parität und Stopbits die bei der Erkennung mit Modbus ASCII
+/// probiert werden
+///
+/// Die Modbus Spezifikation sieht für ASCII 7 Datenbits mit gerader oder
+/// ungerader Parität vor, ohne Parität mit 2 Stopbits. Die Sensoren kennen laut
+/// Rwreg 82 nur RTU Modi. Tupple (Datenbits, Parität, Stopbits)
+pub const ASCII_FRAMINGS: &[(i32, char, i32)] = &[(7, 'E', 1), (7, 'O', 1), (7, 'N', 2)];
+
+/// Übertragungsart der Frames
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransmissionMode {
+    /// Binäre Frames mit CRC, getrennt durch Pausen (Standard)
+    Rtu,
+    /// Frames als Hex Zeichen mit LRC, von ':' und CR LF begrenzt
+    Ascii,
+}
+
+impl Default for TransmissionMode {
+    fn default() -> Self {
+        TransmissionMode::Rtu
+    }
+}
+
+impl fmt::Display for TransmissionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransmissionMode::Rtu => write!(f, "RTU"),
+            TransmissionMode::Ascii => write!(f, "ASCII"),
+        }
+    }
+}
+
+/// Parameter der seriellen Schnittstelle einer Sitzung
+///
+/// Alle Operationen des Modbus Masters öffnen die Schnittstelle mit diesen
+/// Parametern. Standard ist 9600 Baud, 8N1, RTU.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LineSettings {
+    /// Baudrate
+    pub baud_rate: i32,
+    /// Parität 'N', 'E' oder 'O'
+    pub parity: char,
+    /// Datenbits
+    pub data_bits: i32,
+    /// Stopbits
+    pub stop_bits: i32,
+    /// Übertragungsart, RTU oder ASCII
+    pub transmission_mode: TransmissionMode,
+}
+
+impl Default for LineSettings {
+    fn default() -> Self {
+        LineSettings {
+            baud_rate: 9600,
+            parity: 'N',
+            data_bits: 8,
+            stop_bits: 1,
+            transmission_mode: TransmissionMode::Rtu,
+        }
+    }
+}
+
+impl LineSettings {
+    /// Erstellt einen Modbus RTU Context mit diesen Parametern
+    ///
+    /// libmodbus kennt kein ASCII, dafür öffnet der [`Bus`](super::Bus) einen
+    /// [`NativePort`](super::native::NativePort).
+    pub fn open(&self, tty_path: &str) -> Result<Modbus, LibModbusError> {
+        Modbus::new_rtu(
+            tty_path,
+            self.baud_rate,
+            self.parity,
+            self.data_bits,
+            self.stop_bits,
+        )
+    }
+}
+
+/// z.B. "9600 8N1", bei ASCII "9600 7E1 ASCII"
+impl fmt::Display for LineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{}{}",
+            self.baud_rate, self.data_bits, self.parity, self.stop_bits
+        )?;
+        match self.transmission_mode {
+            TransmissionMode::Rtu => Ok(()),
+            TransmissionMode::Ascii => write!(f, " ASCII"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default() {
+        assert_eq!(LineSettings::default().to_string(), "9600 8N1");
+    }
+
+    #[test]
+    fn display() {
+        let settings = LineSettings {
+            baud_rate: 19200,
+            parity: 'E',
+            ..Default::default()
+        };
+        assert_eq!(settings.to_string(), "19200 8E1");
+        let settings = LineSettings {
+            data_bits: 7,
+            transmission_mode: TransmissionMode::Ascii,
+            ..settings
+        };
+        assert_eq!(settings.to_string(), "19200 7E1 ASCII");
+    }
+}
@@ -1,31 +1,121 @@
 //! Modbus Master
+//!
+//! Der Modbus Master ist unabhängig von der GUI. Jede Operation liefert ihr
+//! Ergebnis als Future, die Werte der Live Ansicht und der Fortschritt von Bus
+//! Scan und Erkennung der Schnittstellenparameter werden als
+//! [`ModbusMasterEvent`] an alle Abonnenten gesendet.
+//!
+//! ```no_run
+//! # use rgms_konfig::modbus_master::ModbusMaster;
+//! # async fn example() {
+//! let modbus_master = ModbusMaster::new();
+//! match modbus_master.nullgas("/dev/ttyUSB0".to_string(), 247, 79, 1).await {
+//!     Ok(_) => println!("Nullpunkt gesetzt"),
+//!     Err(error) => println!("Fehler: {}", error),
+//! }
+//! # }
+//! ```
 
+/// Modbus ASCII Übertragung
+pub mod ascii;
+/// Geöffnete Schnittstelle
+pub mod bus;
+/// Zustand der Verbindung
+pub mod connection;
+/// Serielle Schnittstelle oder Netzwerk Gateway
+pub mod endpoint;
 /// ModbusMaster Fehler
 pub mod error;
+/// Ereignisse des Modbus Masters
+pub mod event;
+/// Schnittstellenparameter eines Sensors ändern
+pub mod line_change;
+/// Parameter der seriellen Schnittstelle
+pub mod line_settings;
+/// Modbus ohne libmodbus
+pub mod native;
+/// Live Ansicht
+pub mod poller;
+/// Richtungsumschaltung, Pausen und Treiber einer Schnittstelle
+pub mod port_options;
+/// Modbus RTU Übertragung
+pub mod rtu;
+/// Bus Scanner
+pub mod scanner;
+/// Zeitplan der Live Ansicht
+pub mod schedule;
+/// Adressierung des einzigen Sensors am Bus
+pub mod single_device;
+/// Statistik der Kommunikation
+pub mod statistics;
+/// Mitschnitt des Modbus Verkehrs
+pub mod traffic;
+/// Übertragung über libmodbus oder die eigene Umsetzung
+pub mod transport;
 
+pub use bus::{Bus, Link};
+pub use connection::{Connection, ConnectionState};
+pub use endpoint::Endpoint;
 pub use error::ModbusMasterError;
+pub use event::{ModbusMasterEvent, Subscribers};
+pub use line_settings::{LineSettings, TransmissionMode};
+pub use poller::{PollTarget, Poller};
+pub use port_options::PortOptions;
+pub use scanner::{BusDevice, DetectJob, ScanJob};
+pub use schedule::{PollRate, PollSchedule};
+pub use single_device::Addressing;
+pub use statistics::{ErrorKind, SlaveStatistics, Statistics};
+pub use traffic::{TrafficRecord, TrafficRecorder};
+pub use transport::Transport;
 
-use crate::{
-    gui::gtk3::{GuiMessage, *},
-    registers::{Rreg, Rwreg},
-};
-use futures::channel::mpsc::Sender;
-use libmodbus::{Modbus, ModbusClient, ModbusRTU};
-use std::sync::Arc;
-use std::sync::Mutex;
+use crate::registers::{Rreg, Rwreg};
+use futures::channel::{mpsc::UnboundedReceiver, oneshot};
+use libmodbus::prelude::Error as LibModbusError;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::future::Future;
+use std::ops::RangeInclusive;
 use std::thread;
-use tokio::{runtime::Runtime, sync::mpsc};
+use std::time::{Duration, Instant};
+use tokio::{runtime::Runtime, sync::mpsc, time};
 
+// Wartezeit nach dem Entsperren der Platine
 const LOCK_TIMEOUT: u64 = 20;
+// Anzahl Befehle die auf den Modbus Master warten können
+const QUEUE_SIZE: usize = 32;
+// Die Statistik wird höchstens einmal pro Intervall gesendet
+const STATISTICS_INTERVAL: Duration = Duration::from_secs(1);
+// Wartezeit bevor ein geschriebenes Register zurück gelesen wird
+const VERIFY_TIMEOUT: u64 = 100;
+// Wartezeit bis der Sensor nach einem Neustart wieder antwortet
+const RESTART_TIME: Duration = Duration::from_secs(3);
+// Höchstens so viele Register pro Anfrage mit 0x03 bzw. 0x10, größere Anzahlen
+// lehnt die Firmware mit Exception 03 ab (Funktionscode 3 und 16 in
+// `resources/27-10-2020_Beschreibung_RA-GAS Sensor-MB.pdf`)
+const MAX_BLOCK_LEN: usize = 10;
+
+/// Über den Responder sendet der Modbus Master das Ergebnis einer Anfrage zurück
+pub type Responder<T> = oneshot::Sender<Result<T, ModbusMasterError>>;
 
 /// Possible ModbusMaster commands
-/// TODO: Nutze Struct Enum Types Connect { tty: String, rregs: Vec<Rreg>, rwregs: Vec<Rwregs>, ...}
+///
+/// Die Nachrichten werden von den Funktionen des [`ModbusMaster`] erzeugt.
 #[derive(Debug)]
 pub enum ModbusMasterMessage {
-    /// Starte Control Loop
-    Connect(String, u8, Vec<Rreg>, Vec<Rwreg>, u16),
-    /// Stoppe Control Loop
-    Disconnect,
+    /// Startet die Live Ansicht
+    Connect {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Sensoren der Live Ansicht
+        targets: Vec<PollTarget>,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Beendet die Live Ansicht
+    Disconnect {
+        /// Ergebnis
+        responder: Responder<()>,
+    },
     /// Nullgas
     Nullgas {
         /// serielle Schnittstelle
@@ -36,6 +126,8 @@ pub enum ModbusMasterMessage {
         reg_protection: u16,
         /// Messzellen Nummer
         sensor_num: u16,
+        /// Ergebnis
+        responder: Responder<()>,
     },
     /// Messgas
     Messgas {
@@ -47,6 +139,8 @@ pub enum ModbusMasterMessage {
         reg_protection: u16,
         /// Messzellen Nummer
         sensor_num: u16,
+        /// Ergebnis
+        responder: Responder<()>,
     },
     /// Speichert die MCS Bus Konfiguration
     SetNewMcsBusId {
@@ -58,6 +152,10 @@ pub enum ModbusMasterMessage {
         new_slave_id: u16,
         /// Entsperr Register Nummer
         reg_protection: u16,
+        /// Geschriebenen Wert zurück lesen und vergleichen
+        verify: bool,
+        /// Ergebnis
+        responder: Responder<()>,
     },
     /// Speichert die Modbus Konfiguration
     SetNewModbusId {
@@ -69,18 +167,25 @@ pub enum ModbusMasterMessage {
         new_slave_id: u16,
         /// Entsperr Register Nummer
         reg_protection: u16,
+        /// Geschriebenen Wert zurück lesen und vergleichen
+        verify: bool,
+        /// Ergebnis
+        responder: Responder<()>,
     },
     /// Setzt die Arbeitsweise
-    // (String, u8, u16, u16),
     SetNewWorkingMode {
         /// serielle Schnittstelle
         tty_path: String,
         /// Modbus Slave ID
         slave: u8,
-        /// Neue Modbus Adresse
+        /// Neue Arbeitsweise
         working_mode: u16,
         /// Entsperr Register Nummer
         reg_protection: u16,
+        /// Geschriebenen Wert zurück lesen und vergleichen
+        verify: bool,
+        /// Ergebnis
+        responder: Responder<()>,
     },
     /// Update one register
     UpdateRegister {
@@ -88,13 +193,145 @@ pub enum ModbusMasterMessage {
         tty_path: String,
         /// Modbus Slave ID
         slave: u8,
-        /// Neue Modbus Adresse
+        /// Register Nummer
         reg_nr: u16,
         /// Entsperr Register Nummer
         reg_protection: u16,
         /// neuer wert
         new_value: u16,
+        /// Geschriebenen Wert zurück lesen und vergleichen
+        verify: bool,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Schreibt mehrere Register in einem Durchgang
+    WriteRegisters {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Modbus Slave ID
+        slave: u8,
+        /// Entsperr Register Nummer
+        reg_protection: u16,
+        /// Liste aus (Register Nummer, neuer Wert), wird in dieser Reihenfolge geschrieben
+        values: Vec<(u16, u16)>,
+        /// Bei einem Fehler die vorher gelesenen Werte wiederherstellen
+        rollback: bool,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Durchsucht den Bus nach Sensoren
+    Scan {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Bereich der Modbus Slave IDs
+        slaves: RangeInclusive<u8>,
+        /// Baudraten die durchsucht werden
+        baud_rates: Vec<i32>,
+        /// Ergebnis, der Scan wurde gestartet
+        responder: Responder<()>,
+    },
+    /// Bricht einen laufenden Bus Scan ab
+    CancelScan {
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Startet die Suche nach Baudrate und Parität unter denen ein Sensor antwortet
+    DetectLineSettings {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Modbus Slave ID, `None` für den einzigen Sensor am Bus
+        slave: Option<u8>,
+        /// Ergebnis, die Suche wurde gestartet
+        responder: Responder<()>,
+    },
+    /// Bricht eine laufende Erkennung der Schnittstellenparameter ab
+    CancelDetect {
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Setzt die Parameter der seriellen Schnittstelle für alle weiteren Operationen
+    SetLineSettings {
+        /// Neue Parameter
+        line_settings: LineSettings,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Setzt die Optionen der Schnittstellen für alle weiteren Operationen
+    SetPortOptions {
+        /// Optionen pro Schnittstelle (`tty_path`)
+        port_options: HashMap<String, PortOptions>,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Stellt den Sensor und die Sitzung auf neue Schnittstellenparameter um
+    ChangeLineSettings {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Modbus Slave ID
+        slave: u8,
+        /// Entsperr Register Nummer
+        reg_protection: u16,
+        /// Neue Parameter
+        line_settings: LineSettings,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Setzt die Modbus bzw. MCS Adresse des einzigen Sensors am Bus
+    SetSingleDeviceAddress {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Wie der Sensor angesprochen wird
+        addressing: Addressing,
+        /// MCS Adresse statt Modbus Adresse?
+        mcs_config: bool,
+        /// Neue Adresse
+        new_slave_id: u16,
+        /// Entsperr Register Nummer
+        reg_protection: u16,
+        /// Neue Adresse zurück lesen?
+        verify: bool,
+        /// Ergebnis, die Adresse unter der der Sensor gefunden wurde
+        responder: Responder<Option<u8>>,
+    },
+    /// Startet den Sensor neu, eventuell mit Grunddaten
+    Restart {
+        /// serielle Schnittstelle
+        tty_path: String,
+        /// Modbus Slave ID
+        slave: u8,
+        /// Entsperr Register Nummer
+        reg_protection: u16,
+        /// Neustart bzw. Grunddaten Code der Platine
+        code: u16,
+        /// Ergebnis
+        responder: Responder<()>,
     },
+    /// Setzt den Zeitplan der Live Ansicht
+    SetPollSchedule {
+        /// Neuer Zeitplan
+        schedule: PollSchedule,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Schaltet den Mitschnitt des Modbus Verkehrs ein oder aus
+    SetTrafficRecording {
+        /// Mitschnitt eingeschaltet?
+        enabled: bool,
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Liefert die Statistik der Sitzung
+    GetStatistics {
+        /// Ergebnis
+        responder: Responder<Statistics>,
+    },
+    /// Setzt die Statistik zurück
+    ResetStatistics {
+        /// Ergebnis
+        responder: Responder<()>,
+    },
+    /// Meldet einen Abonnenten für die Ereignisse an
+    Subscribe(futures::channel::mpsc::UnboundedSender<ModbusMasterEvent>),
 }
 
 /// Modbus Master
@@ -106,346 +343,878 @@ pub struct ModbusMaster {
 
 impl ModbusMaster {
     /// Erzeugt einen neuen Modbus Master
-    pub fn new(gui_tx: Sender<GuiMessage>) -> ModbusMaster {
+    ///
+    /// Der Thread des Modbus Masters ist der einzige Besitzer der seriellen
+    /// Schnittstelle. Alle Zugriffe werden hier nacheinander ausgeführt, so
+    /// dass sich Live Ansicht, Bus Scan und Schreibzugriffe nie überschneiden.
+    /// Befehle des Benutzers haben Vorrang, Live Ansicht und Bus Scan laufen
+    /// nur wenn keine Befehle warten.
+    pub fn new() -> ModbusMaster {
         // Komunikationskanäle
-        let (tx, mut rx) = mpsc::channel(1);
-
-        // Control Loop erzeugen
-        // Diese Funktion liefert den Empfänger-Teil eines Channels zurück. Über
-        // diesen kann mit dem Control Loop kommuniziert werden.
-        let mut control_loop_tx = spawn_control_loop();
+        let (tx, mut rx) = mpsc::channel(QUEUE_SIZE);
 
-        std::thread::spawn(move || {
+        thread::spawn(move || {
             let mut rt = Runtime::new().expect("Could not create Runtime");
+            let mut bus_owner = BusOwner::new();
+            let mut queue: VecDeque<ModbusMasterMessage> = VecDeque::new();
 
-            rt.block_on(async {
-                // Control variable die den Control Loop steuert
-                let is_online = Arc::new(Mutex::new(false));
-
-                while let Some(command) = rx.recv().await {
-                    match command {
-                        // Startet dem Control Loop
-                        ModbusMasterMessage::Connect(
-                            tty_path,
-                            slave,
-                            rregs,
-                            rwregs,
-                            reg_protection,
-                        ) => {
-                            info!("ModbusMasterMessage::Connect");
-                            // debug!("tty_path: {}, slave: {}, rregs: {:?}, rwregs: {:?}", tty_path, slave, rregs, rwregs);
-
-                            let mut state = is_online.lock().unwrap();
-                            *state = true;
-
-                            // Sende Start Commando an Control Loop
-                            match control_loop_tx.try_send(MsgControlLoop::Start(
-                                is_online.clone(),
-                                tty_path,
-                                slave,
-                                rregs,
-                                rwregs,
-                                reg_protection,
-                                gui_tx.clone(),
-                            )) {
-                                Ok(_) => {
-                                    // show_info(&gui_tx, "Live Ansicht gestartet");
-                                }
-                                Err(error) => show_warning(
-                                    &gui_tx,
-                                    &format!(
-                                        "Control Loop konnte nicht erreicht werden: {}",
-                                        error
-                                    ),
-                                ),
-                            }
-                        }
-                        ModbusMasterMessage::Disconnect => {
-                            info!("ModbusMasterMessage::Disconnect");
-                            let mut state = is_online.lock().unwrap();
-                            *state = false;
-                        }
-                        // Nullgas setzen
-                        ModbusMasterMessage::Nullgas {
-                            tty_path,
-                            slave,
-                            reg_protection,
-                            sensor_num,
-                        } => match set_nullgas(tty_path, slave, reg_protection, sensor_num) {
-                            Ok(_) => {
-                                show_info(&gui_tx, "Nullpunkt erfolgreich gesetzt");
-                            }
-                            Err(error) => show_error(
-                                &gui_tx,
-                                &format!("Nullgas konnte nicht gesetzt werden: {}", error),
-                            ),
-                        },
-                        // Messgas setzen
-                        ModbusMasterMessage::Messgas {
-                            tty_path,
-                            slave,
-                            reg_protection,
-                            sensor_num,
-                        } => match set_messgas(tty_path, slave, reg_protection, sensor_num) {
-                            Ok(_) => {
-                                show_info(&gui_tx, "Endwert Messgas erfolgreich gesetzt");
-                            }
-                            Err(error) => show_error(
-                                &gui_tx,
-                                &format!("Messgas konnte nicht gesetzt werden: {}", error),
-                            ),
-                        },
-                        // Neue MCS Bus ID setzen
-                        ModbusMasterMessage::SetNewMcsBusId {
-                            tty_path,
-                            slave,
-                            new_slave_id,
-                            reg_protection,
-                        } => {
-                            match set_new_mcs_bus_id(tty_path, slave, new_slave_id, reg_protection)
-                            {
-                                Ok(_) => {
-                                    show_info(
-                                        &gui_tx,
-                                        &format!(
-                                            "MCS BUS Adresse: <b>{}</b> gespeichert.",
-                                            &new_slave_id
-                                        ),
-                                    );
-                                }
-                                Err(error) => show_warning(
-                                    &gui_tx,
-                                    &format!(
-                                        "Konnte MCS Adresse '{}' nicht speichern:\r\n{}",
-                                        &new_slave_id, error
-                                    ),
-                                ),
-                            }
-                        }
-                        // Neue Modbus Slave ID setzen
-                        ModbusMasterMessage::SetNewModbusId {
-                            tty_path,
-                            slave,
-                            new_slave_id,
-                            reg_protection,
-                        } => {
-                            match set_new_modbus_id(tty_path, slave, new_slave_id, reg_protection) {
-                                Ok(_) => {
-                                    show_info(
-                                        &gui_tx,
-                                        &format!(
-                                            "Modbus Adresse: <b>{}</b> gespeichert.",
-                                            &new_slave_id
-                                        ),
-                                    );
-                                }
-                                Err(error) => show_warning(
-                                    &gui_tx,
-                                    &format!(
-                                        "Konnte Modbus Adresse '{}' nicht speichern:\r\n{}",
-                                        &new_slave_id, error
-                                    ),
-                                ),
-                            }
-                        }
-                        // Neue Arbeitsweise auf Platine speichern
-                        ModbusMasterMessage::SetNewWorkingMode {
-                            tty_path,
-                            slave,
-                            working_mode,
-                            reg_protection,
-                        } => {
-                            info!("ModbusMasterMessage::SetNewWorkingMode");
-                            // Stop control loop
-                            let mut state = is_online.lock().unwrap();
-                            *state = false;
-                            // Sende register
-                            match set_working_mode(tty_path, slave, working_mode, reg_protection) {
-                                Ok(_) => {
-                                    show_info(&gui_tx, "Arbeitsweise erfolgreich gesetzt");
-                                }
-                                Err(error) => show_warning(
-                                    &gui_tx,
-                                    &format!("Konnte Arbeitsweise nicht festlegen:\r\n{}", error),
-                                ),
-                            }
-                        }
-                        // Update ein einzelnes Register
-                        ModbusMasterMessage::UpdateRegister {
-                            tty_path,
-                            slave,
-                            reg_nr,
-                            reg_protection,
-                            new_value,
-                        } => {
-                            match update_register(
-                                tty_path,
-                                slave,
-                                reg_nr,
-                                reg_protection,
-                                new_value,
-                            ) {
-                                Ok(_) => {
-                                    show_info(&gui_tx, "Register erfolgreich aktualisiert");
-                                }
-                                Err(error) => show_warning(
-                                    &gui_tx,
-                                    &format!("Konnte Arbeitsweise nicht festlegen:\r\n{}", error),
-                                ),
+            loop {
+                // Bis die Hintergrundaufgabe wieder fällig ist wird auf den
+                // nächsten Befehl gewartet, ohne Hintergrundaufgabe unbegrenzt
+                if queue.is_empty() {
+                    let received = match bus_owner.idle_time() {
+                        None => rt.block_on(rx.recv()).map(Some),
+                        Some(wait) if wait > Duration::from_secs(0) => {
+                            match rt.block_on(time::timeout(wait, rx.recv())) {
+                                Ok(command) => command.map(Some),
+                                Err(_elapsed) => Some(None),
                             }
                         }
+                        Some(_) => Some(None),
+                    };
+                    match received {
+                        Some(Some(command)) => queue.push_back(command),
+                        // Die Hintergrundaufgabe ist fällig
+                        Some(None) => {}
+                        // Alle Sender wurden verworfen
+                        None => break,
                     }
                 }
-            });
+                // Alle wartenden Befehle übernehmen
+                while let Ok(command) = rx.try_recv() {
+                    queue.push_back(command);
+                }
+
+                match queue.pop_front() {
+                    Some(command) => bus_owner.handle(command),
+                    None => bus_owner.step(),
+                }
+                bus_owner.publish_traffic();
+                bus_owner.publish_statistics();
+            }
         });
 
         ModbusMaster { tx }
     }
+
+    /// Abonniert die Ereignisse des Modbus Masters
+    ///
+    /// Jeder Abonnent erhält alle Ereignisse die nach dem Abonnieren auftreten.
+    pub fn subscribe(&self) -> UnboundedReceiver<ModbusMasterEvent> {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let _ = self.tx.clone().try_send(ModbusMasterMessage::Subscribe(tx));
+        rx
+    }
+
+    /// Startet die Live Ansicht
+    ///
+    /// Die gelesenen Werte werden als [`ModbusMasterEvent::Rregs`] und
+    /// [`ModbusMasterEvent::Rwregs`] gesendet, Änderungen des Verbindungszustands
+    /// als [`ModbusMasterEvent::ConnectionState`].
+    pub fn connect(
+        &self,
+        tty_path: String,
+        slave: u8,
+        rregs: Vec<Rreg>,
+        rwregs: Vec<Rwreg>,
+        reg_protection: u16,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        let target = PollTarget::new(slave, rregs, rwregs, reg_protection);
+        self.connect_line(tty_path, vec![target])
+    }
+
+    /// Startet die Live Ansicht für mehrere Sensoren am selben Bus
+    ///
+    /// Die Sensoren werden abwechselnd gelesen, die Werte jedes Sensors werden
+    /// mit seiner Modbus Slave ID gesendet.
+    pub fn connect_line(
+        &self,
+        tty_path: String,
+        targets: Vec<PollTarget>,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::Connect {
+            tty_path,
+            targets,
+            responder,
+        })
+    }
+
+    /// Beendet die Live Ansicht
+    pub fn disconnect(&self) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::Disconnect { responder })
+    }
+
+    /// Setzt den Nullpunkt der Messzelle `sensor_num`
+    pub fn nullgas(
+        &self,
+        tty_path: String,
+        slave: u8,
+        reg_protection: u16,
+        sensor_num: u16,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::Nullgas {
+            tty_path,
+            slave,
+            reg_protection,
+            sensor_num,
+            responder,
+        })
+    }
+
+    /// Setzt den Endwert Messgas der Messzelle `sensor_num`
+    pub fn messgas(
+        &self,
+        tty_path: String,
+        slave: u8,
+        reg_protection: u16,
+        sensor_num: u16,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::Messgas {
+            tty_path,
+            slave,
+            reg_protection,
+            sensor_num,
+            responder,
+        })
+    }
+
+    /// Speichert die MCS Bus Adresse (Rwreg 95)
+    pub fn set_new_mcs_bus_id(
+        &self,
+        tty_path: String,
+        slave: u8,
+        new_slave_id: u16,
+        reg_protection: u16,
+        verify: bool,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetNewMcsBusId {
+            tty_path,
+            slave,
+            new_slave_id,
+            reg_protection,
+            verify,
+            responder,
+        })
+    }
+
+    /// Speichert die Modbus Adresse (Rwreg 80)
+    pub fn set_new_modbus_id(
+        &self,
+        tty_path: String,
+        slave: u8,
+        new_slave_id: u16,
+        reg_protection: u16,
+        verify: bool,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetNewModbusId {
+            tty_path,
+            slave,
+            new_slave_id,
+            reg_protection,
+            verify,
+            responder,
+        })
+    }
+
+    /// Setzt die Arbeitsweise (Rwreg 99), eine laufende Live Ansicht wird beendet
+    pub fn set_new_working_mode(
+        &self,
+        tty_path: String,
+        slave: u8,
+        working_mode: u16,
+        reg_protection: u16,
+        verify: bool,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetNewWorkingMode {
+            tty_path,
+            slave,
+            working_mode,
+            reg_protection,
+            verify,
+            responder,
+        })
+    }
+
+    /// Schreibt ein einzelnes Register
+    pub fn update_register(
+        &self,
+        tty_path: String,
+        slave: u8,
+        reg_nr: u16,
+        reg_protection: u16,
+        new_value: u16,
+        verify: bool,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::UpdateRegister {
+            tty_path,
+            slave,
+            reg_nr,
+            reg_protection,
+            new_value,
+            verify,
+            responder,
+        })
+    }
+
+    /// Schreibt mehrere Register in einem Durchgang, siehe [`ModbusMasterMessage::WriteRegisters`]
+    pub fn write_registers(
+        &self,
+        tty_path: String,
+        slave: u8,
+        reg_protection: u16,
+        values: Vec<(u16, u16)>,
+        rollback: bool,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::WriteRegisters {
+            tty_path,
+            slave,
+            reg_protection,
+            values,
+            rollback,
+            responder,
+        })
+    }
+
+    /// Startet einen Bus Scan
+    ///
+    /// Das Ergebnis liegt vor sobald der Scan gestartet wurde, Fortschritt und
+    /// gefundene Sensoren werden als Ereignisse gesendet.
+    pub fn scan(
+        &self,
+        tty_path: String,
+        slaves: RangeInclusive<u8>,
+        baud_rates: Vec<i32>,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::Scan {
+            tty_path,
+            slaves,
+            baud_rates,
+            responder,
+        })
+    }
+
+    /// Bricht einen laufenden Bus Scan ab
+    pub fn cancel_scan(&self) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::CancelScan { responder })
+    }
+
+    /// Startet die Suche nach Baudrate und Parität unter denen ein Sensor antwortet
+    ///
+    /// Ohne `slave` wird der einzige Sensor am Bus unter allen Adressen
+    /// gesucht. Wie beim Bus Scan werden Fortschritt und Ergebnis als
+    /// [`ModbusMasterEvent::DetectProgress`] und
+    /// [`ModbusMasterEvent::DetectFinished`] gesendet, mit der Adresse unter
+    /// der der Sensor geantwortet hat.
+    pub fn detect_line_settings(
+        &self,
+        tty_path: String,
+        slave: Option<u8>,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::DetectLineSettings {
+            tty_path,
+            slave,
+            responder,
+        })
+    }
+
+    /// Bricht eine laufende Erkennung der Schnittstellenparameter ab
+    pub fn cancel_detect(&self) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::CancelDetect { responder })
+    }
+
+    /// Setzt die Parameter der seriellen Schnittstelle für alle weiteren Operationen
+    pub fn set_line_settings(
+        &self,
+        line_settings: LineSettings,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetLineSettings {
+            line_settings,
+            responder,
+        })
+    }
+
+    /// Setzt die Optionen der Schnittstellen für alle weiteren Operationen
+    ///
+    /// Schnittstellen ohne Eintrag verwenden die Standard Optionen. Ändern sich
+    /// die Optionen der geöffneten Schnittstelle, wird sie neu geöffnet.
+    pub fn set_port_options(
+        &self,
+        port_options: HashMap<String, PortOptions>,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetPortOptions {
+            port_options,
+            responder,
+        })
+    }
+
+    /// Stellt Baudrate (Rwreg 81) und Modbus Mode (Rwreg 82) des Sensors um
+    ///
+    /// Danach wird mit den neuen Parametern geprüft ob der Sensor antwortet,
+    /// wenn nicht werden die alten Werte wiederhergestellt. Bei Erfolg gelten
+    /// die neuen Parameter für alle weiteren Operationen, eine laufende Live
+    /// Ansicht verwendet sie ab der nächsten Anfrage.
+    pub fn change_line_settings(
+        &self,
+        tty_path: String,
+        slave: u8,
+        reg_protection: u16,
+        line_settings: LineSettings,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::ChangeLineSettings {
+            tty_path,
+            slave,
+            reg_protection,
+            line_settings,
+            responder,
+        })
+    }
+
+    /// Setzt die Modbus bzw. MCS Adresse des einzigen Sensors am Bus
+    ///
+    /// Die bisherige Adresse des Sensors muss nicht bekannt sein. Bei
+    /// [`Addressing::FirstResponding`] werden alle Adressen abgefragt, das kann
+    /// bis zu einer halben Minute dauern. Geliefert wird die Adresse unter der
+    /// der Sensor gefunden wurde, bei einem Broadcast `None`.
+    pub fn set_single_device_address(
+        &self,
+        tty_path: String,
+        addressing: Addressing,
+        mcs_config: bool,
+        new_slave_id: u16,
+        reg_protection: u16,
+        verify: bool,
+    ) -> impl Future<Output = Result<Option<u8>, ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetSingleDeviceAddress {
+            tty_path,
+            addressing,
+            mcs_config,
+            new_slave_id,
+            reg_protection,
+            verify,
+            responder,
+        })
+    }
+
+    /// Startet den Sensor über das Schreibschutz Register neu
+    ///
+    /// `code` ist der [`restart_code`](crate::platine::Platine::restart_code)
+    /// oder der [`factory_reset_code`](crate::platine::Platine::factory_reset_code)
+    /// der Platine. Eine laufende Live Ansicht des Sensors wartet den Neustart
+    /// ab und verbindet sich danach erneut.
+    pub fn restart(
+        &self,
+        tty_path: String,
+        slave: u8,
+        reg_protection: u16,
+        code: u16,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::Restart {
+            tty_path,
+            slave,
+            reg_protection,
+            code,
+            responder,
+        })
+    }
+
+    /// Setzt den Zeitplan der Live Ansicht
+    ///
+    /// Eine laufende Live Ansicht übernimmt den Zeitplan ab dem nächsten Durchlauf.
+    pub fn set_poll_schedule(
+        &self,
+        schedule: PollSchedule,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetPollSchedule {
+            schedule,
+            responder,
+        })
+    }
+
+    /// Schaltet den Mitschnitt des Modbus Verkehrs ein oder aus
+    ///
+    /// Bei eingeschaltetem Mitschnitt wird jede Anfrage und Antwort als
+    /// [`ModbusMasterEvent::Traffic`] an die Abonnenten gesendet.
+    pub fn set_traffic_recording(
+        &self,
+        enabled: bool,
+    ) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::SetTrafficRecording { enabled, responder })
+    }
+
+    /// Liefert die Statistik der Sitzung
+    ///
+    /// Änderungen werden außerdem laufend als [`ModbusMasterEvent::Statistics`]
+    /// an die Abonnenten gesendet.
+    pub fn statistics(&self) -> impl Future<Output = Result<Statistics, ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::GetStatistics { responder })
+    }
+
+    /// Setzt die Statistik zurück
+    pub fn reset_statistics(&self) -> impl Future<Output = Result<(), ModbusMasterError>> {
+        self.request(|responder| ModbusMasterMessage::ResetStatistics { responder })
+    }
+
+    // Sendet eine Anfrage an den Thread des Modbus Masters
+    //
+    // Die Nachricht wird sofort gesendet, so bleibt die Reihenfolge der
+    // Anfragen erhalten auch wenn das Future später (oder nie) abgefragt wird.
+    fn request<T>(
+        &self,
+        message: impl FnOnce(Responder<T>) -> ModbusMasterMessage,
+    ) -> impl Future<Output = Result<T, ModbusMasterError>> {
+        let (responder, response) = oneshot::channel();
+        let sent = self
+            .tx
+            .clone()
+            .try_send(message(responder))
+            .map_err(|_| ModbusMasterError::Unreachable);
+
+        async move {
+            sent?;
+            response.await.map_err(|_| ModbusMasterError::Unreachable)?
+        }
+    }
 }
 
-// Nachrichten die den Control Loop streuern
-#[derive(Debug)]
-enum MsgControlLoop {
-    Start(
-        Arc<Mutex<bool>>,
-        String,
-        u8,
-        Vec<Rreg>,
-        Vec<Rwreg>,
-        u16, // Protection Register Nummer
-        Sender<GuiMessage>,
-    ),
+impl Default for ModbusMaster {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[allow(unused_variables)]
-// Starte Control Loop
-fn spawn_control_loop() -> mpsc::Sender<MsgControlLoop> {
-    let (tx, mut rx) = mpsc::channel(1);
-
-    thread::spawn(move || {
-        let mut rt = Runtime::new().expect("Could not create Runtime");
-
-        rt.block_on(async {
-            while let Some(command) = rx.recv().await {
-                match command {
-                    MsgControlLoop::Start(
-                        is_online,
-                        tty_path,
-                        slave,
-                        rregs,
-                        rwregs,
-                        reg_protection,
-                        gui_tx,
-                    ) => {
-                        debug!("MsgControlLoop::Start verarbeiten");
-
-                        loop {
-                            if !(*is_online.lock().unwrap()) {
-                                break;
-                            };
-                            // Lese-Register auslesen
-                            let rregs = read_rregs(tty_path.clone(), slave, rregs.clone());
-                            // Lese-Register
-                            match rregs {
-                                Ok(results) => {
-                                    // Lese-Register an Gui senden
-                                    gui_tx
-                                        .clone()
-                                        .try_send(GuiMessage::UpdateRregs(results.clone()))
-                                        .expect(r#"Failed to send Message"#);
-                                    // // Sensor Werte an GUI Elemente senden
-                                    // gui_tx
-                                    //     .clone()
-                                    //     .try_send(GuiMessage::UpdateSensorValues(results.clone()))
-                                    //     .expect(r#"Failed to send Message"#);
-                                }
-                                Err(error) => {
-                                    // Fehler an GUI Sensen
-                                    show_warning(
-                                        &gui_tx,
-                                        &format!("Konnte Lese-Register nicht lesen:\r\n{}", error),
-                                    )
-                                }
-                            }
+// Besitzer der seriellen Schnittstelle, lebt im Thread des Modbus Masters
+struct BusOwner {
+    bus: Bus,
+    subscribers: Subscribers,
+    // Live Ansicht
+    poller: Option<Poller>,
+    // Zeitplan für neue Live Ansichten
+    poll_schedule: PollSchedule,
+    // Laufender Bus Scan
+    scan: Option<ScanJob>,
+    // Laufende Erkennung der Schnittstellenparameter
+    detect: Option<DetectJob>,
+    // Zeitpunkt an dem die Statistik zuletzt gesendet wurde
+    statistics_published: Option<Instant>,
+}
 
-                            #[cfg(feature = "ra-gas")]
-                            // Schreib.-/ Lese-Register auslesen
-                            let rwregs = read_rwregs(
-                                tty_path.clone(),
-                                slave,
-                                rwregs.clone(),
-                                reg_protection,
-                            );
-                            #[cfg(feature = "ra-gas")]
-                            // Schreib.-/ Lese-Register
-                            match rwregs {
-                                // Schreib.-/ Lese-Register an Gui senden
-                                Ok(results) => {
-                                    gui_tx
-                                        .clone()
-                                        .try_send(GuiMessage::UpdateRwregs(results))
-                                        .expect(r#"Failed to send Message"#);
-                                }
-                                // Schreib.-/ Lese-Register in GUI aktualisieren
-                                Err(error) => show_warning(
-                                    &gui_tx,
-                                    &format!(
-                                        "Konnte Schreib.-/ Lese-Register nicht lesen:\r\n{}",
-                                        error
-                                    ),
-                                ),
-                            }
-                        }
-                    }
-                }
+impl BusOwner {
+    fn new() -> Self {
+        BusOwner {
+            bus: Bus::new(),
+            subscribers: Subscribers::default(),
+            poller: None,
+            poll_schedule: PollSchedule::default(),
+            scan: None,
+            detect: None,
+            statistics_published: None,
+        }
+    }
+
+    // Zeit bis die Hintergrundaufgabe (Live Ansicht, Bus Scan oder Erkennung)
+    // wieder fällig ist, `None` wenn keine läuft
+    fn idle_time(&self) -> Option<Duration> {
+        if self.scan.is_some() || self.detect.is_some() {
+            Some(Duration::from_secs(0))
+        } else {
+            self.poller.as_ref().map(Poller::idle_time)
+        }
+    }
+
+    // Führt einen Schritt der Hintergrundaufgabe aus, Bus Scan und Erkennung
+    // haben Vorrang vor der Live Ansicht
+    fn step(&mut self) {
+        if let Some(scan) = self.scan.as_mut() {
+            if !scan.step(&mut self.bus, &mut self.subscribers) {
+                self.scan = None;
             }
-        });
-    });
+        } else if let Some(detect) = self.detect.as_mut() {
+            if !detect.step(&mut self.bus, &mut self.subscribers) {
+                self.detect = None;
+            }
+        } else if let Some(poller) = self.poller.as_mut() {
+            if !poller.step(&mut self.bus, &mut self.subscribers) {
+                // Verbindung verloren, der Zustand wurde bereits gemeldet
+                self.poller = None;
+            }
+        }
+    }
 
-    tx
-}
+    // Startet die Live Ansicht, eine laufende Live Ansicht wird vorher beendet
+    fn start_poller(&mut self, mut poller: Poller) {
+        self.stop_poller();
+        poller.start(&mut self.subscribers);
+        self.poller = Some(poller);
+    }
 
-/// Diese Funktion iteriert über die Lese-Register und liest diese
-/// sequenziell (nach einander) aus
-fn read_rregs(
-    tty_path: String,
-    slave: u8,
-    regs: Vec<Rreg>,
-) -> Result<Vec<(u16, u16)>, ModbusMasterError> {
-    debug!("read_rregs");
-
-    let mut result: Vec<(u16, u16)> = vec![];
-    for reg in regs {
-        match read_input_register(&tty_path, slave, reg) {
-            Ok(tupple) => result.push(tupple),
-            Err(error) => return Err(error),
+    // Beendet die Live Ansicht
+    fn stop_poller(&mut self) {
+        if let Some(mut poller) = self.poller.take() {
+            poller.stop(&mut self.subscribers);
         }
     }
-    // thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-    Ok(result)
-}
 
-#[cfg(feature = "ra-gas")]
-/// Diese Funktion iteriert über die Schreib.-/ Lese-Register und liest diese
-/// sequenziell (nach einander) aus
-fn read_rwregs(
-    tty_path: String,
-    slave: u8,
-    regs: Vec<Rwreg>,
-    reg_protection: u16,
-) -> Result<Vec<(u16, u16)>, ModbusMasterError> {
-    debug!("read_rwregs");
-
-    let mut result: Vec<(u16, u16)> = vec![];
-    for reg in regs {
-        match read_holding_register(&tty_path, slave, reg, reg_protection) {
-            Ok(tupple) => result.push(tupple),
-            Err(error) => return Err(error),
+    // Sendet die neuen Einträge des Mitschnitts an die Abonnenten
+    fn publish_traffic(&mut self) {
+        let records = self.bus.traffic().take();
+        if !records.is_empty() {
+            self.subscribers
+                .publish(ModbusMasterEvent::Traffic(records));
+        }
+    }
+
+    // Sendet die geänderte Statistik an die Abonnenten
+    //
+    // Während Live Ansicht oder Bus Scan laufen höchstens einmal pro
+    // `STATISTICS_INTERVAL`, sonst sofort damit der letzte Stand ankommt.
+    fn publish_statistics(&mut self) {
+        let due = self.idle_time().is_none()
+            || self
+                .statistics_published
+                .map_or(true, |published| published.elapsed() >= STATISTICS_INTERVAL);
+        if !due {
+            return;
+        }
+        if let Some(statistics) = self.bus.take_changed_statistics() {
+            self.subscribers
+                .publish(ModbusMasterEvent::Statistics(statistics));
+            self.statistics_published = Some(Instant::now());
         }
     }
 
-    // thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-    Ok(result)
+    // Sendet das Ergebnis einer Anfrage zurück
+    //
+    // Nach einem Fehler wird die Schnittstelle geschlossen und beim nächsten
+    // Zugriff neu geöffnet.
+    fn respond<T>(&mut self, responder: Responder<T>, result: Result<T, ModbusMasterError>) {
+        if result.is_err() {
+            self.bus.close();
+        }
+        // Wartet niemand auf das Ergebnis ist das kein Fehler
+        let _ = responder.send(result);
+    }
+
+    // Bearbeitet einen Befehl
+    fn handle(&mut self, command: ModbusMasterMessage) {
+        match command {
+            // Startet die Live Ansicht
+            ModbusMasterMessage::Connect {
+                tty_path,
+                targets,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::Connect");
+                // debug!("tty_path: {}, targets: {:?}", tty_path, targets);
+                let schedule = self.poll_schedule.clone();
+                self.start_poller(Poller::new(tty_path, targets, schedule));
+                self.respond(responder, Ok(()));
+            }
+            // Beendet die Live Ansicht
+            ModbusMasterMessage::Disconnect { responder } => {
+                info!("ModbusMasterMessage::Disconnect");
+                self.stop_poller();
+                self.bus.close();
+                self.respond(responder, Ok(()));
+            }
+            // Nullgas setzen
+            ModbusMasterMessage::Nullgas {
+                tty_path,
+                slave,
+                reg_protection,
+                sensor_num,
+                responder,
+            } => {
+                let result =
+                    set_nullgas(&mut self.bus, &tty_path, slave, reg_protection, sensor_num);
+                self.respond(responder, result);
+            }
+            // Messgas setzen
+            ModbusMasterMessage::Messgas {
+                tty_path,
+                slave,
+                reg_protection,
+                sensor_num,
+                responder,
+            } => {
+                let result =
+                    set_messgas(&mut self.bus, &tty_path, slave, reg_protection, sensor_num);
+                self.respond(responder, result);
+            }
+            // Neue MCS Bus ID setzen
+            ModbusMasterMessage::SetNewMcsBusId {
+                tty_path,
+                slave,
+                new_slave_id,
+                reg_protection,
+                verify,
+                responder,
+            } => {
+                let result = set_new_mcs_bus_id(
+                    &mut self.bus,
+                    &tty_path,
+                    slave,
+                    new_slave_id,
+                    reg_protection,
+                    verify,
+                );
+                self.respond(responder, result);
+            }
+            // Neue Modbus Slave ID setzen
+            ModbusMasterMessage::SetNewModbusId {
+                tty_path,
+                slave,
+                new_slave_id,
+                reg_protection,
+                verify,
+                responder,
+            } => {
+                let result = set_new_modbus_id(
+                    &mut self.bus,
+                    &tty_path,
+                    slave,
+                    new_slave_id,
+                    reg_protection,
+                    verify,
+                );
+                self.respond(responder, result);
+            }
+            // Neue Arbeitsweise auf Platine speichern
+            ModbusMasterMessage::SetNewWorkingMode {
+                tty_path,
+                slave,
+                working_mode,
+                reg_protection,
+                verify,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::SetNewWorkingMode");
+                // Live Ansicht beenden, die Register ändern sich mit der Arbeitsweise
+                self.stop_poller();
+                let result = set_working_mode(
+                    &mut self.bus,
+                    &tty_path,
+                    slave,
+                    working_mode,
+                    reg_protection,
+                    verify,
+                );
+                self.respond(responder, result);
+            }
+            // Update ein einzelnes Register
+            ModbusMasterMessage::UpdateRegister {
+                tty_path,
+                slave,
+                reg_nr,
+                reg_protection,
+                new_value,
+                verify,
+                responder,
+            } => {
+                let result = update_register(
+                    &mut self.bus,
+                    &tty_path,
+                    slave,
+                    reg_nr,
+                    reg_protection,
+                    new_value,
+                    verify,
+                );
+                self.respond(responder, result);
+            }
+            // Mehrere Register schreiben
+            ModbusMasterMessage::WriteRegisters {
+                tty_path,
+                slave,
+                reg_protection,
+                values,
+                rollback,
+                responder,
+            } => {
+                let result = write_registers(
+                    &mut self.bus,
+                    &tty_path,
+                    slave,
+                    reg_protection,
+                    values,
+                    rollback,
+                );
+                self.respond(responder, result);
+            }
+            // Bus Scan starten
+            ModbusMasterMessage::Scan {
+                tty_path,
+                slaves,
+                baud_rates,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::Scan");
+                if self.scan.is_some() {
+                    return self.respond(responder, Err(ModbusMasterError::ScanRunning));
+                }
+                if self.detect.is_some() {
+                    return self.respond(responder, Err(ModbusMasterError::DetectRunning));
+                }
+                // Der Scan benötigt die Schnittstelle, Live Ansicht beenden
+                self.stop_poller();
+                self.scan = Some(ScanJob::new(tty_path, slaves, baud_rates));
+                self.respond(responder, Ok(()));
+            }
+            // Bus Scan abbrechen
+            ModbusMasterMessage::CancelScan { responder } => {
+                info!("ModbusMasterMessage::CancelScan");
+                if let Some(scan) = self.scan.as_mut() {
+                    scan.cancel();
+                }
+                self.respond(responder, Ok(()));
+            }
+            // Schnittstellenparameter eines Sensors suchen
+            ModbusMasterMessage::DetectLineSettings {
+                tty_path,
+                slave,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::DetectLineSettings");
+                if let Err(error) = endpoint::require_serial(&tty_path) {
+                    return self.respond(responder, Err(error));
+                }
+                if self.scan.is_some() {
+                    return self.respond(responder, Err(ModbusMasterError::ScanRunning));
+                }
+                if self.detect.is_some() {
+                    return self.respond(responder, Err(ModbusMasterError::DetectRunning));
+                }
+                // Die Erkennung benötigt die Schnittstelle, Live Ansicht beenden
+                self.stop_poller();
+                let transmission_mode = self.bus.line_settings().transmission_mode;
+                self.detect = Some(DetectJob::new(tty_path, slave, transmission_mode));
+                self.respond(responder, Ok(()));
+            }
+            // Erkennung der Schnittstellenparameter abbrechen
+            ModbusMasterMessage::CancelDetect { responder } => {
+                info!("ModbusMasterMessage::CancelDetect");
+                if let Some(detect) = self.detect.as_mut() {
+                    detect.cancel();
+                }
+                self.respond(responder, Ok(()));
+            }
+            // Schnittstellenparameter der Sitzung setzen
+            ModbusMasterMessage::SetLineSettings {
+                line_settings,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::SetLineSettings: {}", line_settings);
+                self.bus.set_line_settings(line_settings);
+                self.respond(responder, Ok(()));
+            }
+            // Optionen der Schnittstellen setzen
+            ModbusMasterMessage::SetPortOptions {
+                port_options,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::SetPortOptions: {:?}", port_options);
+                self.bus.set_port_options(port_options);
+                self.respond(responder, Ok(()));
+            }
+            // Sensor auf neue Schnittstellenparameter umstellen
+            ModbusMasterMessage::ChangeLineSettings {
+                tty_path,
+                slave,
+                reg_protection,
+                line_settings,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::ChangeLineSettings: {}", line_settings);
+                let result = line_change::change(
+                    &mut self.bus,
+                    &tty_path,
+                    slave,
+                    reg_protection,
+                    line_settings,
+                );
+                self.respond(responder, result);
+            }
+            // Adresse des einzigen Sensors am Bus setzen
+            ModbusMasterMessage::SetSingleDeviceAddress {
+                tty_path,
+                addressing,
+                mcs_config,
+                new_slave_id,
+                reg_protection,
+                verify,
+                responder,
+            } => {
+                info!(
+                    "ModbusMasterMessage::SetSingleDeviceAddress: {:?}",
+                    addressing
+                );
+                let result = single_device::set_address(
+                    &mut self.bus,
+                    &tty_path,
+                    addressing,
+                    mcs_config,
+                    new_slave_id,
+                    reg_protection,
+                    verify,
+                );
+                self.respond(responder, result);
+            }
+            // Sensor neu starten
+            ModbusMasterMessage::Restart {
+                tty_path,
+                slave,
+                reg_protection,
+                code,
+                responder,
+            } => {
+                info!(
+                    "ModbusMasterMessage::Restart: slave: {}, code: {}",
+                    slave, code
+                );
+                let result = restart(&mut self.bus, &tty_path, slave, reg_protection, code);
+                if result.is_ok() {
+                    // Die Schnittstelle nach dem Neustart frisch öffnen
+                    self.bus.close();
+                    if let Some(poller) = self.poller.as_mut() {
+                        poller.restart(slave, RESTART_TIME, &mut self.subscribers);
+                    }
+                }
+                self.respond(responder, result);
+            }
+            // Zeitplan der Live Ansicht setzen
+            ModbusMasterMessage::SetPollSchedule {
+                schedule,
+                responder,
+            } => {
+                info!("ModbusMasterMessage::SetPollSchedule: {:?}", schedule);
+                if let Some(poller) = self.poller.as_mut() {
+                    poller.set_schedule(schedule.clone());
+                }
+                self.poll_schedule = schedule;
+                self.respond(responder, Ok(()));
+            }
+            // Mitschnitt ein-/ ausschalten
+            ModbusMasterMessage::SetTrafficRecording { enabled, responder } => {
+                info!("ModbusMasterMessage::SetTrafficRecording: {}", enabled);
+                self.bus.traffic().set_enabled(enabled);
+                self.respond(responder, Ok(()));
+            }
+            // Statistik abfragen
+            ModbusMasterMessage::GetStatistics { responder } => {
+                let statistics = self.bus.statistics().clone();
+                self.respond(responder, Ok(statistics));
+            }
+            // Statistik zurücksetzen
+            ModbusMasterMessage::ResetStatistics { responder } => {
+                info!("ModbusMasterMessage::ResetStatistics");
+                self.bus.reset_statistics();
+                self.respond(responder, Ok(()));
+            }
+            // Abonnent anmelden
+            ModbusMasterMessage::Subscribe(subscriber) => {
+                self.subscribers.add(subscriber);
+            }
+        }
+    }
 }
 
 // Liest die Input Register (0x04) (Lese-Register)
@@ -453,6 +1222,7 @@ fn read_rwregs(
 // Diese Funktion ist einfach. Sie liest immer ein Register aus und gibt den
 // Wert oder ein Fehler zurück.
 fn read_input_register(
+    bus: &mut Bus,
     tty_path: &str,
     slave: u8,
     reg: Rreg,
@@ -462,16 +1232,11 @@ fn read_input_register(
     let reg_nr = reg.reg_nr() as u16;
     let mut value = vec![0u16; 1];
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
+    let mut modbus = bus.open(tty_path)?;
     modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            modbus.read_input_registers(reg_nr, 1, &mut value)?;
-        }
-        Err(e) => return Err(ModbusMasterError::ReadInputRegister { reg_nr, source: e }),
-    }
+    modbus
+        .read_input_registers(reg_nr, 1, &mut value)
+        .map_err(|source| ModbusMasterError::ReadInputRegister { reg_nr, source })?;
 
     let value = (reg_nr, value[0]);
 
@@ -479,7 +1244,6 @@ fn read_input_register(
     Ok(value)
 }
 
-#[cfg(feature = "ra-gas")]
 // Liest die Holding Register (0x03) (Schreib.-/ Lese-Register)
 //
 // Im Prinzip funktioniert diese Funktion wie `read_input_register` jedoch
@@ -487,6 +1251,7 @@ fn read_input_register(
 // "gesperrte" Register. Diese Register sind nur nach "Eingabe" eines Freigabe
 // Codes lesbar. Der Code wird in ein Register geschreiben.
 fn read_holding_register(
+    bus: &mut Bus,
     tty_path: &str,
     slave: u8,
     reg: Rwreg,
@@ -494,51 +1259,42 @@ fn read_holding_register(
 ) -> Result<(u16, u16), ModbusMasterError> {
     debug!("read_holding_register");
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
     let reg_nr = reg.reg_nr() as u16;
     let mut value = vec![0u16; 1];
 
-    match modbus.connect() {
-        Ok(_) => {
-            if reg.is_protected() {
-                modbus.write_register(reg_protection, 9876)?;
-                thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            }
-            modbus.read_registers(reg_nr, 1, &mut value)?;
-        }
-        Err(e) => return Err(ModbusMasterError::ReadHoldingRegister { reg_nr, source: e }),
-    }
+    let mut modbus = if reg.is_protected() {
+        bus.unlock(tty_path, slave, reg_protection)?
+    } else {
+        let mut modbus = bus.open(tty_path)?;
+        modbus.set_slave(slave)?;
+        modbus
+    };
+    modbus
+        .read_registers(reg_nr, 1, &mut value)
+        .map_err(|source| ModbusMasterError::ReadHoldingRegister { reg_nr, source })?;
+
     let value = (reg_nr, value[0]);
 
     debug!("Rreg: (reg_nr, value): {:?}", &value);
-    // thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
     Ok(value)
 }
 
 // Setzt die Arbeitsweise des Sensors (Rwreg 99)
 fn set_working_mode(
-    tty_path: String,
+    bus: &mut Bus,
+    tty_path: &str,
     slave: u8,
     working_mode: u16,
     reg_protection: u16,
+    verify: bool,
 ) -> Result<(), ModbusMasterError> {
     debug!("set_working_mode: {:?}", working_mode);
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            // Entsperren
-            modbus.write_register(reg_protection, 9876)?;
-            thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            // Arbeitsweise setzen
-            modbus.write_register(99, working_mode)?;
-        }
-        Err(e) => return Err(e.into()),
+    // Arbeitsweise setzen
+    let mut modbus = write_unlocked(bus, tty_path, slave, reg_protection, 99, working_mode)?;
+    if verify {
+        thread::sleep(std::time::Duration::from_millis(VERIFY_TIMEOUT));
+        verify_register(&mut modbus, 99, working_mode)?;
     }
 
     Ok(())
@@ -546,7 +1302,8 @@ fn set_working_mode(
 
 // Nullgas Rwreg 10 - 11111
 fn set_nullgas(
-    tty_path: String,
+    bus: &mut Bus,
+    tty_path: &str,
     slave: u8,
     reg_protection: u16,
     sensor_num: u16,
@@ -556,27 +1313,16 @@ fn set_nullgas(
     // Register Nummer Nullgas
     let nullgas_reg_nr = if sensor_num == 1 { 10 } else { 20 };
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            // Entsperren
-            modbus.write_register(reg_protection, 9876)?;
-            thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            // Nullpunkt festlegen
-            modbus.write_register(nullgas_reg_nr, 11111)?;
-        }
-        Err(e) => return Err(e.into()),
-    }
+    // Nullpunkt festlegen
+    write_unlocked(bus, tty_path, slave, reg_protection, nullgas_reg_nr, 11111)?;
 
     Ok(())
 }
 
 // Messgas Rwreg 12 - 11111
 fn set_messgas(
-    tty_path: String,
+    bus: &mut Bus,
+    tty_path: &str,
     slave: u8,
     reg_protection: u16,
     sensor_num: u16,
@@ -586,83 +1332,101 @@ fn set_messgas(
     // Register Nummer Messgas
     let messgas_reg_nr = if sensor_num == 1 { 12 } else { 22 };
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            // Entsperren
-            modbus.write_register(reg_protection, 9876)?;
-            thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            // Messgas festlegen
-            modbus.write_register(messgas_reg_nr, 11111)?;
-        }
-        Err(e) => return Err(e.into()),
-    }
+    // Messgas festlegen
+    write_unlocked(bus, tty_path, slave, reg_protection, messgas_reg_nr, 11111)?;
 
     Ok(())
 }
 
+// Schreibt den Neustart bzw. Grunddaten Code in das Schreibschutz Register
+//
+// Der Sensor startet eventuell schon vor der Antwort neu, ein Timeout ist
+// deshalb kein Fehler. Nach dem Neustart ist die Platine wieder gesperrt.
+fn restart(
+    bus: &mut Bus,
+    tty_path: &str,
+    slave: u8,
+    reg_protection: u16,
+    code: u16,
+) -> Result<(), ModbusMasterError> {
+    debug!(
+        "restart: tty_path: {}, slave: {}, code: {}",
+        tty_path, slave, code
+    );
+
+    let written = bus
+        .unlock(tty_path, slave, reg_protection)?
+        .write_register(reg_protection, code);
+    bus.forget_unlock(slave);
+    match written {
+        Ok(_) => Ok(()),
+        Err(error) if ErrorKind::of(&error) == ErrorKind::Timeout => {
+            debug!("restart: no response: {:?}", error);
+            Ok(())
+        }
+        Err(source) => Err(ModbusMasterError::WriteRegister {
+            reg_nr: reg_protection,
+            source,
+        }),
+    }
+}
+
 // Speichert die neue Modbus Adresse (Rwreg 80)
 fn set_new_modbus_id(
-    tty_path: String,
+    bus: &mut Bus,
+    tty_path: &str,
     slave: u8,
     new_slave_id: u16,
     reg_protection: u16,
+    verify: bool,
 ) -> Result<(), ModbusMasterError> {
     debug!(
         "set_new_modbus_id: tty_path: {}, slave: {}, new_slave_id: {}",
         tty_path, slave, new_slave_id
     );
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            // Entsperren
-            modbus.write_register(reg_protection, 9876)?;
-            thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            // Modbus Slave ID festlegen
-            if reg_protection == 79 {
-                modbus.write_register(80, new_slave_id)?;
-            } else {
-                modbus.write_register(50, new_slave_id)?;
-            }
-        }
-        Err(e) => return Err(e.into()),
+    // Modbus Slave ID festlegen
+    let new_slave = modbus_slave_id(new_slave_id)?;
+    let reg_nr = if reg_protection == 79 { 80 } else { 50 };
+    write_unlocked(bus, tty_path, slave, reg_protection, reg_nr, new_slave_id)?;
+    // Die Platine antwortet ab jetzt nur noch unter der neuen Adresse,
+    // deshalb wird dort erneut entsperrt und zurück gelesen.
+    if verify {
+        thread::sleep(std::time::Duration::from_millis(VERIFY_TIMEOUT));
+        let mut modbus = bus.unlock(tty_path, new_slave, reg_protection)?;
+        verify_register(&mut modbus, reg_nr, new_slave_id)?;
     }
 
     Ok(())
 }
 
+// Prüft die neue Modbus Adresse bevor sie geschrieben wird
+fn modbus_slave_id(new_slave_id: u16) -> Result<u8, ModbusMasterError> {
+    u8::try_from(new_slave_id)
+        .ok()
+        .filter(|slave| (1..=single_device::MAX_SLAVE_ID).contains(slave))
+        .ok_or(ModbusMasterError::InvalidSlaveId(new_slave_id))
+}
+
 // Speichert die neue MCS Bus Adresse (Rwreg 95)
 fn set_new_mcs_bus_id(
-    tty_path: String,
+    bus: &mut Bus,
+    tty_path: &str,
     slave: u8,
     new_slave_id: u16,
     reg_protection: u16,
+    verify: bool,
 ) -> Result<(), ModbusMasterError> {
     debug!(
         "new_mcs_slave_id: tty_path: {}, slave: {}, new_slave_id: {}",
         tty_path, slave, new_slave_id
     );
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            // Entsperren
-            modbus.write_register(reg_protection, 9876)?;
-            thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            // MCS ID festlegen
-            modbus.write_register(95, new_slave_id)?;
-        }
-        Err(e) => return Err(e.into()),
+    // MCS ID festlegen
+    let mut modbus = write_unlocked(bus, tty_path, slave, reg_protection, 95, new_slave_id)?;
+    if verify {
+        thread::sleep(std::time::Duration::from_millis(VERIFY_TIMEOUT));
+        verify_register(&mut modbus, 95, new_slave_id)?;
     }
 
     Ok(())
@@ -671,31 +1435,295 @@ fn set_new_mcs_bus_id(
 /// Update ein Register
 ///
 fn update_register(
-    tty_path: String,
+    bus: &mut Bus,
+    tty_path: &str,
     slave: u8,
     reg_nr: u16,
     reg_protection: u16,
     new_value: u16,
+    verify: bool,
 ) -> Result<(), ModbusMasterError> {
     debug!(
         "update_register: tty_path: {}, slave: {}, reg_nr: {}",
         tty_path, slave, reg_nr
     );
 
-    let mut modbus = Modbus::new_rtu(&tty_path, 9600, 'N', 8, 1)?;
-    modbus.set_slave(slave)?;
-    // modbus.set_debug(true)?;
-
-    match modbus.connect() {
-        Ok(_) => {
-            // Entsperren
-            modbus.write_register(reg_protection, 9876)?;
-            thread::sleep(std::time::Duration::from_millis(LOCK_TIMEOUT));
-            // Wert schreiben
-            modbus.write_register(reg_nr, new_value)?;
+    // Wert schreiben
+    let mut modbus = write_unlocked(bus, tty_path, slave, reg_protection, reg_nr, new_value)?;
+    if verify {
+        thread::sleep(std::time::Duration::from_millis(VERIFY_TIMEOUT));
+        verify_register(&mut modbus, reg_nr, new_value)?;
+    }
+
+    Ok(())
+}
+
+/// Schreibt mehrere Register in einem Durchgang
+///
+/// Die Platine wird nur einmal entsperrt. Zusammenhängende Register werden mit
+/// Funktion 0x10 (Write Multiple Registers) geschrieben, einzelne Register
+/// und Blöcke die die Firmware mit 0x10 ablehnt mit 0x06 (Write Single Register).
+///
+/// Ist `rollback` gesetzt werden vor dem Schreiben die aktuellen Werte gelesen.
+/// Schlägt ein Schreibzugriff fehl werden diese Werte zurück geschrieben, so
+/// dass die Platine nicht halb konfiguriert zurück bleibt.
+fn write_registers(
+    bus: &mut Bus,
+    tty_path: &str,
+    slave: u8,
+    reg_protection: u16,
+    values: Vec<(u16, u16)>,
+    rollback: bool,
+) -> Result<(), ModbusMasterError> {
+    debug!(
+        "write_registers: tty_path: {}, slave: {}, values: {:?}",
+        tty_path, slave, values
+    );
+
+    let mut modbus = bus.unlock(tty_path, slave, reg_protection)?;
+
+    let blocks = contiguous_blocks(&values);
+
+    // Aktuelle Werte für ein eventuelles Zurückschreiben sichern
+    let mut previous: Vec<(u16, Vec<u16>)> = vec![];
+    if rollback {
+        for (start, block) in &blocks {
+            let mut old = vec![0u16; block.len()];
+            modbus.read_registers(*start, block.len() as u16, &mut old)?;
+            previous.push((*start, old));
+        }
+    }
+
+    for (start, block) in &blocks {
+        if let Err((reg_nr, source)) = write_block(&mut modbus, *start, block) {
+            if !rollback {
+                return Err(ModbusMasterError::WriteRegister { reg_nr, source });
+            }
+            // Alle Blöcke zurück schreiben, auch die bereits geschriebenen
+            let restored = previous
+                .iter()
+                .all(|(start, old)| write_block(&mut modbus, *start, old).is_ok());
+            if restored {
+                return Err(ModbusMasterError::RolledBack { reg_nr, source });
+            } else {
+                return Err(ModbusMasterError::RollbackFailed { reg_nr, source });
+            }
         }
-        Err(e) => return Err(e.into()),
     }
 
     Ok(())
 }
+
+// Schreibt ein Register, die Platine wird falls nötig vorher entsperrt
+//
+// Lehnt die Platine den Schreibzugriff als gesperrt ab, war die Sperre
+// eventuell schon wieder aktiv. Dann wird erneut entsperrt und ein zweites Mal
+// geschrieben. Nach Timeouts, CRC Fehlern oder Exception 03 wird nicht
+// wiederholt, der Wert könnte schon angekommen sein, z.B. der Code für die
+// Kalibrierung. Geliefert wird der
+// Modbus Context um den Wert z.B. zurück zu lesen.
+fn write_unlocked<'a>(
+    bus: &'a mut Bus,
+    tty_path: &str,
+    slave: u8,
+    reg_protection: u16,
+    reg_nr: u16,
+    value: u16,
+) -> Result<Link<'a>, ModbusMasterError> {
+    let written = bus
+        .unlock(tty_path, slave, reg_protection)?
+        .write_register(reg_nr, value);
+    match written {
+        Err(ref error) if is_lock_error(error) => {
+            debug!("write_unlocked: reg_nr {} rejected, unlock again", reg_nr);
+            bus.forget_unlock(slave);
+            bus.unlock(tty_path, slave, reg_protection)?
+                .write_register(reg_nr, value)?;
+        }
+        written => written?,
+    }
+    bus.unlock(tty_path, slave, reg_protection)
+}
+
+// Lehnt eine gesperrte Platine den Schreibzugriff ab?
+//
+// Die gesperrten Register sind laut Registerbelegung "nur nach Entsicherung
+// veränderbar", ein nicht schreibbares Register beantwortet die Firmware mit
+// der Exception Illegal Data Address (02). Exception 03 ist keine Ablehnung,
+// der Wert wurde auf den Messbereich begrenzt und trotzdem verwendet.
+fn is_lock_error(error: &LibModbusError) -> bool {
+    errno(error) == Some(rtu::EMBXILADD)
+}
+
+// Lehnt die Firmware die Funktion 0x10 ab? Sie antwortet dann mit der
+// Exception Illegal Function bzw. Illegal Data Address, geschrieben wurde
+// nichts.
+fn is_unsupported_write(error: &LibModbusError) -> bool {
+    match errno(error) {
+        Some(code) => code == rtu::EMBXILFUN || code == rtu::EMBXILADD,
+        None => false,
+    }
+}
+
+// errno Wert eines libmodbus Fehlers
+fn errno(error: &LibModbusError) -> Option<i64> {
+    match error {
+        LibModbusError::Client { source, .. } => source.raw_os_error().map(i64::from),
+        _ => None,
+    }
+}
+
+// Schreibt einen Block zusammenhängender Register
+//
+// Lehnt die Firmware die Funktion 0x10 ab, wird jedes Register einzeln mit
+// 0x06 geschrieben. Nach anderen Fehlern nicht, der Block könnte schon
+// gespeichert sein. Im Fehlerfall wird die Register Nummer des fehlerhaften
+// Zugriffs geliefert.
+fn write_block(
+    modbus: &mut Link<'_>,
+    start: u16,
+    block: &[u16],
+) -> Result<(), (u16, LibModbusError)> {
+    if block.len() > 1 {
+        match modbus.write_registers(start, block.len() as u16, block) {
+            Ok(_) => return Ok(()),
+            Err(ref error) if is_unsupported_write(error) => {
+                debug!("write_block: 0x10 rejected at {}, use 0x06", start)
+            }
+            Err(error) => return Err((start, error)),
+        }
+    }
+    for (reg_nr, value) in (start..).zip(block.iter()) {
+        modbus
+            .write_register(reg_nr, *value)
+            .map_err(|error| (reg_nr, error))?;
+    }
+    Ok(())
+}
+
+// Fasst aufeinander folgende Register Nummern zu Blöcken zusammen
+//
+// Die Reihenfolge der Werte bleibt erhalten, es werden nur direkt
+// nacheinander stehende Register mit fortlaufender Nummer zusammengefasst.
+// Ein Block hat höchstens `MAX_BLOCK_LEN` Register.
+fn contiguous_blocks(values: &[(u16, u16)]) -> Vec<(u16, Vec<u16>)> {
+    let mut blocks: Vec<(u16, Vec<u16>)> = vec![];
+    for (reg_nr, value) in values {
+        match blocks.last_mut() {
+            Some((start, block))
+                if block.len() < MAX_BLOCK_LEN
+                    && *start as usize + block.len() == *reg_nr as usize =>
+            {
+                block.push(*value)
+            }
+            _ => blocks.push((*reg_nr, vec![*value])),
+        }
+    }
+    blocks
+}
+
+// Liest ein geschriebenes Register (0x03) zurück und vergleicht den Wert
+//
+// Eine gesperrte Platine lehnt den Schreibzugriff ab (siehe `is_lock_error`),
+// das Zurücklesen zeigt ob der Wert unverändert gespeichert wurde, z.B. nicht
+// auf den Messbereich begrenzt.
+fn verify_register(
+    modbus: &mut Link<'_>,
+    reg_nr: u16,
+    expected: u16,
+) -> Result<(), ModbusMasterError> {
+    debug!(
+        "verify_register: reg_nr: {}, expected: {}",
+        reg_nr, expected
+    );
+
+    let mut value = vec![0u16; 1];
+    modbus.read_registers(reg_nr, 1, &mut value)?;
+
+    if value[0] != expected {
+        return Err(ModbusMasterError::VerifyFailed {
+            reg_nr,
+            expected,
+            actual: value[0],
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modbus_slave_id_range() {
+        assert_eq!(modbus_slave_id(1).ok(), Some(1));
+        assert_eq!(modbus_slave_id(247).ok(), Some(247));
+        for invalid in &[0, 248, 300] {
+            match modbus_slave_id(*invalid) {
+                Err(ModbusMasterError::InvalidSlaveId(slave_id)) => assert_eq!(slave_id, *invalid),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn retry_only_rejected_writes() {
+        let error = |code: i64| LibModbusError::Client {
+            msg: "write_register".to_string(),
+            source: std::io::Error::from_raw_os_error(code as i32),
+        };
+        assert!(is_lock_error(&error(rtu::EMBXILADD)));
+        assert!(!is_lock_error(&error(rtu::EMBXILVAL)));
+        assert!(!is_lock_error(&error(rtu::ETIMEDOUT)));
+        assert!(!is_lock_error(&error(rtu::EMBBADCRC)));
+    }
+
+    #[test]
+    fn fall_back_only_on_rejected_function() {
+        let error = |code: i64| LibModbusError::Client {
+            msg: "write_registers".to_string(),
+            source: std::io::Error::from_raw_os_error(code as i32),
+        };
+        assert!(is_unsupported_write(&error(rtu::EMBXILFUN)));
+        assert!(is_unsupported_write(&error(rtu::EMBXILADD)));
+        assert!(!is_unsupported_write(&error(rtu::EMBXILVAL)));
+        assert!(!is_unsupported_write(&error(rtu::ETIMEDOUT)));
+        assert!(!is_unsupported_write(&error(rtu::EMBBADCRC)));
+    }
+
+    #[test]
+    fn contiguous_blocks_empty() {
+        assert!(contiguous_blocks(&[]).is_empty());
+    }
+
+    #[test]
+    fn contiguous_blocks_grouped() {
+        let values = [(15, 0), (16, 400), (17, 1000), (18, 2000), (13, 500)];
+        assert_eq!(
+            contiguous_blocks(&values),
+            vec![(15, vec![0, 400, 1000, 2000]), (13, vec![500])]
+        );
+    }
+
+    #[test]
+    fn contiguous_blocks_split_at_max_len() {
+        let values: Vec<(u16, u16)> = (20..32).map(|reg_nr| (reg_nr, reg_nr * 10)).collect();
+        assert_eq!(
+            contiguous_blocks(&values),
+            vec![
+                (20, (20..30).map(|reg_nr| reg_nr * 10).collect()),
+                (30, vec![300, 310]),
+            ]
+        );
+    }
+
+    #[test]
+    fn contiguous_blocks_keep_order() {
+        let values = [(17, 1000), (16, 400), (80, 5)];
+        assert_eq!(
+            contiguous_blocks(&values),
+            vec![(17, vec![1000]), (16, vec![400]), (80, vec![5])]
+        );
+    }
+}
@@ -0,0 +1,449 @@
+//! Modbus ohne libmodbus
+//!
+//! Der [`NativePort`] spricht Modbus RTU und ASCII direkt über die serielle
+//! Schnittstelle, die Frames bilden [`rtu`](super::rtu) und
+//! [`ascii`](super::ascii). Über eine TCP Verbindung sendet er RTU Frames an
+//! ein Gateway (`rtu+tcp://host:port`), das libmodbus nicht kennt. So sind alle Bytes einer Anfrage bekannt und
+//! landen unverändert im [`TrafficRecorder`](super::traffic::TrafficRecorder).
+//!
+//! Fehler werden wie bei libmodbus mit `errno` Werten gemeldet. So zählt die
+//! [`Statistics`](super::Statistics) Timeouts, CRC Fehler und Exceptions
+//! gleich, egal über welche Übertragung die Anfrage lief.
+use crate::{
+    modbus_master::{
+        ascii,
+        rtu::{self, EMBBADDATA, EMBBADSLAVE, MODBUS_ENOBASE},
+        traffic::{
+            RawExchange, READ_HOLDING_REGISTERS, READ_INPUT_REGISTERS, WRITE_MULTIPLE_REGISTERS,
+            WRITE_SINGLE_REGISTER,
+        },
+        LineSettings, TransmissionMode,
+    },
+    sniffer,
+};
+use libmodbus::{prelude::Error as LibModbusError, Timeout};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    runtime::{self, Runtime},
+    time,
+};
+use tokio_serial::Serial;
+
+// Modbus Broadcast Adresse, darauf antwortet kein Slave
+const BROADCAST_ADDRESS: u8 = 0;
+// Standard Antwortzeit, wie bei libmodbus
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+// Über TCP kommen die Bytes nicht im Takt der Baudrate, ein Frame unbekannter
+// Länge endet erst nach einer längeren Pause
+const TCP_FRAME_END: Duration = Duration::from_millis(50);
+
+/// Serielle Schnittstelle mit eigener Modbus RTU bzw. ASCII Übertragung
+///
+/// Wird wie der libmodbus Context vom [`Bus`](super::Bus) verwendet, alle
+/// Anfragen laufen synchron im Thread des Modbus Masters. Die Schnittstelle
+/// wird über eine eigene tokio Runtime gelesen und geschrieben.
+///
+/// Bei RTU hält der Port vor jeder Anfrage die Pause von 3,5 Zeichen nach dem
+/// letzten Frame ein. Über TCP entfällt die Pause, die Zeiten auf dem Bus hält
+/// das Gateway ein.
+pub struct NativePort {
+    rt: Runtime,
+    port: Stream,
+    transmission_mode: TransmissionMode,
+    slave: u8,
+    response_timeout: Duration,
+    // Dauer eines Zeichens, die Antwortzeit beginnt erst nach dem Senden
+    char_time: Duration,
+    // Pause zwischen zwei RTU Frames
+    frame_gap: Duration,
+    // Ende des letzten Frames
+    last_frame: Option<Instant>,
+    // Bytes der letzten Anfrage, nur bei RTU
+    exchange: Option<RawExchange>,
+}
+
+impl NativePort {
+    /// Öffnet die Schnittstelle `tty_path` mit den Parametern `line_settings`
+    ///
+    /// Die Übertragungsart bestimmt `line_settings.transmission_mode`.
+    pub fn open(tty_path: &str, line_settings: &LineSettings) -> io::Result<Self> {
+        let mut rt = runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()?;
+        let settings = sniffer::serial_port_settings(line_settings);
+        let port = rt.block_on(async { Serial::from_path(tty_path, &settings) })?;
+
+        let parity_bits = if line_settings.parity == 'N' { 0 } else { 1 };
+        let bits = 1 + line_settings.data_bits + parity_bits + line_settings.stop_bits;
+        let char_time = Duration::from_micros(
+            bits.max(1) as u64 * 1_000_000 / line_settings.baud_rate.max(1) as u64,
+        );
+
+        Ok(NativePort {
+            rt,
+            port: Stream::Serial(port),
+            transmission_mode: line_settings.transmission_mode,
+            slave: 0,
+            response_timeout: RESPONSE_TIMEOUT,
+            char_time,
+            frame_gap: rtu::frame_gap(line_settings.baud_rate),
+            last_frame: None,
+            exchange: None,
+        })
+    }
+
+    /// Verbindet zu einem Gateway das RTU Frames über TCP weitergibt
+    pub fn connect(host: &str, port: u16) -> io::Result<Self> {
+        let mut rt = runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()?;
+        let stream = rt.block_on(TcpStream::connect((host, port)))?;
+        stream.set_nodelay(true)?;
+
+        Ok(NativePort {
+            rt,
+            port: Stream::Tcp(stream),
+            transmission_mode: TransmissionMode::Rtu,
+            slave: 0,
+            response_timeout: RESPONSE_TIMEOUT,
+            char_time: Duration::from_secs(0),
+            frame_gap: Duration::from_secs(0),
+            last_frame: None,
+            exchange: None,
+        })
+    }
+
+    /// Setzt die Modbus Slave ID für alle folgenden Anfragen
+    pub fn set_slave(&mut self, slave: u8) -> Result<(), LibModbusError> {
+        self.slave = slave;
+        Ok(())
+    }
+
+    /// Setzt die Wartezeit auf eine Antwort
+    pub fn set_response_timeout(&mut self, timeout: Timeout) -> Result<(), LibModbusError> {
+        self.response_timeout = Duration::from_secs(u64::from(timeout.sec))
+            + Duration::from_micros(u64::from(timeout.usec));
+        Ok(())
+    }
+
+    /// Liefert die Bytes der letzten Anfrage und ihrer Antwort
+    ///
+    /// Nur bei RTU, die ASCII Zeichen passen nicht in einen RTU Mitschnitt.
+    pub fn take_exchange(&mut self) -> Option<RawExchange> {
+        self.exchange.take()
+    }
+
+    /// Liest Holding Register (0x03)
+    pub fn read_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        self.read("read_registers", READ_HOLDING_REGISTERS, address, num, dest)
+    }
+
+    /// Liest Input Register (0x04)
+    pub fn read_input_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        self.read(
+            "read_input_registers",
+            READ_INPUT_REGISTERS,
+            address,
+            num,
+            dest,
+        )
+    }
+
+    /// Schreibt ein Register (0x06)
+    pub fn write_register(&mut self, address: u16, value: u16) -> Result<(), LibModbusError> {
+        let mut pdu = vec![WRITE_SINGLE_REGISTER];
+        pdu.extend_from_slice(&address.to_be_bytes());
+        pdu.extend_from_slice(&value.to_be_bytes());
+        // Die Antwort wiederholt Register und Wert
+        match self.transaction("write_register", &pdu)? {
+            Some(data) if data != pdu[1..] => Err(client_error("write_register", EMBBADDATA)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Schreibt mehrere Register (0x10)
+    pub fn write_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        src: &[u16],
+    ) -> Result<u16, LibModbusError> {
+        let values = &src[..usize::from(num).min(src.len())];
+        let mut pdu = vec![WRITE_MULTIPLE_REGISTERS];
+        pdu.extend_from_slice(&address.to_be_bytes());
+        pdu.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        pdu.push((2 * values.len()) as u8);
+        for value in values {
+            pdu.extend_from_slice(&value.to_be_bytes());
+        }
+        // Die Antwort wiederholt erstes Register und Anzahl
+        match self.transaction("write_registers", &pdu)? {
+            Some(data) if data != pdu[1..5] => Err(client_error("write_registers", EMBBADDATA)),
+            _ => Ok(values.len() as u16),
+        }
+    }
+
+    fn read(
+        &mut self,
+        msg: &str,
+        function: u8,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        let mut pdu = vec![function];
+        pdu.extend_from_slice(&address.to_be_bytes());
+        pdu.extend_from_slice(&num.to_be_bytes());
+        let data = self
+            .transaction(msg, &pdu)?
+            .ok_or_else(|| LibModbusError::Client {
+                msg: msg.to_string(),
+                source: io::Error::new(io::ErrorKind::InvalidInput, "Broadcast ohne Antwort"),
+            })?;
+        // Byte Anzahl, dann die Werte
+        if data.len() != 1 + 2 * usize::from(num) || usize::from(data[0]) != 2 * usize::from(num) {
+            return Err(client_error(msg, EMBBADDATA));
+        }
+        for (dest, word) in dest.iter_mut().zip(data[1..].chunks_exact(2)) {
+            *dest = u16::from_be_bytes([word[0], word[1]]);
+        }
+        Ok(num)
+    }
+
+    // Sendet die Anfrage und liefert die Daten der Antwort nach der Funktion
+    //
+    // Auf einen Broadcast antwortet kein Slave, dann wird direkt nach dem
+    // Senden `None` geliefert.
+    fn transaction(&mut self, msg: &str, pdu: &[u8]) -> Result<Option<Vec<u8>>, LibModbusError> {
+        let transmission_mode = self.transmission_mode;
+        let request = match transmission_mode {
+            TransmissionMode::Rtu => rtu::encode(self.slave, pdu),
+            TransmissionMode::Ascii => ascii::encode(self.slave, pdu),
+        };
+        if transmission_mode == TransmissionMode::Rtu {
+            if let Some(last_frame) = self.last_frame {
+                let elapsed = last_frame.elapsed();
+                if elapsed < self.frame_gap {
+                    thread::sleep(self.frame_gap - elapsed);
+                }
+            }
+        }
+
+        // Die Antwortzeit beginnt erst wenn die Anfrage gesendet ist
+        let timeout = self.response_timeout + self.char_time * request.len() as u32;
+        let gap = match self.port {
+            Stream::Serial(_) => self.frame_gap,
+            Stream::Tcp(_) => TCP_FRAME_END,
+        };
+        let broadcast = self.slave == BROADCAST_ADDRESS;
+        let port = &mut self.port;
+        let sent = &request;
+        let received = self.rt.block_on(async move {
+            discard(port).await?;
+            port.write_all(sent).await?;
+            if broadcast {
+                return Ok(None);
+            }
+            match transmission_mode {
+                TransmissionMode::Rtu => rtu::receive(port, timeout, gap).await.map(Some),
+                TransmissionMode::Ascii => ascii::receive(port, timeout).await.map(Some),
+            }
+        });
+        self.last_frame = Some(Instant::now());
+        if transmission_mode == TransmissionMode::Rtu {
+            self.exchange = Some(RawExchange {
+                request,
+                response: received.as_ref().ok().cloned().flatten(),
+            });
+        }
+
+        let frame = match received {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(None),
+            Err(source) => {
+                return Err(LibModbusError::Client {
+                    msg: msg.to_string(),
+                    source,
+                })
+            }
+        };
+        let bytes = match transmission_mode {
+            TransmissionMode::Rtu => rtu::check(&frame),
+            TransmissionMode::Ascii => ascii::check(&frame),
+        };
+        bytes
+            .and_then(|bytes| parse_response(self.slave, pdu[0], &bytes))
+            .map(Some)
+            .map_err(|code| client_error(msg, code))
+    }
+}
+
+// Fehler wie ihn libmodbus meldet, mit dem errno Wert `code`
+fn client_error(msg: &str, code: i64) -> LibModbusError {
+    LibModbusError::Client {
+        msg: msg.to_string(),
+        source: io::Error::from_raw_os_error(code as i32),
+    }
+}
+
+// Verbindung über die der Port sendet und empfängt
+enum Stream {
+    Serial(Serial),
+    Tcp(TcpStream),
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Serial(port) => Pin::new(port).poll_read(cx, buf),
+            Stream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Serial(port) => Pin::new(port).poll_write(cx, buf),
+            Stream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Serial(port) => Pin::new(port).poll_flush(cx),
+            Stream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Serial(port) => Pin::new(port).poll_shutdown(cx),
+            Stream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+// Verwirft Zeichen die noch von einer verspäteten Antwort stammen
+async fn discard(port: &mut Stream) -> io::Result<()> {
+    let mut buf = [0u8; 64];
+    while let Ok(read) = time::timeout(Duration::from_millis(0), port.read(&mut buf)).await {
+        if read? == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Prüft die Antwort auf eine Anfrage an `slave` mit `function`
+//
+// `bytes` sind Slave ID und PDU ohne Prüfsumme. Liefert die Daten nach der
+// Funktion, oder den errno Wert den libmodbus für den Fehler verwendet.
+fn parse_response(slave: u8, function: u8, bytes: &[u8]) -> Result<Vec<u8>, i64> {
+    if bytes.len() < 2 {
+        return Err(EMBBADDATA);
+    }
+    if bytes[0] != slave {
+        return Err(EMBBADSLAVE);
+    }
+    let data = &bytes[2..];
+    if bytes[1] == function | 0x80 {
+        // Exception Codes 1 bis 11 entsprechen EMBXILFUN bis EMBXGTAR
+        return Err(MODBUS_ENOBASE + i64::from(*data.first().ok_or(EMBBADDATA)?));
+    }
+    if bytes[1] != function {
+        return Err(EMBBADDATA);
+    }
+
+    Ok(data.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn parse_responses() {
+        // Slave 1 antwortet mit Input Register 2 = 35
+        let response = [0x01, 0x04, 0x02, 0x00, 0x23];
+        assert_eq!(
+            parse_response(1, 0x04, &response),
+            Ok(vec![0x02, 0x00, 0x23])
+        );
+        assert_eq!(parse_response(2, 0x04, &response), Err(EMBBADSLAVE));
+        assert_eq!(parse_response(1, 0x03, &response), Err(EMBBADDATA));
+        assert_eq!(
+            parse_response(1, 0x04, &[0x01, 0x84, 0x02]),
+            Err(MODBUS_ENOBASE + 2)
+        );
+        assert_eq!(parse_response(1, 0x04, &[0x01]), Err(EMBBADDATA));
+    }
+
+    #[test]
+    fn check_then_parse() {
+        let frame = rtu::encode(1, &[0x84, 0x02]);
+        assert_eq!(
+            rtu::check(&frame).and_then(|bytes| parse_response(1, 0x04, &bytes)),
+            Err(MODBUS_ENOBASE + 2)
+        );
+        let frame = ascii::encode(1, &[0x84, 0x02]);
+        assert_eq!(
+            ascii::check(&frame).and_then(|bytes| parse_response(1, 0x04, &bytes)),
+            Err(MODBUS_ENOBASE + 2)
+        );
+    }
+
+    #[test]
+    fn read_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Gateway mit Slave 1, Input Register 2 = 35
+        let gateway = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 8];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(
+                request.to_vec(),
+                rtu::encode(1, &[0x04, 0x00, 0x02, 0x00, 0x01])
+            );
+            stream
+                .write_all(&rtu::encode(1, &[0x04, 0x02, 0x00, 0x23]))
+                .unwrap();
+        });
+
+        let mut native = NativePort::connect("127.0.0.1", port).unwrap();
+        native.set_slave(1).unwrap();
+        let mut dest = [0u16; 1];
+        assert_eq!(native.read_input_registers(2, 1, &mut dest).unwrap(), 1);
+        assert_eq!(dest, [35]);
+        gateway.join().unwrap();
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{
+    modbus_master::{
+        bus::Bus, Connection, ErrorKind, ModbusMasterError, ModbusMasterEvent, PollSchedule,
+        Subscribers,
+    },
+    registers::{Rreg, Rwreg},
+};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Ein Sensor der Live Ansicht mit den Registern seiner Platine
+#[derive(Clone, Debug)]
+pub struct PollTarget {
+    /// Modbus Slave ID
+    pub slave: u8,
+    /// Lese-Register
+    pub rregs: Vec<Rreg>,
+    /// Schreib.-/ Lese-Register
+    pub rwregs: Vec<Rwreg>,
+    /// Entsperr Register Nummer
+    pub reg_protection: u16,
+}
+
+impl PollTarget {
+    /// Erstellt einen Sensor der Live Ansicht
+    pub fn new(slave: u8, rregs: Vec<Rreg>, rwregs: Vec<Rwreg>, reg_protection: u16) -> Self {
+        // Die Schreib.-/ Lese-Register werden nur in der RA-GAS Version gelesen
+        #[cfg(not(feature = "ra-gas"))]
+        let rwregs = {
+            let _ = rwregs;
+            vec![]
+        };
+
+        PollTarget {
+            slave,
+            rregs,
+            rwregs,
+            reg_protection,
+        }
+    }
+}
+
+/// Liest im Hintergrund zyklisch die Register eines oder mehrerer Sensoren
+/// (Live Ansicht)
+///
+/// Bei jedem Aufruf von `step` wird genau ein Register gelesen. So kann der
+/// Modbus Master zwischen zwei Registern die Anfragen des Benutzers bearbeiten.
+/// Welche Register in einem Durchlauf gelesen werden und wie lange zwischen
+/// zwei Durchläufen gewartet wird bestimmt der [`PollSchedule`]. Sind mehrere
+/// Sensoren angeschlossen, kommen sie reihum mit je einem Register dran, ein
+/// Sensor mit vielen Registern hält die anderen so nicht auf. Ist der
+/// Durchlauf eines Sensors abgeschlossen werden nur seine geänderten Werte an
+/// die Abonnenten gesendet.
+///
+/// Der Zustand der Verbindung wird in einer [`Connection`] verfolgt, sie ist
+/// gestört solange einer der Sensoren nicht antwortet.
+#[derive(Debug)]
+pub struct Poller {
+    tty_path: String,
+    targets: Vec<Target>,
+    schedule: PollSchedule,
+    // Nummer des nächsten Durchlaufs
+    cycle: u32,
+    // Beginn des nächsten Durchlaufs
+    next_cycle: Instant,
+    // Index des Sensors der als nächstes dran ist
+    next_target: usize,
+    connection: Connection,
+}
+
+// Zustand eines Sensors der Live Ansicht
+#[derive(Debug)]
+struct Target {
+    target: PollTarget,
+    // Register die in diesem Durchlauf noch gelesen werden
+    due: VecDeque<Slot>,
+    // Zuletzt gesendete Werte, enthält alle Register die in dieser Verbindung
+    // schon gelesen wurden
+    values: HashMap<Slot, u16>,
+    // Geänderte Werte die noch nicht gesendet wurden
+    changed_rregs: Vec<(u16, u16)>,
+    changed_rwregs: Vec<(u16, u16)>,
+    // Ist das letzte Register fehlgeschlagen?
+    failed: bool,
+    // Der Sensor startet neu, bis dahin wird er nicht gelesen
+    paused_until: Option<Instant>,
+}
+
+// Index eines Registers in `rregs` bzw. `rwregs`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Slot {
+    Rreg(usize),
+    Rwreg(usize),
+}
+
+impl Target {
+    fn new(target: PollTarget) -> Self {
+        Target {
+            target,
+            due: VecDeque::new(),
+            values: HashMap::new(),
+            changed_rregs: vec![],
+            changed_rwregs: vec![],
+            failed: false,
+            paused_until: None,
+        }
+    }
+
+    // Hat der Sensor Register?
+    fn has_registers(&self) -> bool {
+        !self.target.rregs.is_empty() || !self.target.rwregs.is_empty()
+    }
+
+    // Füllt die Register die laut Zeitplan in Durchlauf `cycle` fällig sind
+    fn start_cycle(&mut self, cycle: u32, schedule: &PollSchedule, now: Instant) {
+        match self.paused_until {
+            Some(until) if now < until => return,
+            Some(_) => self.paused_until = None,
+            None => {}
+        }
+        for (index, reg) in self.target.rregs.iter().enumerate() {
+            let slot = Slot::Rreg(index);
+            let rate = schedule.rreg_rate(reg.reg_nr() as u16);
+            if rate.is_due(cycle, self.values.contains_key(&slot)) {
+                self.due.push_back(slot);
+            }
+        }
+        for (index, reg) in self.target.rwregs.iter().enumerate() {
+            let slot = Slot::Rwreg(index);
+            let rate = schedule.rwreg_rate(reg.reg_nr() as u16);
+            if rate.is_due(cycle, self.values.contains_key(&slot)) {
+                self.due.push_back(slot);
+            }
+        }
+    }
+
+    // Sendet die geänderten Werte an die Abonnenten
+    fn publish(&mut self, subscribers: &mut Subscribers) {
+        let slave = self.target.slave;
+        if !self.changed_rregs.is_empty() {
+            subscribers.publish(ModbusMasterEvent::Rregs {
+                slave,
+                values: self.changed_rregs.split_off(0),
+            });
+        }
+        if !self.changed_rwregs.is_empty() {
+            subscribers.publish(ModbusMasterEvent::Rwregs {
+                slave,
+                values: self.changed_rwregs.split_off(0),
+            });
+        }
+    }
+}
+
+impl Poller {
+    /// Erstellt einen neuen Poller für die Sensoren `targets`
+    pub fn new(tty_path: String, targets: Vec<PollTarget>, schedule: PollSchedule) -> Self {
+        Poller {
+            tty_path,
+            targets: targets.into_iter().map(Target::new).collect(),
+            schedule,
+            cycle: 0,
+            next_cycle: Instant::now(),
+            next_target: 0,
+            connection: Connection::default(),
+        }
+    }
+
+    /// Ersetzt den Zeitplan, gilt ab dem nächsten Durchlauf
+    pub fn set_schedule(&mut self, schedule: PollSchedule) {
+        self.schedule = schedule;
+    }
+
+    /// Zeit bis zum nächsten fälligen Register
+    ///
+    /// So lange kann der Modbus Master auf Befehle warten.
+    pub fn idle_time(&self) -> Duration {
+        if self.targets.iter().all(|target| target.due.is_empty()) {
+            self.next_cycle.saturating_duration_since(Instant::now())
+        } else {
+            Duration::from_secs(0)
+        }
+    }
+
+    /// Startet die Live Ansicht
+    pub fn start(&mut self, subscribers: &mut Subscribers) {
+        self.connection.connect(subscribers);
+    }
+
+    /// Beendet die Live Ansicht
+    pub fn stop(&mut self, subscribers: &mut Subscribers) {
+        self.connection.disconnect(subscribers);
+    }
+
+    /// Der Sensor `slave` startet neu
+    ///
+    /// Die Live Ansicht wartet `delay` und liest dann alle Register des Sensors
+    /// neu, auch die selten gelesenen. Bis der Sensor wieder antwortet ist die
+    /// Verbindung im Zustand `Connecting`. Gehört der Sensor nicht zur Live
+    /// Ansicht passiert nichts.
+    pub fn restart(&mut self, slave: u8, delay: Duration, subscribers: &mut Subscribers) {
+        let target = match self
+            .targets
+            .iter_mut()
+            .find(|target| target.target.slave == slave)
+        {
+            Some(target) => target,
+            None => return,
+        };
+        target.due.clear();
+        target.values.clear();
+        target.changed_rregs.clear();
+        target.changed_rwregs.clear();
+        target.failed = false;
+        target.paused_until = Some(Instant::now() + delay);
+        self.connection.restart(subscribers);
+    }
+
+    /// Liest das nächste Register
+    ///
+    /// Liefert `false` wenn die Schnittstelle nicht mehr geöffnet werden kann,
+    /// z.B. weil der Adapter abgezogen wurde. Die Live Ansicht ist dann beendet.
+    pub fn step(&mut self, bus: &mut Bus, subscribers: &mut Subscribers) -> bool {
+        // Ohne Register gibt es nichts zu lesen
+        if !self.targets.iter().any(Target::has_registers) {
+            return false;
+        }
+        if self.targets.iter().all(|target| target.due.is_empty()) {
+            let now = Instant::now();
+            if now < self.next_cycle {
+                return true;
+            }
+            self.start_cycle(now);
+        }
+        let targets = &self.targets;
+        let index = match round_robin(self.next_target, targets.len(), |index| {
+            !targets[index].due.is_empty()
+        }) {
+            Some(index) => index,
+            // In diesem Durchlauf ist kein Register fällig
+            None => return true,
+        };
+        self.next_target = index + 1;
+
+        if let Err(error) = bus.open(&self.tty_path) {
+            self.connection.lost(&error, subscribers);
+            return false;
+        }
+
+        let target = &mut self.targets[index];
+        let slave = target.target.slave;
+        let slot = target.due[0];
+        let result = match slot {
+            Slot::Rreg(reg_index) => {
+                let reg = target.target.rregs[reg_index].clone();
+                super::read_input_register(bus, &self.tty_path, slave, reg)
+            }
+            Slot::Rwreg(reg_index) => {
+                let reg = target.target.rwregs[reg_index].clone();
+                super::read_holding_register(
+                    bus,
+                    &self.tty_path,
+                    slave,
+                    reg,
+                    target.target.reg_protection,
+                )
+            }
+        };
+        let (reg_nr, value) = match result {
+            Ok(value) => value,
+            Err(error) => {
+                // Der Durchlauf des Sensors wird abgebrochen, der nächste
+                // beginnt planmäßig. Antwortet nur dieser Sensor nicht, bleiben
+                // die Schnittstelle und die Entsperrung der anderen erhalten.
+                target.failed = true;
+                target.due.clear();
+                self.connection.poll_failed(&error, subscribers);
+                if is_slave_error(&error) {
+                    bus.forget_unlock(slave);
+                } else {
+                    bus.close();
+                }
+                return true;
+            }
+        };
+        target.failed = false;
+        target.due.pop_front();
+
+        if target.values.insert(slot, value) != Some(value) {
+            match slot {
+                Slot::Rreg(_) => target.changed_rregs.push((reg_nr, value)),
+                Slot::Rwreg(_) => target.changed_rwregs.push((reg_nr, value)),
+            }
+        }
+        if target.due.is_empty() {
+            target.publish(subscribers);
+        }
+        if !self.targets.iter().any(|target| target.failed) {
+            self.connection.poll_succeeded(subscribers);
+        }
+
+        true
+    }
+
+    // Beginnt einen neuen Durchlauf mit allen Registern die laut Zeitplan fällig sind
+    fn start_cycle(&mut self, now: Instant) {
+        let cycle = self.cycle;
+        self.cycle = self.cycle.wrapping_add(1);
+        self.next_cycle = now + self.schedule.interval;
+        self.next_target = 0;
+
+        for target in self.targets.iter_mut() {
+            target.start_cycle(cycle, &self.schedule, now);
+        }
+    }
+}
+
+// Nächster Index ab `start` (reihum) für den `has_due` zutrifft
+fn round_robin(start: usize, len: usize, has_due: impl Fn(usize) -> bool) -> Option<usize> {
+    (0..len)
+        .map(|offset| (start + offset) % len)
+        .find(|index| has_due(*index))
+}
+
+// Betrifft der Fehler nur den abgefragten Sensor? Timeouts, CRC Fehler und
+// Exceptions ja, Fehler der Schnittstelle oder beim Öffnen nicht.
+fn is_slave_error(error: &ModbusMasterError) -> bool {
+    match error {
+        ModbusMasterError::ReadInputRegister { source, .. }
+        | ModbusMasterError::ReadHoldingRegister { source, .. }
+        | ModbusMasterError::LibModbusError(source) => ErrorKind::of(source) != ErrorKind::Other,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus_master::rtu;
+    use libmodbus::prelude::Error as LibModbusError;
+    use std::io;
+
+    #[test]
+    fn close_only_on_port_errors() {
+        let read_error = |code: i64| ModbusMasterError::ReadInputRegister {
+            reg_nr: 2,
+            source: LibModbusError::Client {
+                msg: "read_input_registers".to_string(),
+                source: io::Error::from_raw_os_error(code as i32),
+            },
+        };
+        assert!(is_slave_error(&read_error(rtu::ETIMEDOUT)));
+        assert!(is_slave_error(&read_error(rtu::EMBBADCRC)));
+        assert!(is_slave_error(&read_error(rtu::EMBXILADD)));
+        // EIO, z.B. abgezogener Adapter
+        assert!(!is_slave_error(&read_error(5)));
+        assert!(!is_slave_error(&ModbusMasterError::NotConnected));
+    }
+
+    #[test]
+    fn round_robin_is_fair() {
+        let due = [true, false, true, true];
+        let has_due = |index: usize| due[index];
+        assert_eq!(round_robin(0, 4, has_due), Some(0));
+        assert_eq!(round_robin(1, 4, has_due), Some(2));
+        assert_eq!(round_robin(4, 4, has_due), Some(0));
+        assert_eq!(round_robin(0, 4, |_| false), None);
+        assert_eq!(round_robin(0, 0, has_due), None);
+    }
+}
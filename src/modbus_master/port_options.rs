@@ -0,0 +1,202 @@
+//! Richtungsumschaltung, Pausen und Treiber einer Schnittstelle
+//!
+//! Manche RS485 Adapter schalten die Senderichtung nicht selbst um, sondern
+//! über die RTS Leitung, eventuell mit einer Verzögerung. Ältere Firmware, z.B.
+//! NE4 V1.0, benötigt außerdem längere Pausen zwischen den Frames als die
+//! 3,5 Zeichen der Modbus Spezifikation. Statt libmodbus kann für Modbus RTU
+//! auch die eigene Umsetzung ([`NativePort`](super::native::NativePort))
+//! verwendet werden.
+//!
+//! Die Optionen gelten pro Schnittstelle und stehen in der Einstellungsdatei,
+//! z.B. `port_options = A12345 => mode=rs485 rts=up rts_delay=1000 frame_gap=20`.
+use libmodbus::{prelude::Error as LibModbusError, Modbus, ModbusRTU, RequestToSendMode};
+use std::fmt;
+use std::time::Duration;
+
+/// Betriebsart der seriellen Schnittstelle
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SerialMode {
+    /// RS232, die Richtung schaltet der Adapter selbst (Standard)
+    Rs232,
+    /// RS485 Modus des Treibers (`TIOCSRS485`)
+    Rs485,
+}
+
+/// Steuerung der Senderichtung über RTS
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rts {
+    /// Keine Steuerung über RTS (Standard)
+    None,
+    /// RTS ist beim Senden gesetzt
+    Up,
+    /// RTS ist beim Senden zurückgesetzt
+    Down,
+}
+
+/// Umsetzung der Modbus RTU Übertragung
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Driver {
+    /// libmodbus (Standard)
+    Libmodbus,
+    /// Eigene Umsetzung, siehe [`NativePort`](super::native::NativePort)
+    Native,
+}
+
+/// Optionen einer Schnittstelle
+///
+/// Standard ist RS232 ohne RTS Steuerung und ohne zusätzliche Pause, also das
+/// Verhalten von libmodbus.
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::modbus_master::PortOptions;
+/// use std::time::Duration;
+///
+/// let options = PortOptions::parse("mode=rs485 rts=up frame_gap=20").unwrap();
+/// assert_eq!(options.frame_gap, Duration::from_millis(20));
+/// assert_eq!(options.to_string(), "mode=rs485 rts=up frame_gap=20");
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PortOptions {
+    /// Betriebsart (`mode=rs232|rs485`)
+    pub serial_mode: SerialMode,
+    /// Steuerung der Senderichtung (`rts=none|up|down`)
+    pub rts: Rts,
+    /// Verzögerung nach dem Umschalten von RTS in µs, `None` lässt den Wert
+    /// von libmodbus (`rts_delay=…`)
+    pub rts_delay: Option<u32>,
+    /// Mindestpause zwischen zwei Anfragen (`frame_gap=…` in ms)
+    pub frame_gap: Duration,
+    /// Umsetzung von Modbus RTU (`driver=libmodbus|native`)
+    ///
+    /// Modbus ASCII läuft immer über die eigene Umsetzung.
+    pub driver: Driver,
+}
+
+impl Default for PortOptions {
+    fn default() -> Self {
+        PortOptions {
+            serial_mode: SerialMode::Rs232,
+            rts: Rts::None,
+            rts_delay: None,
+            frame_gap: Duration::from_millis(0),
+            driver: Driver::Libmodbus,
+        }
+    }
+}
+
+impl PortOptions {
+    /// Liest die Optionen aus der Form `schlüssel=wert`, durch Leerzeichen getrennt
+    ///
+    /// Nicht genannte Optionen behalten ihren Standard.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut options = PortOptions::default();
+        for option in text.split_whitespace() {
+            let (key, value) = match option.find('=') {
+                Some(pos) => (&option[..pos], &option[pos + 1..]),
+                None => return Err(format!("'=' fehlt: {}", option)),
+            };
+            let invalid = || format!("ungültiger Wert für {}: {}", key, value);
+            match key {
+                "mode" => {
+                    options.serial_mode = match value {
+                        "rs232" => SerialMode::Rs232,
+                        "rs485" => SerialMode::Rs485,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "rts" => {
+                    options.rts = match value {
+                        "none" => Rts::None,
+                        "up" => Rts::Up,
+                        "down" => Rts::Down,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "rts_delay" => options.rts_delay = Some(value.parse().map_err(|_| invalid())?),
+                "frame_gap" => {
+                    options.frame_gap = Duration::from_millis(value.parse().map_err(|_| invalid())?)
+                }
+                "driver" => {
+                    options.driver = match value {
+                        "libmodbus" => Driver::Libmodbus,
+                        "native" => Driver::Native,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(format!("unbekannte Option: {}", key)),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Stellt Betriebsart und RTS Steuerung am geöffneten Modbus Context ein
+    ///
+    /// Die Standard Werte werden nicht gesetzt, so bleiben Adapter deren
+    /// Treiber keinen RS485 Modus kennen unberührt.
+    pub fn apply(&self, modbus: &mut Modbus) -> Result<(), LibModbusError> {
+        if self.serial_mode == SerialMode::Rs485 {
+            modbus.rtu_set_serial_mode(libmodbus::SerialMode::RtuRS485)?;
+        }
+        match self.rts {
+            Rts::None => {}
+            Rts::Up => modbus.rtu_set_rts(RequestToSendMode::RtuRtsUp)?,
+            Rts::Down => modbus.rtu_set_rts(RequestToSendMode::RtuRtsDown)?,
+        }
+        if let Some(rts_delay) = self.rts_delay {
+            modbus.rtu_set_rts_delay(rts_delay as i32)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Wie in der Einstellungsdatei, nur die vom Standard abweichenden Optionen
+impl fmt::Display for PortOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = vec![];
+        if self.serial_mode == SerialMode::Rs485 {
+            options.push("mode=rs485".to_string());
+        }
+        match self.rts {
+            Rts::None => {}
+            Rts::Up => options.push("rts=up".to_string()),
+            Rts::Down => options.push("rts=down".to_string()),
+        }
+        if let Some(rts_delay) = self.rts_delay {
+            options.push(format!("rts_delay={}", rts_delay));
+        }
+        if self.frame_gap > Duration::from_millis(0) {
+            options.push(format!("frame_gap={}", self.frame_gap.as_millis()));
+        }
+        if self.driver == Driver::Native {
+            options.push("driver=native".to_string());
+        }
+        if options.is_empty() {
+            write!(f, "Standard")
+        } else {
+            write!(f, "{}", options.join(" "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let options = PortOptions::parse("rts=down rts_delay=1000").unwrap();
+        assert_eq!(options.serial_mode, SerialMode::Rs232);
+        assert_eq!(options.rts, Rts::Down);
+        assert_eq!(options.rts_delay, Some(1000));
+        assert_eq!(PortOptions::parse("").unwrap(), PortOptions::default());
+        assert!(PortOptions::parse("mode=rs422").is_err());
+        assert!(PortOptions::parse("frame_gap").is_err());
+        assert!(PortOptions::parse("baud=9600").is_err());
+        let options = PortOptions::parse("driver=native").unwrap();
+        assert_eq!(options.driver, Driver::Native);
+        assert_eq!(options.to_string(), "driver=native");
+    }
+}
@@ -0,0 +1,241 @@
+//! Modbus RTU
+//!
+//! Eigene Umsetzung der RTU Übertragung, unabhängig von libmodbus. Ein Frame
+//! besteht aus Slave ID, PDU und CRC16 (Low Byte zuerst). Frames werden durch
+//! eine Pause von 3,5 Zeichen getrennt, siehe [`frame_gap`].
+//!
+//! Der [`NativePort`](super::native::NativePort) sendet und empfängt damit die
+//! Anfragen des Modbus Masters, der [`Sniffer`](crate::sniffer::Sniffer)
+//! zerlegt damit die mitgehörten Bytes.
+//!
+//! Hier stehen auch die `errno` Werte von libmodbus, mit denen alle
+//! Übertragungen ihre Fehler melden.
+use crate::modbus_master::traffic::{
+    READ_HOLDING_REGISTERS, READ_INPUT_REGISTERS, WRITE_MULTIPLE_REGISTERS, WRITE_SINGLE_REGISTER,
+};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    time,
+};
+
+// errno von libmodbus für Timeouts (ETIMEDOUT), die C Runtime von Windows
+// verwendet einen anderen Wert als Linux
+#[cfg(not(windows))]
+pub(crate) const ETIMEDOUT: i64 = 110;
+#[cfg(windows)]
+pub(crate) const ETIMEDOUT: i64 = 138;
+// Basis der eigenen errno Werte von libmodbus (MODBUS_ENOBASE)
+pub(crate) const MODBUS_ENOBASE: i64 = 112_345_678;
+// Exception Illegal Function (EMBXILFUN)
+pub(crate) const EMBXILFUN: i64 = MODBUS_ENOBASE + 1;
+// Exception Illegal Data Address (EMBXILADD)
+pub(crate) const EMBXILADD: i64 = MODBUS_ENOBASE + 2;
+// Exception Illegal Data Value (EMBXILVAL)
+pub(crate) const EMBXILVAL: i64 = MODBUS_ENOBASE + 3;
+// Ungültige CRC (EMBBADCRC)
+pub(crate) const EMBBADCRC: i64 = MODBUS_ENOBASE + 12;
+// Ungültige Antwort (EMBBADDATA)
+pub(crate) const EMBBADDATA: i64 = MODBUS_ENOBASE + 13;
+// Antwort eines anderen Slaves (EMBBADSLAVE)
+pub(crate) const EMBBADSLAVE: i64 = MODBUS_ENOBASE + 17;
+
+/// Kürzester Frame: Slave, Funktion, CRC
+pub const MIN_FRAME_LEN: usize = 4;
+/// Längster Frame nach Modbus Spezifikation
+pub const MAX_FRAME_LEN: usize = 256;
+// Erlaubte Pause zwischen zwei Zeichen eines unvollständigen Frames, wie bei libmodbus
+const BYTE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Pause die einen Frame beendet (3,5 Zeichen)
+///
+/// Ein Zeichen sind 11 Bit. Über 19200 Baud gilt laut Spezifikation fest
+/// 1,75ms. Kürzer als 2ms wird nicht gewartet, genauer sind die Timer nicht.
+pub fn frame_gap(baud_rate: i32) -> Duration {
+    let micros = if baud_rate > 19200 {
+        1750
+    } else {
+        38_500_000 / baud_rate.max(1) as u64
+    };
+    Duration::from_micros(micros).max(Duration::from_millis(2))
+}
+
+/// CRC16 nach Modbus (Polynom 0xA001, Startwert 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xa001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Erstellt den Frame für Slave ID und PDU
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::modbus_master::rtu;
+///
+/// // Slave 1 liest Input Register 2
+/// let frame = rtu::encode(1, &[0x04, 0x00, 0x02, 0x00, 0x01]);
+/// assert_eq!(frame, vec![0x01, 0x04, 0x00, 0x02, 0x00, 0x01, 0x90, 0x0a]);
+/// ```
+pub fn encode(slave: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(pdu.len() + 3);
+    frame.push(slave);
+    frame.extend_from_slice(pdu);
+    let crc = crc16(&frame);
+    // Die CRC wird im Gegensatz zu den Daten Low Byte zuerst übertragen
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// Stimmt die CRC am Ende des Frames?
+pub fn crc_ok(frame: &[u8]) -> bool {
+    if frame.len() < MIN_FRAME_LEN {
+        return false;
+    }
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    crc16(data).to_le_bytes() == [crc[0], crc[1]]
+}
+
+/// Mögliche Längen eines Frames anhand von Funktion und Byte Anzahl
+///
+/// Anfrage und Antwort werden nicht unterschieden, leer für unbekannte
+/// Funktionen oder wenn die Funktion noch nicht empfangen ist.
+pub fn expected_lengths(bytes: &[u8]) -> Vec<usize> {
+    let function = match bytes.get(1) {
+        Some(function) => *function,
+        None => return vec![],
+    };
+    match function {
+        _ if function & 0x80 != 0 => vec![5],
+        READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+            let mut lengths = vec![8];
+            if let Some(byte_count) = bytes.get(2) {
+                lengths.push(5 + usize::from(*byte_count));
+            }
+            lengths
+        }
+        WRITE_SINGLE_REGISTER => vec![8],
+        WRITE_MULTIPLE_REGISTERS => {
+            let mut lengths = vec![8];
+            if let Some(byte_count) = bytes.get(6) {
+                lengths.push(9 + usize::from(*byte_count));
+            }
+            lengths
+        }
+        _ => vec![],
+    }
+}
+
+// Prüft Länge und CRC eines empfangenen Frames, liefert Slave ID und PDU
+pub(crate) fn check(frame: &[u8]) -> Result<Vec<u8>, i64> {
+    if frame.len() < MIN_FRAME_LEN {
+        return Err(EMBBADDATA);
+    }
+    if !crc_ok(frame) {
+        return Err(EMBBADCRC);
+    }
+    Ok(frame[..frame.len() - 2].to_vec())
+}
+
+// Wartezeit auf das nächste Zeichen eines begonnenen Frames, `None` wenn der
+// Frame vollständig ist
+//
+// USB Adapter liefern die Zeichen gepuffert, Pausen über 3,5 Zeichen sind dort
+// auch innerhalb eines Frames normal. Solange nach Funktion und Byte Anzahl
+// noch Zeichen fehlen, gilt deshalb die Wartezeit zwischen zwei Zeichen wie bei
+// libmodbus. Sonst beendet die Pause `gap` den Frame.
+fn next_timeout(frame: &[u8], gap: Duration) -> Option<Duration> {
+    let lengths = expected_lengths(frame);
+    if lengths.contains(&frame.len()) && crc_ok(frame) || frame.len() >= MAX_FRAME_LEN {
+        None
+    } else if frame.len() < 2 || lengths.iter().any(|len| *len > frame.len()) {
+        Some(BYTE_TIMEOUT)
+    } else {
+        Some(gap)
+    }
+}
+
+// Empfängt den nächsten Frame
+//
+// Bis zum ersten Zeichen gilt die Antwortzeit `timeout`, danach endet der Frame
+// sobald er vollständig ist oder nach einer Pause, siehe `next_timeout`.
+pub(crate) async fn receive(
+    port: &mut (impl AsyncRead + Unpin),
+    timeout: Duration,
+    gap: Duration,
+) -> io::Result<Vec<u8>> {
+    let deadline = Instant::now() + timeout;
+    let mut frame = vec![];
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    loop {
+        let timeout = if frame.is_empty() {
+            deadline.saturating_duration_since(Instant::now())
+        } else {
+            match next_timeout(&frame, gap) {
+                Some(timeout) => timeout,
+                None => return Ok(frame),
+            }
+        };
+        let read = match time::timeout(timeout, port.read(&mut buf)).await {
+            Ok(read) => read?,
+            Err(_) if frame.is_empty() => {
+                return Err(io::Error::from_raw_os_error(ETIMEDOUT as i32))
+            }
+            Err(_) => return Ok(frame),
+        };
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let free = MAX_FRAME_LEN - frame.len();
+        frame.extend_from_slice(&buf[..read.min(free)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_of_read_request() {
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), 0x0a84);
+    }
+
+    #[test]
+    fn encode_and_check() {
+        let frame = encode(1, &[0x03, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(frame, vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0a]);
+        assert_eq!(check(&frame), Ok(frame[..6].to_vec()));
+
+        let mut corrupt = frame;
+        corrupt[5] = 0x02;
+        assert_eq!(check(&corrupt), Err(EMBBADCRC));
+        assert_eq!(check(&[0x01, 0x03, 0x84]), Err(EMBBADDATA));
+    }
+
+    #[test]
+    fn frame_end() {
+        let gap = frame_gap(9600);
+        let response = encode(1, &[0x04, 0x02, 0x00, 0x23]);
+        assert_eq!(next_timeout(&response, gap), None);
+        assert_eq!(next_timeout(&response[..1], gap), Some(BYTE_TIMEOUT));
+        assert_eq!(next_timeout(&response[..5], gap), Some(BYTE_TIMEOUT));
+        // Unbekannte Funktion, nur die Pause beendet den Frame
+        assert_eq!(next_timeout(&[0x01, 0x2b, 0x0e], gap), Some(gap));
+    }
+
+    #[test]
+    fn frame_gap_by_baud_rate() {
+        assert_eq!(frame_gap(9600), Duration::from_micros(4010));
+        assert_eq!(frame_gap(115200), Duration::from_millis(2));
+    }
+}
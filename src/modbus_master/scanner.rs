@@ -0,0 +1,410 @@
+//! Bus Scanner
+//!
+//! Sucht auf einer seriellen Schnittstelle nach antwortenden Sensoren. Jede
+//! Adresse des gewählten Bereichs wird, für jede gewählte Baudrate, mit einer
+//! kurzen Antwortzeit abgefragt.
+//!
+//! Außerdem können mit dem [`DetectJob`] Baudrate und Parität eines einzelnen
+//! Sensors gesucht werden, dessen Rwreg 81 (Baudrate) oder Rwreg 82 (Modbus
+//! Mode) verstellt wurde, auch wenn seine Adresse unbekannt ist, und die
+//! Adresse des einzigen Sensors am Bus. Erkennung und Bus Scan verwenden die
+//! Übertragungsart der Sitzung.
+use crate::{
+    modbus_master::{
+        bus::{Bus, Link},
+        line_settings::{TransmissionMode, ASCII_FRAMINGS, MODBUS_MODES},
+        single_device, LineSettings, ModbusMasterError, ModbusMasterEvent, Subscribers,
+    },
+    platine,
+};
+use libmodbus::Timeout;
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Baudraten der Sensoren
+///
+/// Tupple (Wert Rwreg 81, Baudrate)
+pub const BAUD_RATES: &[(u16, i32)] = &[(0, 2400), (1, 9600), (2, 19200), (3, 38400)];
+
+// Antwortzeit je Adresse in Mikrosekunden
+const SCAN_RESPONSE_TIMEOUT: u32 = 100_000;
+
+/// Ein beim Bus Scan gefundener Sensor
+#[derive(Clone, Debug)]
+pub struct BusDevice {
+    /// Modbus Slave ID
+    pub slave: u8,
+    /// Baudrate unter der der Sensor geantwortet hat
+    pub baud_rate: i32,
+    /// Erkannte Platine, Name aus `platine::HW_VERSIONS`
+    pub platine: Option<&'static str>,
+    /// Arbeitsweise (Rreg 1)
+    pub working_mode: u16,
+    /// Softwaredatum (Rreg 49)
+    pub software_date: Option<u16>,
+}
+
+/// Ein laufender Bus Scan
+///
+/// Der Scan wird vom Modbus Master schrittweise ausgeführt, bei jedem Aufruf
+/// von `step` wird genau eine Adresse abgefragt. Zwischen zwei Adressen kann
+/// der Modbus Master so andere Anfragen bearbeiten oder den Scan abbrechen.
+#[derive(Debug)]
+pub struct ScanJob {
+    tty_path: String,
+    // Noch abzufragende (Baudrate, Modbus Slave ID)
+    pending: VecDeque<(i32, u8)>,
+    total: usize,
+    found: usize,
+    cancelled: bool,
+}
+
+impl ScanJob {
+    /// Erstellt einen neuen Bus Scan
+    pub fn new(tty_path: String, slaves: RangeInclusive<u8>, baud_rates: Vec<i32>) -> Self {
+        info!(
+            "Bus Scan: tty_path: {}, slaves: {:?}, baud_rates: {:?}",
+            tty_path, slaves, baud_rates
+        );
+
+        let pending: VecDeque<(i32, u8)> = baud_rates
+            .iter()
+            .flat_map(|baud_rate| slaves.clone().map(move |slave| (*baud_rate, slave)))
+            .collect();
+
+        ScanJob {
+            tty_path,
+            total: pending.len(),
+            pending,
+            found: 0,
+            cancelled: false,
+        }
+    }
+
+    /// Bricht den Scan beim nächsten Schritt ab
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    /// Fragt die nächste Adresse ab
+    ///
+    /// Der Fortschritt und jeder gefundene Sensor werden an die Abonnenten
+    /// gesendet. Liefert `false` wenn der Scan beendet ist.
+    pub fn step(&mut self, bus: &mut Bus, subscribers: &mut Subscribers) -> bool {
+        let (baud_rate, slave) = match self.pending.pop_front() {
+            Some(next) if !self.cancelled => next,
+            _ => {
+                // Die Schnittstelle wurde mit kurzer Antwortzeit geöffnet
+                bus.close();
+                subscribers.publish(ModbusMasterEvent::ScanFinished {
+                    found: self.found,
+                    cancelled: self.cancelled,
+                });
+                return false;
+            }
+        };
+
+        let line_settings = scan_line_settings(bus.line_settings(), baud_rate);
+        let mut modbus = match open_for_probing(bus, &self.tty_path, &line_settings) {
+            Ok(modbus) => modbus,
+            Err(error) => {
+                bus.close();
+                subscribers.publish(ModbusMasterEvent::ScanFailed(Arc::new(error)));
+                return false;
+            }
+        };
+
+        if let Some(device) = probe(&mut modbus, slave, baud_rate) {
+            debug!("Bus Scan: found {:?}", device);
+            self.found += 1;
+            subscribers.publish(ModbusMasterEvent::ScanDeviceFound(device));
+        }
+
+        subscribers.publish(ModbusMasterEvent::ScanProgress {
+            done: self.total - self.pending.len(),
+            total: self.total,
+        });
+
+        true
+    }
+}
+
+/// Eine laufende Suche nach Baudrate und Parität eines Sensors
+///
+/// Alle Baudraten aus [`BAUD_RATES`] werden in der Übertragungsart der Sitzung
+/// probiert, mit RTU in allen [`MODBUS_MODES`] der Sensoren, mit ASCII in den
+/// [`ASCII_FRAMINGS`]. Ohne Slave ID wird der einzige Sensor am Bus gesucht, je
+/// Kombination unter allen Adressen in der Reihenfolge von
+/// [`single_device::search_order`]. Einen Broadcast beantworten die Sensoren
+/// nicht.
+///
+/// Wie der [`ScanJob`] wird die Suche schrittweise ausgeführt, bei jedem Aufruf
+/// von `step` wird genau eine Kombination unter einer Adresse abgefragt.
+#[derive(Debug)]
+pub struct DetectJob {
+    tty_path: String,
+    // Noch zu probierende (Parameter, Modbus Slave ID)
+    pending: VecDeque<(LineSettings, u8)>,
+    total: usize,
+    cancelled: bool,
+}
+
+impl DetectJob {
+    /// Erstellt eine neue Suche, `slave` ist `None` für den einzigen Sensor am Bus
+    pub fn new(tty_path: String, slave: Option<u8>, transmission_mode: TransmissionMode) -> Self {
+        info!(
+            "Detect line settings: tty_path: {}, slave: {:?}, transmission_mode: {}",
+            tty_path, slave, transmission_mode
+        );
+
+        let slaves: Vec<u8> = match slave {
+            Some(slave) => vec![slave],
+            None => single_device::search_order().collect(),
+        };
+        let pending: VecDeque<(LineSettings, u8)> = detect_candidates(transmission_mode)
+            .into_iter()
+            .flat_map(|line_settings| slaves.iter().map(move |slave| (line_settings, *slave)))
+            .collect();
+
+        DetectJob {
+            tty_path,
+            total: pending.len(),
+            pending,
+            cancelled: false,
+        }
+    }
+
+    /// Bricht die Suche beim nächsten Schritt ab
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    /// Probiert die nächste Kombination
+    ///
+    /// Der Fortschritt und das Ergebnis werden an die Abonnenten gesendet.
+    /// Liefert `false` wenn die Suche beendet ist.
+    pub fn step(&mut self, bus: &mut Bus, subscribers: &mut Subscribers) -> bool {
+        let (line_settings, slave) = match self.pending.pop_front() {
+            Some(next) if !self.cancelled => next,
+            _ => {
+                self.finish(bus, subscribers, None);
+                return false;
+            }
+        };
+        debug!("Detect line settings: try {} at {}", line_settings, slave);
+
+        let mut modbus = match open_for_probing(bus, &self.tty_path, &line_settings) {
+            Ok(modbus) => modbus,
+            Err(error) => {
+                bus.close();
+                subscribers.publish(ModbusMasterEvent::DetectFailed(Arc::new(error)));
+                return false;
+            }
+        };
+        if probe(&mut modbus, slave, line_settings.baud_rate).is_some() {
+            self.finish(bus, subscribers, Some((slave, line_settings)));
+            return false;
+        }
+
+        subscribers.publish(ModbusMasterEvent::DetectProgress {
+            done: self.total - self.pending.len(),
+            total: self.total,
+        });
+
+        true
+    }
+
+    fn finish(
+        &self,
+        bus: &mut Bus,
+        subscribers: &mut Subscribers,
+        found: Option<(u8, LineSettings)>,
+    ) {
+        // Die Schnittstelle wurde mit kurzer Antwortzeit geöffnet
+        bus.close();
+        subscribers.publish(ModbusMasterEvent::DetectFinished {
+            found,
+            cancelled: self.cancelled,
+        });
+    }
+}
+
+// Die bei der Erkennung probierten Parameter in der Reihenfolge der Suche
+fn detect_candidates(transmission_mode: TransmissionMode) -> Vec<LineSettings> {
+    let framings: Vec<(i32, char, i32)> = match transmission_mode {
+        TransmissionMode::Rtu => MODBUS_MODES
+            .iter()
+            .map(|(_value, parity, stop_bits)| (8, *parity, *stop_bits))
+            .collect(),
+        TransmissionMode::Ascii => ASCII_FRAMINGS.to_vec(),
+    };
+    BAUD_RATES
+        .iter()
+        .flat_map(|(_value, baud_rate)| {
+            framings
+                .iter()
+                .map(move |(data_bits, parity, stop_bits)| LineSettings {
+                    baud_rate: *baud_rate,
+                    parity: *parity,
+                    data_bits: *data_bits,
+                    stop_bits: *stop_bits,
+                    transmission_mode,
+                })
+        })
+        .collect()
+}
+
+// Parameter des Bus Scans mit `baud_rate`
+//
+// Mit RTU wird wie bisher mit 8N1 gesucht, mit ASCII gelten Datenbits,
+// Parität und Stopbits der Sitzung.
+fn scan_line_settings(session: LineSettings, baud_rate: i32) -> LineSettings {
+    match session.transmission_mode {
+        TransmissionMode::Rtu => LineSettings {
+            baud_rate,
+            ..Default::default()
+        },
+        TransmissionMode::Ascii => LineSettings {
+            baud_rate,
+            ..session
+        },
+    }
+}
+
+/// Sucht die erste Adresse aus `slaves` unter der ein Sensor antwortet
+///
+/// Gesucht wird mit den Schnittstellenparametern der Sitzung. Liefert `None`
+/// wenn unter keiner Adresse ein Sensor antwortet.
+pub fn find_first(
+    bus: &mut Bus,
+    tty_path: &str,
+    slaves: impl IntoIterator<Item = u8>,
+) -> Result<Option<BusDevice>, ModbusMasterError> {
+    info!("Find first device: tty_path: {}", tty_path);
+
+    let line_settings = bus.line_settings();
+    let result = open_for_probing(bus, tty_path, &line_settings).map(|mut modbus| {
+        slaves
+            .into_iter()
+            .find_map(|slave| probe(&mut modbus, slave, line_settings.baud_rate))
+    });
+    // Die Schnittstelle wurde mit kurzer Antwortzeit geöffnet
+    bus.close();
+
+    result
+}
+
+// Öffnet die Schnittstelle mit kurzer Antwortzeit
+fn open_for_probing<'a>(
+    bus: &'a mut Bus,
+    tty_path: &str,
+    line_settings: &LineSettings,
+) -> Result<Link<'a>, ModbusMasterError> {
+    let mut modbus = bus.open_with(tty_path, line_settings)?;
+    modbus.set_response_timeout(Timeout {
+        sec: 0,
+        usec: SCAN_RESPONSE_TIMEOUT,
+    })?;
+
+    Ok(modbus)
+}
+
+// Fragt eine einzelne Adresse ab
+//
+// Antwortet der Sensor auf die Arbeitsweise (Rreg 1) gilt er als gefunden,
+// das Softwaredatum (Rreg 49) ist optional.
+fn probe(modbus: &mut Link<'_>, slave: u8, baud_rate: i32) -> Option<BusDevice> {
+    modbus.set_slave(slave).ok()?;
+
+    let mut value = vec![0u16; 1];
+    modbus.read_input_registers(1, 1, &mut value).ok()?;
+    let working_mode = value[0];
+
+    let software_date = match modbus.read_input_registers(49, 1, &mut value) {
+        Ok(_) => Some(value[0]),
+        Err(_) => None,
+    };
+
+    Some(BusDevice {
+        slave,
+        baud_rate,
+        platine: platine::detect_platine(working_mode, software_date),
+        working_mode,
+        software_date,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus_master::line_change;
+
+    #[test]
+    fn detect_in_session_transmission_mode() {
+        let rtu = detect_candidates(TransmissionMode::Rtu);
+        assert_eq!(rtu.len(), BAUD_RATES.len() * MODBUS_MODES.len());
+        assert_eq!(rtu[0].to_string(), "2400 8N1");
+        let ascii = detect_candidates(TransmissionMode::Ascii);
+        assert_eq!(ascii.len(), BAUD_RATES.len() * ASCII_FRAMINGS.len());
+        assert_eq!(
+            ascii[..3]
+                .iter()
+                .map(LineSettings::to_string)
+                .collect::<Vec<_>>(),
+            vec!["2400 7E1 ASCII", "2400 7O1 ASCII", "2400 7N2 ASCII"]
+        );
+    }
+
+    #[test]
+    fn detect_each_mode_at_all_addresses() {
+        let job = DetectJob::new("/dev/ttyUSB0".to_string(), None, TransmissionMode::Rtu);
+        assert_eq!(job.total, BAUD_RATES.len() * MODBUS_MODES.len() * 247);
+        let first: Vec<(String, u8)> = job
+            .pending
+            .iter()
+            .take(2)
+            .map(|(line_settings, slave)| (line_settings.to_string(), *slave))
+            .collect();
+        assert_eq!(
+            first,
+            vec![
+                ("2400 8N1".to_string(), single_device::SYSTEM_PLUG_ADDRESS),
+                ("2400 8N1".to_string(), 1)
+            ]
+        );
+        let job = DetectJob::new("/dev/ttyUSB0".to_string(), Some(5), TransmissionMode::Rtu);
+        assert_eq!(job.total, BAUD_RATES.len() * MODBUS_MODES.len());
+    }
+
+    #[test]
+    fn detect_only_sensor_modes() {
+        for candidate in detect_candidates(TransmissionMode::Rtu) {
+            let (baud_value, mode_value) = line_change::register_values(&candidate).unwrap();
+            let settings =
+                line_change::with_register_value(LineSettings::default(), 79, 81, baud_value)
+                    .and_then(|settings| {
+                        line_change::with_register_value(settings, 79, 82, mode_value)
+                    });
+            assert_eq!(settings, Some(candidate));
+        }
+    }
+
+    #[test]
+    fn scan_with_session_transmission_mode() {
+        let session = LineSettings {
+            parity: 'E',
+            ..Default::default()
+        };
+        assert_eq!(scan_line_settings(session, 19200).to_string(), "19200 8N1");
+        let session = LineSettings {
+            data_bits: 7,
+            transmission_mode: TransmissionMode::Ascii,
+            ..session
+        };
+        assert_eq!(
+            scan_line_settings(session, 19200).to_string(),
+            "19200 7E1 ASCII"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Wartezeit zwischen zwei Durchläufen der Live Ansicht
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Wie oft ein Register in der Live Ansicht gelesen wird
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PollRate {
+    /// In jedem Durchlauf
+    EveryCycle,
+    /// In jedem n-ten Durchlauf
+    Every(u32),
+    /// Einmal pro Verbindung, für statische Register wie die Gerätekennung
+    Once,
+}
+
+impl PollRate {
+    /// Wird das Register im Durchlauf `cycle` gelesen?
+    ///
+    /// `read` gibt an ob das Register in dieser Verbindung schon gelesen wurde.
+    /// Der erste Durchlauf hat die Nummer 0, dort werden alle Register gelesen.
+    pub fn is_due(self, cycle: u32, read: bool) -> bool {
+        match self {
+            PollRate::EveryCycle => true,
+            PollRate::Every(n) => n <= 1 || cycle % n == 0,
+            PollRate::Once => !read,
+        }
+    }
+}
+
+/// Zeitplan der Live Ansicht
+///
+/// Ein Durchlauf liest alle fälligen Register, der nächste Durchlauf beginnt
+/// frühestens `interval` nach dem Beginn des vorherigen. Wie oft ein Register
+/// gelesen wird bestimmt die Rate seiner Gruppe (Lese-Register bzw. Schreib.-/
+/// Lese-Register), sofern für das Register keine eigene Rate festgelegt ist.
+#[derive(Clone, Debug)]
+pub struct PollSchedule {
+    /// Wartezeit zwischen zwei Durchläufen
+    pub interval: Duration,
+    /// Rate der Lese-Register
+    pub rreg_rate: PollRate,
+    /// Rate der Schreib.-/ Lese-Register
+    pub rwreg_rate: PollRate,
+    /// Raten einzelner Lese-Register (Register Nummer, Rate)
+    pub rreg_rates: HashMap<u16, PollRate>,
+    /// Raten einzelner Schreib.-/ Lese-Register (Register Nummer, Rate)
+    pub rwreg_rates: HashMap<u16, PollRate>,
+}
+
+impl Default for PollSchedule {
+    /// Lese-Register in jedem Durchlauf, Schreib.-/ Lese-Register in jedem
+    /// fünften. Gerätekennung, Softwaredatum und Betriebsstunden werden einmal
+    /// pro Verbindung gelesen.
+    fn default() -> Self {
+        PollSchedule {
+            interval: POLL_INTERVAL,
+            rreg_rate: PollRate::EveryCycle,
+            rwreg_rate: PollRate::Every(5),
+            rreg_rates: vec![
+                // Gerätekennung Kunden
+                (0, PollRate::Once),
+                // Softwaredatum
+                (49, PollRate::Once),
+            ]
+            .into_iter()
+            .collect(),
+            rwreg_rates: vec![
+                // Betriebsstunden
+                (97, PollRate::Once),
+                // Gerätekennung vom Werk
+                (98, PollRate::Once),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+impl PollSchedule {
+    /// Rate eines Lese-Registers
+    pub fn rreg_rate(&self, reg_nr: u16) -> PollRate {
+        *self.rreg_rates.get(&reg_nr).unwrap_or(&self.rreg_rate)
+    }
+
+    /// Rate eines Schreib.-/ Lese-Registers
+    pub fn rwreg_rate(&self, reg_nr: u16) -> PollRate {
+        *self.rwreg_rates.get(&reg_nr).unwrap_or(&self.rwreg_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_cycle() {
+        assert!(PollRate::EveryCycle.is_due(0, false));
+        assert!(PollRate::EveryCycle.is_due(7, true));
+    }
+
+    #[test]
+    fn every_nth_cycle() {
+        let rate = PollRate::Every(5);
+        assert!(rate.is_due(0, false));
+        assert!(!rate.is_due(1, true));
+        assert!(!rate.is_due(4, true));
+        assert!(rate.is_due(5, true));
+        // 0 und 1 bedeuten jeder Durchlauf
+        assert!(PollRate::Every(0).is_due(3, true));
+        assert!(PollRate::Every(1).is_due(3, true));
+    }
+
+    #[test]
+    fn once_per_connection() {
+        assert!(PollRate::Once.is_due(0, false));
+        assert!(!PollRate::Once.is_due(1, true));
+        // Konnte das Register noch nicht gelesen werden wird es erneut versucht
+        assert!(PollRate::Once.is_due(3, false));
+    }
+
+    #[test]
+    fn register_rate_overrides_group_rate() {
+        let schedule = PollSchedule::default();
+        assert_eq!(schedule.rreg_rate(0), PollRate::Once);
+        assert_eq!(schedule.rreg_rate(2), PollRate::EveryCycle);
+        assert_eq!(schedule.rwreg_rate(97), PollRate::Once);
+        assert_eq!(schedule.rwreg_rate(80), PollRate::Every(5));
+    }
+}
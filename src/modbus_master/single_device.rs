@@ -0,0 +1,196 @@
+//! Adressierung des einzigen Sensors am Bus
+//!
+//! Ist nur ein Sensor angeschlossen, muss seine Adresse zum Umadressieren
+//! nicht bekannt sein. Entweder wird die erste Adresse gesucht unter der ein
+//! Sensor antwortet, oder die neue Adresse wird per Broadcast (Slave 0)
+//! geschrieben. Den Broadcast nimmt nicht jede Firmware an, die Sensoren
+//! antworten darauf grundsätzlich nicht.
+use crate::modbus_master::{
+    bus::{Bus, UNLOCK_CODE},
+    scanner, ErrorKind, ModbusMasterError,
+};
+use libmodbus::prelude::Error as LibModbusError;
+use std::iter;
+use std::thread;
+use std::time::Duration;
+
+/// Modbus Adresse mit gestecktem Systemstecker
+pub const SYSTEM_PLUG_ADDRESS: u8 = 247;
+
+// Modbus Broadcast Adresse
+const BROADCAST_ADDRESS: u8 = 0;
+// Höchste gültige Slave ID, 248 bis 255 sind reserviert
+pub(crate) const MAX_SLAVE_ID: u8 = 247;
+// Pause nach einem Broadcast, die Sensoren bearbeiten ihn ohne zu antworten
+const BROADCAST_TURNAROUND: Duration = Duration::from_millis(100);
+
+/// Wie der einzige Sensor am Bus angesprochen wird
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Addressing {
+    /// Unter der ersten Adresse die antwortet, siehe [`search_order`]
+    FirstResponding,
+    /// Per Broadcast an Slave 0
+    Broadcast,
+}
+
+/// Reihenfolge der Suche nach dem einzigen Sensor
+///
+/// Zuerst die Adresse des Systemsteckers, dann alle anderen gültigen Slave IDs
+/// (1 bis 247) aufsteigend.
+pub fn search_order() -> impl Iterator<Item = u8> {
+    iter::once(SYSTEM_PLUG_ADDRESS)
+        .chain((1..=MAX_SLAVE_ID).filter(|slave| *slave != SYSTEM_PLUG_ADDRESS))
+}
+
+/// Setzt die Modbus Adresse (Rwreg 80) bzw. MCS Adresse (Rwreg 95) des
+/// einzigen Sensors am Bus
+///
+/// Liefert die Adresse unter der der Sensor gefunden wurde, bei einem
+/// Broadcast `None`.
+pub fn set_address(
+    bus: &mut Bus,
+    tty_path: &str,
+    addressing: Addressing,
+    mcs_config: bool,
+    new_slave_id: u16,
+    reg_protection: u16,
+    verify: bool,
+) -> Result<Option<u8>, ModbusMasterError> {
+    info!(
+        "Set address of single device: tty_path: {}, addressing: {:?}, new_slave_id: {}",
+        tty_path, addressing, new_slave_id
+    );
+
+    // Vor der Suche prüfen, nicht erst beim Schreiben
+    if !mcs_config {
+        super::modbus_slave_id(new_slave_id)?;
+    }
+
+    match addressing {
+        Addressing::FirstResponding => {
+            let slave = find(bus, tty_path)?;
+            if mcs_config {
+                super::set_new_mcs_bus_id(
+                    bus,
+                    tty_path,
+                    slave,
+                    new_slave_id,
+                    reg_protection,
+                    verify,
+                )?;
+            } else {
+                super::set_new_modbus_id(
+                    bus,
+                    tty_path,
+                    slave,
+                    new_slave_id,
+                    reg_protection,
+                    verify,
+                )?;
+            }
+            Ok(Some(slave))
+        }
+        Addressing::Broadcast => {
+            broadcast(
+                bus,
+                tty_path,
+                mcs_config,
+                new_slave_id,
+                reg_protection,
+                verify,
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+// Adresse des ersten Sensors der antwortet
+fn find(bus: &mut Bus, tty_path: &str) -> Result<u8, ModbusMasterError> {
+    match scanner::find_first(bus, tty_path, search_order())? {
+        Some(device) => {
+            debug!("Single device: found {:?}", device);
+            Ok(device.slave)
+        }
+        None => Err(ModbusMasterError::NoDeviceFound),
+    }
+}
+
+// Entsperrt den Sensor und schreibt die neue Adresse per Broadcast
+//
+// Die neue Modbus Adresse wird direkt unter der neuen Adresse zurück gelesen,
+// für die MCS Adresse wird der Sensor vorher gesucht.
+fn broadcast(
+    bus: &mut Bus,
+    tty_path: &str,
+    mcs_config: bool,
+    new_slave_id: u16,
+    reg_protection: u16,
+    verify: bool,
+) -> Result<(), ModbusMasterError> {
+    let reg_nr = if mcs_config {
+        95
+    } else if reg_protection == 79 {
+        80
+    } else {
+        50
+    };
+
+    let new_slave = if mcs_config {
+        None
+    } else {
+        Some(super::modbus_slave_id(new_slave_id)?)
+    };
+
+    // Die alte Adresse ist unbekannt, keine bestehende Entsperrung gilt sicher
+    bus.forget_all_unlocks();
+    {
+        let mut modbus = bus.open(tty_path)?;
+        modbus.set_slave(BROADCAST_ADDRESS)?;
+        without_response(
+            reg_protection,
+            modbus.write_register(reg_protection, UNLOCK_CODE),
+        )?;
+        thread::sleep(BROADCAST_TURNAROUND);
+        without_response(reg_nr, modbus.write_register(reg_nr, new_slave_id))?;
+    }
+
+    if verify {
+        thread::sleep(BROADCAST_TURNAROUND);
+        let slave = match new_slave {
+            Some(slave) => slave,
+            None => find(bus, tty_path)?,
+        };
+        let mut modbus = bus.open(tty_path)?;
+        modbus.set_slave(slave)?;
+        super::verify_register(&mut modbus, reg_nr, new_slave_id)?;
+    }
+
+    Ok(())
+}
+
+// Auf einen Broadcast antwortet kein Sensor, der Timeout ist kein Fehler
+fn without_response(
+    reg_nr: u16,
+    result: Result<(), LibModbusError>,
+) -> Result<(), ModbusMasterError> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(error) if ErrorKind::of(&error) == ErrorKind::Timeout => Ok(()),
+        Err(source) => Err(ModbusMasterError::WriteRegister { reg_nr, source }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_starts_with_system_plug() {
+        let order: Vec<u8> = search_order().collect();
+        assert_eq!(order.len(), 247);
+        assert_eq!(&order[..3], &[247, 1, 2]);
+        assert_eq!(order.iter().filter(|slave| **slave == 247).count(), 1);
+        assert!(!order.contains(&0));
+        assert!(!order.contains(&248));
+    }
+}
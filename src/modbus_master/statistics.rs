@@ -0,0 +1,332 @@
+//! Statistik der Kommunikation pro Slave
+//!
+//! Der [`Bus`](super::Bus) zählt für jeden Slave die Anfragen und ihre
+//! Ergebnisse über die ganze Sitzung. Der Modbus Master sendet die Statistik
+//! regelmäßig als
+//! [`ModbusMasterEvent::Statistics`](super::ModbusMasterEvent::Statistics) an
+//! die Abonnenten.
+use crate::modbus_master::rtu::{EMBBADCRC, ETIMEDOUT, MODBUS_ENOBASE};
+use libmodbus::prelude::Error as LibModbusError;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Art eines Fehlers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// Der Slave hat nicht (rechtzeitig) geantwortet
+    Timeout,
+    /// Die Antwort hatte eine falsche CRC
+    Crc,
+    /// Der Slave hat mit einer Modbus Exception geantwortet
+    Exception,
+    /// Alle anderen Fehler, z.B. der Schnittstelle
+    Other,
+}
+
+impl ErrorKind {
+    /// Ordnet einen Fehler von libmodbus bzw. des Transports einer Art zu
+    ///
+    /// libmodbus meldet Fehler über `errno`, der Wert steht im `source` des
+    /// Fehlers.
+    pub fn of(error: &LibModbusError) -> ErrorKind {
+        let source = match error {
+            LibModbusError::Client { source, .. } => source,
+            _ => return ErrorKind::Other,
+        };
+        if source.kind() == io::ErrorKind::TimedOut {
+            return ErrorKind::Timeout;
+        }
+        match source.raw_os_error().map(i64::from) {
+            Some(ETIMEDOUT) => ErrorKind::Timeout,
+            Some(EMBBADCRC) => ErrorKind::Crc,
+            // EMBXILFUN .. EMBXGTAR, die Exception Codes 1 bis 11
+            Some(code) if code > MODBUS_ENOBASE && code < EMBBADCRC => ErrorKind::Exception,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Ordnet einen Fehler des [`Sniffer`](crate::sniffer::Sniffer) einer Art zu
+    ///
+    /// Der Sniffer kennt nur CRC Fehler ("CRC Fehler") und Exceptions
+    /// ("Exception 0x02 ..."). Fehler des Modbus Masters werden mit
+    /// [`ErrorKind::of`] zugeordnet.
+    pub fn classify(error: &str) -> ErrorKind {
+        if error.starts_with("CRC Fehler") {
+            ErrorKind::Crc
+        } else if error.starts_with("Exception") {
+            ErrorKind::Exception
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+/// Zähler eines Slaves
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SlaveStatistics {
+    /// Anzahl Anfragen
+    pub requests: u64,
+    /// Anzahl erfolgreicher Anfragen
+    pub successes: u64,
+    /// Anzahl Timeouts
+    pub timeouts: u64,
+    /// Anzahl Antworten mit falscher CRC
+    pub crc_errors: u64,
+    /// Anzahl Exceptions
+    pub exceptions: u64,
+    /// Anzahl anderer Fehler
+    pub other_errors: u64,
+    /// Kürzeste Laufzeit einer erfolgreichen Anfrage
+    pub min_latency: Option<Duration>,
+    /// Längste Laufzeit einer erfolgreichen Anfrage
+    pub max_latency: Option<Duration>,
+    // Summe und Anzahl der bekannten Laufzeiten erfolgreicher Anfragen
+    total_latency: Duration,
+    latencies: u32,
+}
+
+impl SlaveStatistics {
+    /// Zählt eine Anfrage, `error` ist die Art des Fehlers falls sie fehlgeschlagen ist
+    pub fn record(&mut self, latency: Option<Duration>, error: Option<ErrorKind>) {
+        self.requests += 1;
+        match error {
+            None => {
+                self.successes += 1;
+                if let Some(latency) = latency {
+                    self.total_latency += latency;
+                    self.latencies += 1;
+                    self.min_latency =
+                        Some(self.min_latency.map_or(latency, |min| min.min(latency)));
+                    self.max_latency =
+                        Some(self.max_latency.map_or(latency, |max| max.max(latency)));
+                }
+            }
+            Some(ErrorKind::Timeout) => self.timeouts += 1,
+            Some(ErrorKind::Crc) => self.crc_errors += 1,
+            Some(ErrorKind::Exception) => self.exceptions += 1,
+            Some(ErrorKind::Other) => self.other_errors += 1,
+        }
+    }
+
+    /// Anzahl fehlgeschlagener Anfragen
+    pub fn errors(&self) -> u64 {
+        self.timeouts + self.crc_errors + self.exceptions + self.other_errors
+    }
+
+    /// Mittlere Laufzeit der erfolgreichen Anfragen
+    pub fn avg_latency(&self) -> Option<Duration> {
+        match self.latencies {
+            0 => None,
+            latencies => Some(self.total_latency / latencies),
+        }
+    }
+
+    /// Anteil erfolgreicher Anfragen in Prozent
+    pub fn success_rate(&self) -> Option<f64> {
+        match self.requests {
+            0 => None,
+            requests => Some(self.successes as f64 * 100.0 / requests as f64),
+        }
+    }
+}
+
+/// Zähler aller Slaves einer Sitzung
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Statistics {
+    slaves: BTreeMap<u8, SlaveStatistics>,
+}
+
+impl Statistics {
+    /// Zählt eine Anfrage an `slave`
+    pub fn record(&mut self, slave: u8, latency: Option<Duration>, error: Option<ErrorKind>) {
+        self.slaves.entry(slave).or_default().record(latency, error);
+    }
+
+    /// Zähler eines Slaves
+    pub fn slave(&self, slave: u8) -> Option<&SlaveStatistics> {
+        self.slaves.get(&slave)
+    }
+
+    /// Zähler aller Slaves, aufsteigend nach Slave ID
+    pub fn slaves(&self) -> impl Iterator<Item = (u8, &SlaveStatistics)> {
+        self.slaves
+            .iter()
+            .map(|(slave, statistics)| (*slave, statistics))
+    }
+
+    /// Summe der Zähler aller Slaves
+    pub fn total(&self) -> SlaveStatistics {
+        self.slaves
+            .values()
+            .fold(SlaveStatistics::default(), |mut total, statistics| {
+                total.requests += statistics.requests;
+                total.successes += statistics.successes;
+                total.timeouts += statistics.timeouts;
+                total.crc_errors += statistics.crc_errors;
+                total.exceptions += statistics.exceptions;
+                total.other_errors += statistics.other_errors;
+                total.total_latency += statistics.total_latency;
+                total.latencies += statistics.latencies;
+                total.min_latency = min_option(total.min_latency, statistics.min_latency);
+                total.max_latency = total.max_latency.max(statistics.max_latency);
+                total
+            })
+    }
+
+    /// Sind noch keine Anfragen gezählt?
+    pub fn is_empty(&self) -> bool {
+        self.slaves.is_empty()
+    }
+
+    /// Setzt alle Zähler zurück
+    pub fn reset(&mut self) {
+        self.slaves.clear();
+    }
+}
+
+/// Zusammenfassung für die Statusleiste
+///
+/// z.B. "Bus: 120 Anfragen, 98.3% erfolgreich, 2 Timeouts, 0 CRC Fehler, Ø 12ms"
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        write!(
+            f,
+            "Bus: {} Anfragen, {:.1}% erfolgreich, {} Timeouts, {} CRC Fehler",
+            total.requests,
+            total.success_rate().unwrap_or(0.0),
+            total.timeouts,
+            total.crc_errors
+        )?;
+        if let Some(avg) = total.avg_latency() {
+            write!(f, ", Ø {}ms", avg.as_millis())?;
+        }
+        Ok(())
+    }
+}
+
+// Kleinerer Wert, `None` zählt nicht
+fn min_option(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+// Laufzeit in Millisekunden, leer wenn unbekannt
+fn millis(latency: Option<Duration>) -> String {
+    latency
+        .map(|latency| latency.as_millis().to_string())
+        .unwrap_or_default()
+}
+
+/// Schreibt die Statistik als CSV, eine Zeile pro Slave
+pub fn write_csv<W: Write>(statistics: &Statistics, writer: W) -> io::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(&[
+        "Slave",
+        "Anfragen",
+        "Erfolgreich",
+        "Timeouts",
+        "CRC Fehler",
+        "Exceptions",
+        "Andere Fehler",
+        "Laufzeit min [ms]",
+        "Laufzeit Ø [ms]",
+        "Laufzeit max [ms]",
+    ])?;
+    for (slave, statistics) in statistics.slaves() {
+        wtr.write_record(&[
+            slave.to_string(),
+            statistics.requests.to_string(),
+            statistics.successes.to_string(),
+            statistics.timeouts.to_string(),
+            statistics.crc_errors.to_string(),
+            statistics.exceptions.to_string(),
+            statistics.other_errors.to_string(),
+            millis(statistics.min_latency),
+            millis(statistics.avg_latency()),
+            millis(statistics.max_latency),
+        ])?;
+    }
+    wtr.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        assert_eq!(
+            ErrorKind::classify("Exception 0x02 (Illegal Data Address)"),
+            ErrorKind::Exception
+        );
+        assert_eq!(ErrorKind::classify("CRC Fehler"), ErrorKind::Crc);
+        assert_eq!(ErrorKind::classify("Permission denied"), ErrorKind::Other);
+    }
+
+    #[test]
+    fn kind_of_errors() {
+        let error = |source| LibModbusError::Client {
+            msg: "read_registers".to_string(),
+            source,
+        };
+        assert_eq!(
+            ErrorKind::of(&error(io::Error::from_raw_os_error(ETIMEDOUT as i32))),
+            ErrorKind::Timeout
+        );
+        assert_eq!(
+            ErrorKind::of(&error(io::ErrorKind::TimedOut.into())),
+            ErrorKind::Timeout
+        );
+        assert_eq!(
+            ErrorKind::of(&error(io::Error::from_raw_os_error(
+                (MODBUS_ENOBASE + 2) as i32
+            ))),
+            ErrorKind::Exception
+        );
+        assert_eq!(
+            ErrorKind::of(&error(io::Error::from_raw_os_error(EMBBADCRC as i32))),
+            ErrorKind::Crc
+        );
+        assert_eq!(
+            ErrorKind::of(&error(io::ErrorKind::PermissionDenied.into())),
+            ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn latency_of_successful_requests() {
+        let mut statistics = SlaveStatistics::default();
+        statistics.record(Some(Duration::from_millis(10)), None);
+        statistics.record(Some(Duration::from_millis(30)), None);
+        statistics.record(Some(Duration::from_millis(500)), Some(ErrorKind::Timeout));
+        assert_eq!(statistics.requests, 3);
+        assert_eq!(statistics.successes, 2);
+        assert_eq!(statistics.timeouts, 1);
+        assert_eq!(statistics.min_latency, Some(Duration::from_millis(10)));
+        assert_eq!(statistics.avg_latency(), Some(Duration::from_millis(20)));
+        assert_eq!(statistics.max_latency, Some(Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn total_and_reset() {
+        let mut statistics = Statistics::default();
+        statistics.record(1, Some(Duration::from_millis(10)), None);
+        statistics.record(2, None, Some(ErrorKind::Crc));
+        let total = statistics.total();
+        assert_eq!(total.requests, 2);
+        assert_eq!(total.crc_errors, 1);
+        assert_eq!(
+            statistics.to_string(),
+            "Bus: 2 Anfragen, 50.0% erfolgreich, 0 Timeouts, 1 CRC Fehler, Ø 10ms"
+        );
+
+        statistics.reset();
+        assert!(statistics.is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+//! Mitschnitt des Modbus Verkehrs
+//!
+//! Ist der Mitschnitt eingeschaltet, hält der Modbus Master jede Anfrage und
+//! jede Antwort als [`TrafficRecord`] fest und sendet sie als
+//! [`ModbusMasterEvent::Traffic`](super::ModbusMasterEvent::Traffic) an die
+//! Abonnenten. Die Einträge können als CSV oder als pcap Datei exportiert
+//! werden.
+use crate::modbus_master::rtu::crc16;
+use chrono::{DateTime, Local};
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Read Holding Registers
+pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+/// Read Input Registers
+pub const READ_INPUT_REGISTERS: u8 = 0x04;
+/// Write Single Register
+pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+/// Write Multiple Registers
+pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// pcap Link Typ der Pakete (LINKTYPE_USER0)
+///
+/// Einen eigenen Link Typ für Modbus RTU gibt es nicht. In Wireshark wird
+/// unter "DLT_USER" für "User 0 (DLT=147)" das Protokoll "mbrtu" eingetragen.
+pub const PCAP_LINKTYPE: u32 = 147;
+
+/// Richtung eines Eintrags
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Anfrage des Masters
+    Request,
+    /// Antwort des Sensors
+    Response,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Request => write!(f, "Anfrage"),
+            Direction::Response => write!(f, "Antwort"),
+        }
+    }
+}
+
+/// Ein Eintrag des Mitschnitts
+#[derive(Clone, Debug)]
+pub struct TrafficRecord {
+    /// Zeitpunkt
+    pub timestamp: DateTime<Local>,
+    /// Anfrage oder Antwort
+    pub direction: Direction,
+    /// Modbus Slave ID
+    pub slave: u8,
+    /// Modbus Funktion
+    pub function: u8,
+    /// Erstes Register
+    pub address: u16,
+    /// Anzahl Register
+    pub count: u16,
+    /// Geschriebene bzw. gelesene Werte
+    pub values: Vec<u16>,
+    /// Laufzeit der Anfrage, nur bei Antworten
+    pub latency: Option<Duration>,
+    /// Fehler, nur bei Antworten
+    pub error: Option<String>,
+    /// Klartext, z.B. "Slave 12 liest Rreg 2 = 35 (Gaskonzentration)"
+    ///
+    /// Wird nur vom [`Sniffer`](crate::sniffer::Sniffer) gefüllt.
+    pub description: Option<String>,
+    /// Gesendete bzw. empfangene Bytes inklusive CRC, falls bekannt
+    pub frame: Option<Vec<u8>>,
+}
+
+/// Bytes einer Anfrage und ihrer Antwort, wie sie über die Schnittstelle liefen
+///
+/// Liefert nur der [`NativePort`](super::native::NativePort) bei Modbus RTU.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RawExchange {
+    /// Gesendete Anfrage
+    pub request: Vec<u8>,
+    /// Empfangene Antwort, `None` wenn keine kam
+    pub response: Option<Vec<u8>>,
+}
+
+impl TrafficRecord {
+    /// Registerbereich, z.B. "2" oder "10-12"
+    pub fn registers(&self) -> String {
+        match self.count {
+            0 | 1 => self.address.to_string(),
+            count => format!(
+                "{}-{}",
+                self.address,
+                u32::from(self.address) + u32::from(count) - 1
+            ),
+        }
+    }
+
+    /// Werte durch Leerzeichen getrennt
+    pub fn values(&self) -> String {
+        self.values
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Der Modbus RTU Frame des Eintrags
+    ///
+    /// Sind die Bytes bekannt ([`frame`](Self::frame)), werden sie unverändert
+    /// geliefert. libmodbus gibt die gesendeten Bytes aber nicht heraus, dann
+    /// wird der Frame aus dem Eintrag nachgebildet. Für fehlgeschlagene
+    /// Anfragen ist die Antwort nicht bekannt, ebenso für Frames mit falscher
+    /// CRC. In beiden Fällen wird `None` geliefert.
+    pub fn rtu_frame(&self) -> Option<Vec<u8>> {
+        if let Some(frame) = &self.frame {
+            return Some(frame.clone());
+        }
+        if self.error.is_some() {
+            return None;
+        }
+        let mut frame = vec![self.slave, self.function];
+        match (self.direction, self.function) {
+            (Direction::Request, READ_HOLDING_REGISTERS)
+            | (Direction::Request, READ_INPUT_REGISTERS) => {
+                push_u16(&mut frame, self.address);
+                push_u16(&mut frame, self.count);
+            }
+            (Direction::Response, READ_HOLDING_REGISTERS)
+            | (Direction::Response, READ_INPUT_REGISTERS) => {
+                frame.push((self.values.len() * 2) as u8);
+                self.values
+                    .iter()
+                    .for_each(|value| push_u16(&mut frame, *value));
+            }
+            (_, WRITE_SINGLE_REGISTER) => {
+                push_u16(&mut frame, self.address);
+                push_u16(&mut frame, *self.values.first()?);
+            }
+            (Direction::Request, WRITE_MULTIPLE_REGISTERS) => {
+                push_u16(&mut frame, self.address);
+                push_u16(&mut frame, self.count);
+                frame.push((self.values.len() * 2) as u8);
+                self.values
+                    .iter()
+                    .for_each(|value| push_u16(&mut frame, *value));
+            }
+            (Direction::Response, WRITE_MULTIPLE_REGISTERS) => {
+                push_u16(&mut frame, self.address);
+                push_u16(&mut frame, self.count);
+            }
+            _ => return None,
+        }
+        let crc = crc16(&frame);
+        // Die CRC wird im Gegensatz zu den Daten Low Byte zuerst übertragen
+        frame.extend_from_slice(&crc.to_le_bytes());
+        Some(frame)
+    }
+}
+
+/// Sammelt die Einträge des Mitschnitts
+///
+/// Der Recorder gehört dem [`Bus`](super::Bus). Der Modbus Master holt die
+/// neuen Einträge nach jedem Befehl bzw. Schritt ab und sendet sie an die
+/// Abonnenten.
+#[derive(Debug, Default)]
+pub struct TrafficRecorder {
+    enabled: bool,
+    records: Vec<TrafficRecord>,
+}
+
+impl TrafficRecorder {
+    /// Ist der Mitschnitt eingeschaltet?
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Schaltet den Mitschnitt ein oder aus
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.records.clear();
+        }
+    }
+
+    /// Hält eine Anfrage mit ihrer Antwort fest
+    ///
+    /// `response` sind die gelesenen Werte oder der Fehler der Anfrage,
+    /// `exchange` die Bytes falls der Transport sie kennt.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &mut self,
+        timestamp: DateTime<Local>,
+        latency: Duration,
+        slave: u8,
+        function: u8,
+        address: u16,
+        count: u16,
+        written: &[u16],
+        response: Result<&[u16], String>,
+        exchange: Option<RawExchange>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let (request_frame, response_frame) = match exchange {
+            Some(exchange) => (Some(exchange.request), exchange.response),
+            None => (None, None),
+        };
+        self.records.push(TrafficRecord {
+            timestamp,
+            direction: Direction::Request,
+            slave,
+            function,
+            address,
+            count,
+            values: written.to_vec(),
+            latency: None,
+            error: None,
+            description: None,
+            frame: request_frame,
+        });
+        let (values, error) = match response {
+            Ok(values) => (values.to_vec(), None),
+            Err(error) => (vec![], Some(error)),
+        };
+        let timestamp = chrono::Duration::from_std(latency)
+            .map(|latency| timestamp + latency)
+            .unwrap_or(timestamp);
+        self.records.push(TrafficRecord {
+            timestamp,
+            direction: Direction::Response,
+            slave,
+            function,
+            address,
+            count,
+            values,
+            latency: Some(latency),
+            error,
+            description: None,
+            frame: response_frame,
+        });
+    }
+
+    /// Liefert die neuen Einträge, der Recorder ist danach leer
+    pub fn take(&mut self) -> Vec<TrafficRecord> {
+        self.records.split_off(0)
+    }
+}
+
+/// Schreibt die Einträge als CSV
+pub fn write_csv<W: Write>(records: &[TrafficRecord], writer: W) -> io::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(&[
+        "Zeit",
+        "Richtung",
+        "Slave",
+        "Funktion",
+        "Register",
+        "Werte",
+        "Laufzeit [ms]",
+        "Fehler",
+        "Beschreibung",
+    ])?;
+    for record in records {
+        wtr.write_record(&[
+            record.timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            record.direction.to_string(),
+            record.slave.to_string(),
+            format!("0x{:02X}", record.function),
+            record.registers(),
+            record.values(),
+            record
+                .latency
+                .map(|latency| latency.as_millis().to_string())
+                .unwrap_or_default(),
+            record.error.clone().unwrap_or_default(),
+            record.description.clone().unwrap_or_default(),
+        ])?;
+    }
+    wtr.flush()
+}
+
+/// Schreibt die Einträge als pcap Datei
+///
+/// Jeder Eintrag mit bekanntem Frame wird ein Paket, siehe
+/// [`TrafficRecord::rtu_frame`] und [`PCAP_LINKTYPE`].
+pub fn write_pcap<W: Write>(records: &[TrafficRecord], mut writer: W) -> io::Result<()> {
+    // Globaler Header: Magic, Version 2.4, Zeitzone, Genauigkeit, Snaplen, Link Typ
+    writer.write_all(&0xa1b2_c3d4u32.to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&4u16.to_le_bytes())?;
+    writer.write_all(&0i32.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&65535u32.to_le_bytes())?;
+    writer.write_all(&PCAP_LINKTYPE.to_le_bytes())?;
+
+    for record in records {
+        if let Some(frame) = record.rtu_frame() {
+            writer.write_all(&(record.timestamp.timestamp() as u32).to_le_bytes())?;
+            writer.write_all(&record.timestamp.timestamp_subsec_micros().to_le_bytes())?;
+            writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+            writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+            writer.write_all(&frame)?;
+        }
+    }
+    writer.flush()
+}
+
+// Hängt einen Wert High Byte zuerst an
+fn push_u16(frame: &mut Vec<u8>, value: u16) {
+    frame.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(direction: Direction, function: u8, values: Vec<u16>) -> TrafficRecord {
+        TrafficRecord {
+            timestamp: Local::now(),
+            direction,
+            slave: 1,
+            function,
+            address: 0,
+            count: 1,
+            values,
+            latency: None,
+            error: None,
+            description: None,
+            frame: None,
+        }
+    }
+
+    #[test]
+    fn read_request_frame() {
+        let request = record(Direction::Request, READ_HOLDING_REGISTERS, vec![]);
+        assert_eq!(
+            request.rtu_frame(),
+            Some(vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0a])
+        );
+    }
+
+    #[test]
+    fn read_response_frame() {
+        let response = record(Direction::Response, READ_INPUT_REGISTERS, vec![0x0102]);
+        let frame = response.rtu_frame().unwrap();
+        assert_eq!(&frame[..5], &[0x01, 0x04, 0x02, 0x01, 0x02]);
+        assert_eq!(crc16(&frame[..5]).to_le_bytes(), [frame[5], frame[6]]);
+    }
+
+    #[test]
+    fn failed_response_has_no_frame() {
+        let mut response = record(Direction::Response, READ_INPUT_REGISTERS, vec![]);
+        response.error = Some("Connection timed out".to_string());
+        assert_eq!(response.rtu_frame(), None);
+    }
+
+    #[test]
+    fn recorded_frame_wins() {
+        // Antwort mit falscher CRC, die Bytes sind trotzdem bekannt
+        let mut response = record(Direction::Response, READ_INPUT_REGISTERS, vec![]);
+        response.error = Some("Invalid CRC".to_string());
+        response.frame = Some(vec![0x01, 0x04, 0x02, 0x00, 0x23, 0x00, 0x00]);
+        assert_eq!(response.rtu_frame(), response.frame);
+    }
+
+    #[test]
+    fn recorder_disabled_by_default() {
+        let mut recorder = TrafficRecorder::default();
+        recorder.record(
+            Local::now(),
+            Duration::from_millis(5),
+            1,
+            READ_INPUT_REGISTERS,
+            2,
+            1,
+            &[],
+            Ok(&[35]),
+            None,
+        );
+        assert!(recorder.take().is_empty());
+
+        recorder.set_enabled(true);
+        recorder.record(
+            Local::now(),
+            Duration::from_millis(5),
+            1,
+            READ_INPUT_REGISTERS,
+            2,
+            1,
+            &[],
+            Ok(&[35]),
+            None,
+        );
+        let records = recorder.take();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].direction, Direction::Response);
+        assert_eq!(records[1].values, vec![35]);
+        assert!(recorder.take().is_empty());
+    }
+}
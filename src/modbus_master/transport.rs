@@ -0,0 +1,139 @@
+use crate::modbus_master::{
+    line_settings::TransmissionMode,
+    native::NativePort,
+    port_options::{Driver, Rts, SerialMode},
+    traffic::RawExchange,
+    Endpoint, LineSettings, ModbusMasterError, PortOptions,
+};
+use libmodbus::{prelude::Error as LibModbusError, Modbus, ModbusClient, Timeout};
+
+/// Übertragung über die der [`Bus`](super::Bus) die Anfragen sendet
+///
+/// Modbus TCP läuft über libmodbus, ASCII und RTU über TCP über den eigenen
+/// [`NativePort`]. Modbus RTU läuft über libmodbus, außer die [`PortOptions`]
+/// der Schnittstelle wählen `driver=native`. Alle Varianten bieten dieselben
+/// Funktionen an.
+pub enum Transport {
+    /// libmodbus Context (RTU oder TCP)
+    Libmodbus(Modbus),
+    /// Modbus RTU oder ASCII an einer seriellen Schnittstelle, oder RTU über
+    /// TCP, ohne libmodbus
+    Native(NativePort),
+}
+
+impl Transport {
+    /// Öffnet den Endpunkt
+    ///
+    /// Die Übertragungsart aus `line_settings` gilt nur für serielle
+    /// Schnittstellen, ebenso Betriebsart, RTS Steuerung und Treiber aus
+    /// `options`.
+    pub fn open(
+        endpoint: &Endpoint,
+        line_settings: &LineSettings,
+        options: &PortOptions,
+    ) -> Result<Self, ModbusMasterError> {
+        match endpoint {
+            Endpoint::Serial(tty_path)
+                if line_settings.transmission_mode == TransmissionMode::Ascii
+                    || options.driver == Driver::Native =>
+            {
+                if options.serial_mode != SerialMode::Rs232 || options.rts != Rts::None {
+                    warn!(
+                        "Transport: {} wird ohne libmodbus nicht unterstützt",
+                        options
+                    );
+                }
+                Ok(Transport::Native(NativePort::open(
+                    tty_path,
+                    line_settings,
+                )?))
+            }
+            Endpoint::RtuOverTcp { host, port } => {
+                Ok(Transport::Native(NativePort::connect(host, *port)?))
+            }
+            _ => {
+                let mut modbus = endpoint.open(line_settings)?;
+                // modbus.set_debug(true)?;
+                modbus.connect()?;
+                if endpoint.is_serial() {
+                    debug!("Bus options: {}", options);
+                    options.apply(&mut modbus)?;
+                }
+                Ok(Transport::Libmodbus(modbus))
+            }
+        }
+    }
+
+    /// Setzt die Modbus Slave ID für alle folgenden Anfragen
+    pub fn set_slave(&mut self, slave: u8) -> Result<(), LibModbusError> {
+        match self {
+            Transport::Libmodbus(modbus) => modbus.set_slave(slave),
+            Transport::Native(port) => port.set_slave(slave),
+        }
+    }
+
+    /// Setzt die Wartezeit auf eine Antwort
+    pub fn set_response_timeout(&mut self, timeout: Timeout) -> Result<(), LibModbusError> {
+        match self {
+            Transport::Libmodbus(modbus) => modbus.set_response_timeout(timeout),
+            Transport::Native(port) => port.set_response_timeout(timeout),
+        }
+    }
+
+    /// Liefert die Bytes der letzten Anfrage und ihrer Antwort
+    ///
+    /// libmodbus gibt die Bytes nicht heraus, dann `None`.
+    pub fn take_exchange(&mut self) -> Option<RawExchange> {
+        match self {
+            Transport::Libmodbus(_) => None,
+            Transport::Native(port) => port.take_exchange(),
+        }
+    }
+
+    /// Liest Holding Register (0x03)
+    pub fn read_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        match self {
+            Transport::Libmodbus(modbus) => modbus.read_registers(address, num, dest),
+            Transport::Native(port) => port.read_registers(address, num, dest),
+        }
+    }
+
+    /// Liest Input Register (0x04)
+    pub fn read_input_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, LibModbusError> {
+        match self {
+            Transport::Libmodbus(modbus) => modbus.read_input_registers(address, num, dest),
+            Transport::Native(port) => port.read_input_registers(address, num, dest),
+        }
+    }
+
+    /// Schreibt ein Register (0x06)
+    pub fn write_register(&mut self, address: u16, value: u16) -> Result<(), LibModbusError> {
+        match self {
+            Transport::Libmodbus(modbus) => modbus.write_register(address, value),
+            Transport::Native(port) => port.write_register(address, value),
+        }
+    }
+
+    /// Schreibt mehrere Register (0x10)
+    pub fn write_registers(
+        &mut self,
+        address: u16,
+        num: u16,
+        src: &[u16],
+    ) -> Result<u16, LibModbusError> {
+        match self {
+            Transport::Libmodbus(modbus) => modbus.write_registers(address, num, src),
+            Transport::Native(port) => port.write_registers(address, num, src),
+        }
+    }
+}
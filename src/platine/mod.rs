@@ -75,6 +75,24 @@ pub trait Platine {
     ///
     /// Liefert die Register Nummer mit dem die Platine entsperrt werden kann
     fn reg_protection(&self) -> u16;
+
+    /// Neustart Code
+    ///
+    /// Liefert den Wert der, in das Schreibschutz Register geschrieben, die
+    /// Platine neu startet. Alle bekannten Platinen verwenden
+    /// [`DEFAULT_RESTART_CODE`].
+    fn restart_code(&self) -> u16 {
+        DEFAULT_RESTART_CODE
+    }
+
+    /// Grunddaten Code
+    ///
+    /// Liefert den Wert der, in das Schreibschutz Register geschrieben, die
+    /// Grunddaten (Werkseinstellungen) lädt und die Platine neu startet. Alle
+    /// bekannten Platinen verwenden [`DEFAULT_FACTORY_RESET_CODE`].
+    fn factory_reset_code(&self) -> u16 {
+        DEFAULT_FACTORY_RESET_CODE
+    }
 }
 
 impl Debug for dyn Platine {
@@ -108,3 +126,129 @@ pub const HW_VERSIONS: &[(i32, &str, &str)] = &[
 
 /// Standard Schreibschutz Register
 pub const DEFAULT_REG_PROTECTION: u16 = 79;
+
+/// Standard Neustart Code
+pub const DEFAULT_RESTART_CODE: u16 = 10;
+
+/// Standard Grunddaten Code
+pub const DEFAULT_FACTORY_RESET_CODE: u16 = 20;
+
+/// Letzter Softwarestand der ersten NE4 Platine 'Sensor-MB-NE4-V1.0'
+pub const NE4_LEGACY_SOFTWARE_DATE: u16 = 25050;
+
+/// Erkennt die Platine anhand der Arbeitsweise (Rreg 1) und des Softwaredatums (Rreg 49)
+///
+/// Die Arbeitsweisen der Platinen liegen in getrennten Bereichen, z.B. 100 .. 199
+/// für 'Sensor-MB-NAP5x_REV1_0'. Die beiden NE4 Platinen teilen sich einen
+/// Bereich und werden über das Softwaredatum unterschieden.
+/// Unkonfigurierte Platinen (Arbeitsweise 0) können nicht erkannt werden.
+///
+/// Liefert den Namen der Platine aus [`HW_VERSIONS`].
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::platine::detect_platine;
+///
+/// assert_eq!(detect_platine(12, Some(2120)), Some("Sensor-MB-NE4_REV1_0"));
+/// assert_eq!(detect_platine(0, None), None);
+/// ```
+pub fn detect_platine(working_mode: u16, software_date: Option<u16>) -> Option<&'static str> {
+    let id = match working_mode {
+        1..=99 => match software_date {
+            Some(date)
+                if software_date_ordinal(date)
+                    <= software_date_ordinal(NE4_LEGACY_SOFTWARE_DATE) =>
+            {
+                0
+            }
+            _ => 1,
+        },
+        100..=199 => 3,
+        200..=299 => 5,
+        400..=499 => 2,
+        500..=599 => 4,
+        _ => return None,
+    };
+    HW_VERSIONS
+        .iter()
+        .find(|(hw_id, _name, _desc)| *hw_id == id)
+        .map(|(_id, name, _desc)| *name)
+}
+
+/// Erstellt eine Platine anhand ihres Namens aus [`HW_VERSIONS`]
+///
+/// Die Register werden aus den CSV Dateien gelesen.
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::platine::platine_from_name;
+///
+/// let platine = platine_from_name("Sensor-MB-NE4_REV1_0").unwrap();
+/// assert_eq!(platine.name(), "Sensor-MB-NE4_REV1_0");
+/// assert!(platine_from_name("Sensor-MB-XY").is_err());
+/// ```
+pub fn platine_from_name(name: &str) -> Result<Box<dyn Platine>, Box<dyn std::error::Error>> {
+    let platine: Box<dyn Platine> = match name {
+        "Sensor-MB-NE4-V1.0" => Box::new(SensorMbNe4Legacy::new_from_csv()?),
+        "Sensor-MB-NE4_REV1_0" => Box::new(SensorMbNe4::new_from_csv()?),
+        "Sensor-MB-NAP5xx_REV1_0" => Box::new(SensorMbNap5xx::new_from_csv()?),
+        "Sensor-MB-NAP5x_REV1_0" => Box::new(SensorMbNap5x::new_from_csv()?),
+        "Sensor-MB-CO2_O2_REV1_0" => Box::new(SensorMbCo2O2::new_from_csv()?),
+        "Sensor-MB-SP42A_REV1_0" => Box::new(SensorMbSp42a::new_from_csv()?),
+        _ => return Err(format!("Unbekannte Platine: {}", name).into()),
+    };
+    Ok(platine)
+}
+
+/// Formatiert das Softwaredatum (Rreg 49)
+///
+/// Das Datum ist als `TTMMJ` abgelegt, `J` ist das Jahr ab 2020.
+///
+/// # Examples
+/// ```rust
+/// use rgms_konfig::platine::format_software_date;
+///
+/// assert_eq!(format_software_date(2120), "02.12.2020");
+/// assert_eq!(format_software_date(31129), "31.12.2029");
+/// ```
+pub fn format_software_date(value: u16) -> String {
+    format!(
+        "{:02}.{:02}.{}",
+        value / 1000,
+        (value / 10) % 100,
+        2020 + value % 10
+    )
+}
+
+// Softwaredatum `TTMMJ` als sortierbare Zahl `JMMTT`
+fn software_date_ordinal(value: u16) -> u32 {
+    let value = u32::from(value);
+    (value % 10) * 10000 + ((value / 10) % 100) * 100 + value / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_platine_by_working_mode() {
+        assert_eq!(detect_platine(150, None), Some("Sensor-MB-NAP5x_REV1_0"));
+        assert_eq!(detect_platine(204, None), Some("Sensor-MB-SP42A_REV1_0"));
+        assert_eq!(detect_platine(430, None), Some("Sensor-MB-NAP5xx_REV1_0"));
+        assert_eq!(detect_platine(520, None), Some("Sensor-MB-CO2_O2_REV1_0"));
+        assert_eq!(detect_platine(0, Some(2120)), None);
+        assert_eq!(detect_platine(350, None), None);
+    }
+
+    #[test]
+    fn detect_platine_ne4_legacy() {
+        assert_eq!(detect_platine(10, Some(25050)), Some("Sensor-MB-NE4-V1.0"));
+        assert_eq!(detect_platine(10, Some(2120)), Some("Sensor-MB-NE4_REV1_0"));
+        assert_eq!(detect_platine(10, None), Some("Sensor-MB-NE4_REV1_0"));
+    }
+
+    #[test]
+    fn format_software_date_legacy() {
+        assert_eq!(format_software_date(25050), "25.05.2020");
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    modbus_master::PortOptions,
+    serial_interface::PortInfo,
+    settings::{AdapterAlias, AdapterOptions},
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Vergibt die Namen aus den Einstellungen an die Schnittstellen
+///
+/// Ein Name passt auf eine Schnittstelle wenn die USB Seriennummer oder der Pfad
+/// übereinstimmt. Pfade werden aufgelöst, so passt z.B. ein Pfad unter
+/// `/dev/serial/by-path` auf die `/dev/ttyUSBn` Schnittstelle auf die er zeigt.
+/// Passen mehrere Namen gilt der erste.
+pub fn apply_aliases(ports: &mut [PortInfo], aliases: &[AdapterAlias]) {
+    if aliases.is_empty() {
+        return;
+    }
+    for port in ports.iter_mut() {
+        let device = fs::canonicalize(&port.path).ok();
+        port.alias = aliases
+            .iter()
+            .find(|alias| adapter_matches(&alias.adapter, port, device.as_ref()))
+            .map(|alias| alias.name.clone());
+    }
+}
+
+/// Ordnet die Optionen aus den Einstellungen den Schnittstellen zu
+///
+/// Die Adapter werden wie bei [`apply_aliases`] erkannt, passen mehrere
+/// Einträge gilt der erste. Liefert die Optionen pro Pfad der Schnittstelle,
+/// Schnittstellen ohne Eintrag fehlen.
+pub fn port_options(
+    ports: &[PortInfo],
+    options: &[AdapterOptions],
+) -> HashMap<String, PortOptions> {
+    if options.is_empty() {
+        return HashMap::new();
+    }
+    ports
+        .iter()
+        .filter_map(|port| {
+            let device = fs::canonicalize(&port.path).ok();
+            options
+                .iter()
+                .find(|options| adapter_matches(&options.adapter, port, device.as_ref()))
+                .map(|options| (port.path.clone(), options.options))
+        })
+        .collect()
+}
+
+// Passt der Adapter (Seriennummer oder Pfad) auf die Schnittstelle? `device`
+// ist der aufgelöste Pfad der Schnittstelle.
+fn adapter_matches(adapter: &str, port: &PortInfo, device: Option<&PathBuf>) -> bool {
+    let serial_number = port
+        .usb
+        .as_ref()
+        .and_then(|usb| usb.serial_number.as_deref());
+    if serial_number == Some(adapter) || port.path == adapter {
+        return true;
+    }
+    match device {
+        Some(device) => fs::canonicalize(adapter)
+            .map(|adapter| &adapter == device)
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_interface::UsbInfo;
+
+    #[test]
+    fn alias_by_serial_number_or_path() {
+        let aliases = vec![
+            AdapterAlias {
+                adapter: "A12345".to_string(),
+                name: "Kammer 2".to_string(),
+            },
+            AdapterAlias {
+                adapter: "COM7".to_string(),
+                name: "Kammer 3".to_string(),
+            },
+        ];
+        let mut ports = vec![
+            PortInfo {
+                path: "/dev/ttyUSB3".to_string(),
+                usb: Some(UsbInfo {
+                    vid: 0x0403,
+                    pid: 0x6001,
+                    serial_number: Some("A12345".to_string()),
+                    manufacturer: None,
+                    product: None,
+                }),
+                alias: None,
+            },
+            PortInfo::new("COM7"),
+            PortInfo::new("COM8"),
+        ];
+        apply_aliases(&mut ports, &aliases);
+        assert_eq!(ports[0].to_string(), "Kammer 2 – /dev/ttyUSB3");
+        assert_eq!(ports[1].alias.as_deref(), Some("Kammer 3"));
+        assert_eq!(ports[2].alias, None);
+    }
+}
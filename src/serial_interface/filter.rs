@@ -0,0 +1,184 @@
+use crate::{serial_interface::PortInfo, settings::Settings};
+
+/// Filtert die Schnittstellen nach den Mustern aus den Einstellungen
+///
+/// Die Muster werden mit dem Pfad und der Anzeige der Schnittstelle verglichen,
+/// `*` steht für beliebig viele, `?` für genau ein Zeichen. So lässt sich z.B.
+/// mit `*Bluetooth*` auch unter Windows ein Bluetooth Adapter ausblenden.
+#[derive(Clone, Debug, Default)]
+pub struct PortFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl PortFilter {
+    /// Erstellt den Filter aus den Einstellungen
+    pub fn new(settings: &Settings) -> Self {
+        PortFilter {
+            include: settings.port_include.clone(),
+            exclude: settings.port_exclude.clone(),
+        }
+    }
+
+    /// Wird die Schnittstelle angezeigt?
+    pub fn accepts(&self, port: &PortInfo) -> bool {
+        let matches = |pattern: &String| {
+            wildcard_match(pattern, &port.path) || wildcard_match(pattern, &port.to_string())
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Wählt nach einer Änderung der Schnittstellen die anzuzeigende aus
+///
+/// Die bevorzugten Adapter (`favourites`, USB Seriennummern) werden über ihre
+/// Seriennummer gefunden, also auch wenn sich ihr Pfad geändert hat.
+///
+/// 1. ein neu hinzugekommener bevorzugter Adapter
+/// 2. die bisher gewählte Schnittstelle, solange sie ein bevorzugter Adapter ist
+/// 3. die zuletzt hinzugekommene Schnittstelle
+/// 4. die bisher gewählte Schnittstelle
+/// 5. der bevorzugte Adapter mit der höchsten Priorität
+///
+/// Liefert den Index in `ports`, ohne Schnittstellen `None`.
+pub fn select_port(
+    ports: &[PortInfo],
+    added: &[PortInfo],
+    active: Option<&PortInfo>,
+    favourites: &[String],
+) -> Option<usize> {
+    if ports.is_empty() {
+        return None;
+    }
+    let index_of = |wanted: &PortInfo| ports.iter().position(|port| port == wanted);
+    let favourite_rank = |port: &PortInfo| {
+        let serial_number = port.usb.as_ref()?.serial_number.as_ref()?;
+        favourites
+            .iter()
+            .position(|favourite| favourite == serial_number)
+    };
+    let best_favourite = |candidates: &[PortInfo]| {
+        candidates
+            .iter()
+            .filter_map(|port| favourite_rank(port).map(|rank| (rank, port)))
+            .min_by_key(|(rank, _port)| *rank)
+            .and_then(|(_rank, port)| index_of(port))
+    };
+    // Der bisher gewählte Adapter, gefunden über die Seriennummer oder den Pfad
+    let active_index = active.and_then(|active| {
+        let serial_number = active
+            .usb
+            .as_ref()
+            .and_then(|usb| usb.serial_number.as_ref());
+        ports
+            .iter()
+            .position(|port| {
+                serial_number.is_some()
+                    && port.usb.as_ref().and_then(|usb| usb.serial_number.as_ref()) == serial_number
+            })
+            .or_else(|| ports.iter().position(|port| port.path == active.path))
+    });
+
+    best_favourite(added)
+        .or_else(|| active_index.filter(|index| favourite_rank(&ports[*index]).is_some()))
+        .or_else(|| added.last().and_then(index_of))
+        .or(active_index)
+        .or_else(|| best_favourite(ports))
+        .or(Some(0))
+}
+
+// Vergleicht `text` mit einem Muster aus `*` und `?`
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Position nach dem letzten `*` im Muster und im Text
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Das letzte `*` verschluckt ein weiteres Zeichen
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_interface::UsbInfo;
+
+    fn usb(path: &str, serial_number: &str) -> PortInfo {
+        PortInfo {
+            path: path.to_string(),
+            usb: Some(UsbInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some(serial_number.to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: Some("USB-RS485".to_string()),
+            }),
+            alias: None,
+        }
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("/dev/ttyUSB*", "/dev/ttyUSB0"));
+        assert!(wildcard_match("*Bluetooth*", "Standard Bluetooth – COM4"));
+        assert!(wildcard_match("COM?", "COM3"));
+        assert!(!wildcard_match("COM?", "COM13"));
+        assert!(!wildcard_match("/dev/ttyUSB*", "/dev/ttyACM0"));
+    }
+
+    #[test]
+    fn filter_include_exclude() {
+        let filter = PortFilter::new(&Settings {
+            port_include: vec!["/dev/ttyUSB*".to_string(), "/dev/ttyACM*".to_string()],
+            port_exclude: vec!["*A12345*".to_string()],
+            ..Default::default()
+        });
+        assert!(filter.accepts(&usb("/dev/ttyUSB1", "B67890")));
+        assert!(!filter.accepts(&usb("/dev/ttyUSB0", "A12345")));
+        assert!(!filter.accepts(&PortInfo::new("/dev/rfcomm0")));
+    }
+
+    #[test]
+    fn favourite_is_followed_to_new_path() {
+        let favourites = vec!["A12345".to_string()];
+        let active = usb("/dev/ttyUSB0", "A12345");
+        // Nach dem erneuten Einstecken hat der Adapter einen anderen Pfad
+        let ports = vec![usb("/dev/ttyUSB0", "B67890"), usb("/dev/ttyUSB1", "A12345")];
+        let added = ports.clone();
+        assert_eq!(
+            select_port(&ports, &added, Some(&active), &favourites),
+            Some(1)
+        );
+        // Ein weiterer Adapter verdrängt den bevorzugten nicht
+        let more = vec![
+            ports[0].clone(),
+            ports[1].clone(),
+            PortInfo::new("/dev/ttyUSB2"),
+        ];
+        let added = vec![more[2].clone()];
+        assert_eq!(
+            select_port(&more, &added, Some(&ports[1]), &favourites),
+            Some(1)
+        );
+        // Ohne bevorzugte Adapter wird die neue Schnittstelle gewählt
+        assert_eq!(select_port(&more, &added, Some(&ports[0]), &[]), Some(2));
+        assert_eq!(select_port(&[], &[], None, &favourites), None);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Verzeichnisse der Lock Dateien, das erste beschreibbare wird verwendet. Ist
+// das Systemverzeichnis nicht beschreibbar, z.B. ohne Mitgliedschaft in der
+// Gruppe 'lock', sperren sich zumindest mehrere rgms_konfig gegenseitig.
+fn lock_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from("/var/lock"), std::env::temp_dir()]
+}
+
+/// Prozess der eine Schnittstelle belegt
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LockHolder {
+    /// Prozess ID aus der Lock Datei, `None` wenn sie nicht lesbar ist
+    pub pid: Option<u32>,
+    /// Name des Programms, soweit bekannt
+    pub program: Option<String>,
+}
+
+impl LockHolder {
+    /// Ist es dieser Prozess?
+    pub fn is_self(&self) -> bool {
+        self.pid == Some(std::process::id())
+    }
+}
+
+/// z.B. "minicom (PID 1234)"
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.program, self.pid) {
+            _ if self.is_self() => write!(f, "diesem Programm"),
+            (Some(program), Some(pid)) => write!(f, "{} (PID {})", program, pid),
+            (None, Some(pid)) => write!(f, "PID {}", pid),
+            (_, None) => write!(f, "einem unbekannten Programm"),
+        }
+    }
+}
+
+/// Exklusive Sperre einer seriellen Schnittstelle
+///
+/// Die Sperre ist eine UUCP Lock Datei (`/var/lock/LCK..ttyUSB0`) mit der
+/// Prozess ID, wie sie auch minicom, picocom oder ModemManager verwenden. Sie
+/// ist nur ein Hinweis an andere Programme, verhindert das Öffnen also nicht.
+/// Die Sperre gilt solange der `PortLock` lebt. Lock Dateien beendeter Prozesse
+/// werden übernommen. Unter Windows öffnet das System die Schnittstelle
+/// ohnehin exklusiv, dort wird keine Datei angelegt.
+#[derive(Debug)]
+pub struct PortLock {
+    path: Option<PathBuf>,
+}
+
+impl PortLock {
+    /// Sperrt die Schnittstelle `tty_path`
+    ///
+    /// Ist die Schnittstelle bereits gesperrt, auch von diesem Prozess, wird der
+    /// Inhaber geliefert. Lässt sich keine Lock Datei anlegen wird ohne Sperre
+    /// weiter gearbeitet.
+    pub fn acquire(tty_path: &str) -> Result<Self, LockHolder> {
+        let name = match lock_file_name(tty_path) {
+            Some(name) => name,
+            None => return Ok(PortLock { path: None }),
+        };
+        remove_stale(&lock_dirs(), &name);
+        if let Some(holder) = holder_in(&lock_dirs(), &name) {
+            return Err(holder);
+        }
+        for dir in lock_dirs() {
+            let path = dir.join(&name);
+            match create_lock_file(&path) {
+                Ok(()) => {
+                    debug!("Port lock: {}", path.display());
+                    return Ok(PortLock { path: Some(path) });
+                }
+                // Ein anderer Prozess war schneller
+                Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    return Err(read_holder(&path).unwrap_or(LockHolder {
+                        pid: None,
+                        program: None,
+                    }));
+                }
+                Err(error) => debug!("Port lock: {}: {}", path.display(), error),
+            }
+        }
+        warn!("Port lock: keine Lock Datei für {} möglich", tty_path);
+
+        Ok(PortLock { path: None })
+    }
+}
+
+impl Drop for PortLock {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            debug!("Port unlock: {}", path.display());
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Liefert den Inhaber der Sperre von `tty_path`, `None` wenn sie frei ist
+///
+/// Lock Dateien beendeter Prozesse gelten als frei, sie bleiben aber liegen.
+/// Entfernt werden sie erst von [`PortLock::acquire`].
+pub fn lock_holder(tty_path: &str) -> Option<LockHolder> {
+    holder_in(&lock_dirs(), &lock_file_name(tty_path)?)
+}
+
+// Name der Lock Datei, z.B. `LCK..ttyUSB0` für `/dev/ttyUSB0` und
+// `LCK..pts_3` für `/dev/pts/3`. Links wie `/dev/serial/by-id/...` werden
+// aufgelöst. Unter Windows und für Netzwerk Gateways `None`.
+fn lock_file_name(tty_path: &str) -> Option<String> {
+    if cfg!(windows) || tty_path.contains("://") {
+        return None;
+    }
+    let device = fs::canonicalize(tty_path).unwrap_or_else(|_| PathBuf::from(tty_path));
+    let name = match device.strip_prefix("/dev") {
+        Ok(relative) => relative.to_string_lossy().replace('/', "_"),
+        Err(_) => device.file_name()?.to_string_lossy().into_owned(),
+    };
+
+    Some(format!("LCK..{}", name))
+}
+
+fn holder_in(dirs: &[PathBuf], name: &str) -> Option<LockHolder> {
+    dirs.iter()
+        .filter_map(|dir| read_holder(&dir.join(name)))
+        .find(|holder| !is_stale(holder))
+}
+
+// Entfernt die Lock Dateien beendeter Prozesse
+fn remove_stale(dirs: &[PathBuf], name: &str) {
+    for dir in dirs {
+        let path = dir.join(name);
+        if read_holder(&path).map_or(false, |holder| is_stale(&holder)) {
+            info!("Port lock: entferne verwaiste {}", path.display());
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+// Ist der Prozess beendet und die Datei blieb übrig?
+fn is_stale(holder: &LockHolder) -> bool {
+    !holder.pid.map(is_running).unwrap_or(true)
+}
+
+// Legt die Lock Datei an, die Prozess ID steht nach UUCP Konvention als
+// zehnstellige Zahl in der Datei
+fn create_lock_file(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{:>10}", std::process::id())
+}
+
+fn read_holder(path: &Path) -> Option<LockHolder> {
+    let text = fs::read_to_string(path).ok()?;
+    let pid = parse_pid(&text);
+
+    Some(LockHolder {
+        pid,
+        program: pid.and_then(program_name),
+    })
+}
+
+fn parse_pid(text: &str) -> Option<u32> {
+    text.trim().parse().ok().filter(|pid| *pid > 0)
+}
+
+// Läuft der Prozess noch? Ohne `/proc` wird das angenommen.
+fn is_running(pid: u32) -> bool {
+    !Path::new("/proc/self").exists() || Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+fn program_name(pid: u32) -> Option<String> {
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(name.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_file_names() {
+        assert_eq!(
+            lock_file_name("/dev/ttyUSB-rgms-test").as_deref(),
+            Some("LCK..ttyUSB-rgms-test")
+        );
+        assert_eq!(parse_pid("      1234\n"), Some(1234));
+        assert_eq!(parse_pid("garbage"), None);
+    }
+
+    #[test]
+    fn stale_and_own_locks() {
+        let dir = std::env::temp_dir().join(format!("rgms_konfig-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dirs = vec![dir.clone()];
+        let name = "LCK..ttyTEST0";
+
+        create_lock_file(&dir.join(name)).unwrap();
+        let holder = holder_in(&dirs, name).unwrap();
+        assert!(holder.is_self());
+        assert_eq!(holder.to_string(), "diesem Programm");
+
+        // Lock Datei eines beendeten Prozesses, die Abfrage lässt sie liegen
+        fs::write(dir.join(name), format!("{:>10}\n", u32::max_value())).unwrap();
+        assert_eq!(holder_in(&dirs, name), None);
+        assert!(dir.join(name).exists());
+        remove_stale(&dirs, name);
+        assert!(!dir.join(name).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
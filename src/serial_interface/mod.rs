@@ -4,54 +4,122 @@
 //! Schnittstellen überprüft.
 //! Werden neue Schnittstellen gefunden oder werden Schnittstellen vom System
 //! entfernt dann sendet dieser Thread Nachrichten an die Gui.
-use crate::gui::gtk3::GuiMessage;
+//!
+//! Verglichen werden die Mengen der Schnittstellen mit den Angaben der USB
+//! Adapter, so fällt auch ein getauschter Adapter am selben Pfad auf.
+//! Welche Schnittstellen angezeigt werden bestimmt der [`PortFilter`] aus den
+//! Einstellungen, dort stehen auch die Namen der Adapter.
+//! Von Hand hinzugefügte Schnittstellen (Pseudo-Terminals, Netzwerk Gateways)
+//! werden nach den gefundenen angehängt und nicht gefiltert.
+//! Außerdem meldet der Thread welche Schnittstellen von anderen Programmen
+//! gesperrt sind ([`PortLock`]).
+use crate::{
+    gui::gtk3::GuiMessage,
+    settings::{AdapterAlias, Settings},
+};
 use futures::channel::mpsc;
 use futures::SinkExt;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::{runtime::Runtime, time::interval};
 
+mod alias;
+mod filter;
+mod lock;
+mod port;
+
+pub use alias::{apply_aliases, port_options};
+pub use filter::{select_port, PortFilter};
+pub use lock::{lock_holder, LockHolder, PortLock};
+pub use port::{diff, PortInfo, UsbInfo};
+
 /// Datenstruktur für den SerialInterface Thread
-pub struct SerialInterface {}
+pub struct SerialInterface {
+    endpoints: Arc<Mutex<Vec<String>>>,
+}
 
 impl SerialInterface {
     /// Erzeugt den SerialInterface Thread
-    pub fn new(gui_tx: mpsc::Sender<GuiMessage>) -> Self {
+    pub fn new(gui_tx: mpsc::Sender<GuiMessage>, settings: &Settings) -> Self {
+        let filter = PortFilter::new(settings);
+        let aliases = settings.adapter_aliases.clone();
+        let endpoints = Arc::new(Mutex::new(settings.endpoints.clone()));
+        let thread_endpoints = endpoints.clone();
         std::thread::spawn(move || {
             let mut rt = Runtime::new().expect("create tokio runtime");
 
             rt.block_on(async {
-                let mut ports: Vec<String> = vec![];
                 let mut interval = interval(Duration::from_millis(100));
 
-                let available_ports = get_ports();
+                let get_all_ports = || {
+                    let endpoints = thread_endpoints.lock().expect("lock endpoints").clone();
+                    get_ports(&filter, &aliases, &endpoints)
+                };
+                let mut ports = get_all_ports();
+                let mut busy = BTreeMap::new();
                 let _ = gui_tx
                     .clone()
-                    .send(GuiMessage::UpdateSerialPorts(available_ports.clone()))
+                    .send(GuiMessage::UpdateSerialPorts {
+                        ports: ports.clone(),
+                        added: ports.clone(),
+                        removed: vec![],
+                    })
                     .await;
 
                 loop {
-                    let available_ports = get_ports();
-                    // Hier könnte die Logic stehen für "neuen Port gefunden", "Port wurde entfernt"
-                    if available_ports.len() != ports.len() {
+                    let available_ports = get_all_ports();
+                    let (added, removed) = diff(&ports, &available_ports);
+                    for port in &added {
+                        info!("Schnittstelle hinzugefügt: {}", port);
+                    }
+                    for port in &removed {
+                        info!("Schnittstelle entfernt: {}", port);
+                    }
+                    if !added.is_empty() || !removed.is_empty() {
                         let _ = gui_tx
                             .clone()
-                            .send(GuiMessage::UpdateSerialPorts(available_ports.clone()))
+                            .send(GuiMessage::UpdateSerialPorts {
+                                ports: available_ports.clone(),
+                                added,
+                                removed,
+                            })
                             .await;
                     };
+                    let available_busy = busy_ports(&available_ports);
+                    if available_busy != busy {
+                        let _ = gui_tx
+                            .clone()
+                            .send(GuiMessage::UpdateBusyPorts(available_busy.clone()))
+                            .await;
+                    }
+                    busy = available_busy;
                     ports = available_ports;
                     interval.tick().await;
                 }
             });
         });
 
-        SerialInterface {}
+        SerialInterface { endpoints }
+    }
+
+    /// Die von Hand hinzugefügten Schnittstellen
+    pub fn endpoints(&self) -> Vec<String> {
+        self.endpoints.lock().expect("lock endpoints").clone()
+    }
+
+    /// Ersetzt die von Hand hinzugefügten Schnittstellen
+    ///
+    /// Die Gui erhält die Änderung mit der nächsten Prüfung der Schnittstellen.
+    pub fn set_endpoints(&self, endpoints: Vec<String>) {
+        *self.endpoints.lock().expect("lock endpoints") = endpoints;
     }
 }
 
 /// Liste der verfügbaren seriellen Schnittstellen
-pub fn list_ports() -> tokio_serial::Result<Vec<String>> {
+pub fn list_ports() -> tokio_serial::Result<Vec<PortInfo>> {
     match tokio_serial::available_ports() {
-        Ok(ports) => Ok(ports.into_iter().map(|x| x.port_name).collect()),
+        Ok(ports) => Ok(ports.into_iter().map(PortInfo::from).collect()),
         Err(e) => Err(e),
     }
 }
@@ -60,11 +128,39 @@ pub fn list_ports() -> tokio_serial::Result<Vec<String>> {
 ///
 /// Diese Funktion wird im Gui Thread aufgerufen.
 /// **Unter Linux wird die, nicht nutzbare, Schnittstelle `/dev/ttyS0` entfernt!**
-pub fn get_ports() -> Vec<String> {
+/// Außerdem erhalten die Schnittstellen ihre Namen aus `aliases` und werden
+/// nach `filter` gefiltert, die Muster passen also auch auf die Namen.
+/// Die `endpoints` werden ungefiltert angehängt, sofern sie nicht schon
+/// gefunden wurden.
+pub fn get_ports(
+    filter: &PortFilter,
+    aliases: &[AdapterAlias],
+    endpoints: &[String],
+) -> Vec<PortInfo> {
     let mut ports = list_ports().expect("Scanning for ports should never fail");
     ports.sort();
+    apply_aliases(&mut ports, aliases);
     // Remove unwanted ports under linux
-    ports.retain(|p| p != "/dev/ttyS0");
+    ports.retain(|p| p.path != "/dev/ttyS0" && filter.accepts(p));
+
+    let mut manual: Vec<PortInfo> = endpoints
+        .iter()
+        .filter(|endpoint| !ports.iter().any(|port| &port.path == *endpoint))
+        .map(|endpoint| PortInfo::new(endpoint))
+        .collect();
+    apply_aliases(&mut manual, aliases);
+    ports.extend(manual);
 
     ports
 }
+
+/// Von anderen Programmen gesperrte Schnittstellen mit dem Inhaber der Sperre
+///
+/// Die Sperren dieses Programms werden nicht gemeldet.
+pub fn busy_ports(ports: &[PortInfo]) -> BTreeMap<String, LockHolder> {
+    ports
+        .iter()
+        .filter_map(|port| lock_holder(&port.path).map(|holder| (port.path.clone(), holder)))
+        .filter(|(_, holder)| !holder.is_self())
+        .collect()
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+use tokio_serial::{SerialPortInfo, SerialPortType};
+
+/// Eine serielle Schnittstelle mit den Angaben des USB Adapters
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PortInfo {
+    /// Pfad der Schnittstelle, z.B. `/dev/ttyUSB0` oder `COM3`
+    pub path: String,
+    /// Angaben des USB Adapters, `None` bei anderen Schnittstellen
+    pub usb: Option<UsbInfo>,
+    /// Name des Adapters aus den Einstellungen
+    pub alias: Option<String>,
+}
+
+/// Angaben eines USB Adapters
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct UsbInfo {
+    /// Vendor ID
+    pub vid: u16,
+    /// Product ID
+    pub pid: u16,
+    /// Seriennummer
+    pub serial_number: Option<String>,
+    /// Hersteller
+    pub manufacturer: Option<String>,
+    /// Produktbezeichnung
+    pub product: Option<String>,
+}
+
+impl PortInfo {
+    /// Schnittstelle ohne weitere Angaben
+    pub fn new(path: &str) -> Self {
+        PortInfo {
+            path: path.to_string(),
+            usb: None,
+            alias: None,
+        }
+    }
+}
+
+impl From<SerialPortInfo> for PortInfo {
+    fn from(info: SerialPortInfo) -> Self {
+        let usb = match info.port_type {
+            SerialPortType::UsbPort(usb) => Some(UsbInfo {
+                vid: usb.vid,
+                pid: usb.pid,
+                serial_number: usb.serial_number,
+                manufacturer: usb.manufacturer,
+                product: usb.product,
+            }),
+            _ => None,
+        };
+        PortInfo {
+            path: info.port_name,
+            usb,
+            alias: None,
+        }
+    }
+}
+
+/// Anzeige im Auswahlfeld, z.B. "FTDI USB-RS485 (A12345) – /dev/ttyUSB0"
+///
+/// Hat der Adapter einen Namen ersetzt dieser die Angaben des Adapters, z.B.
+/// "Kammer 2 – /dev/ttyUSB0".
+impl fmt::Display for PortInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.alias, &self.usb) {
+            (Some(alias), _) => write!(f, "{} – {}", alias, self.path),
+            (None, Some(usb)) => write!(f, "{} – {}", usb, self.path),
+            (None, None) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Hersteller, Produkt und Seriennummer, ohne diese Angaben Vendor und Product ID
+impl fmt::Display for UsbInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name: Vec<&str> = [self.manufacturer.as_deref(), self.product.as_deref()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        if name.is_empty() {
+            write!(f, "USB {:04x}:{:04x}", self.vid, self.pid)?;
+        } else {
+            write!(f, "{}", name.join(" "))?;
+        }
+        if let Some(serial_number) = &self.serial_number {
+            write!(f, " ({})", serial_number)?;
+        }
+        Ok(())
+    }
+}
+
+/// Neu hinzugekommene und entfernte Schnittstellen
+///
+/// Verglichen werden Pfad und Adapter. Wird ein Adapter gegen einen anderen
+/// getauscht der denselben Pfad erhält, ist der alte entfernt und der neue
+/// hinzugekommen.
+pub fn diff(old: &[PortInfo], new: &[PortInfo]) -> (Vec<PortInfo>, Vec<PortInfo>) {
+    let added = new
+        .iter()
+        .filter(|port| !old.contains(port))
+        .cloned()
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|port| !new.contains(port))
+        .cloned()
+        .collect();
+
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ftdi(path: &str, serial_number: &str) -> PortInfo {
+        PortInfo {
+            path: path.to_string(),
+            usb: Some(UsbInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some(serial_number.to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: Some("USB-RS485".to_string()),
+            }),
+            alias: None,
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            ftdi("/dev/ttyUSB0", "A12345").to_string(),
+            "FTDI USB-RS485 (A12345) – /dev/ttyUSB0"
+        );
+        assert_eq!(PortInfo::new("COM1").to_string(), "COM1");
+    }
+
+    #[test]
+    fn diff_detects_swapped_adapter() {
+        let old = vec![PortInfo::new("/dev/ttyS1"), ftdi("/dev/ttyUSB0", "A12345")];
+        let new = vec![PortInfo::new("/dev/ttyS1"), ftdi("/dev/ttyUSB0", "B67890")];
+        let (added, removed) = diff(&old, &new);
+        assert_eq!(added, vec![ftdi("/dev/ttyUSB0", "B67890")]);
+        assert_eq!(removed, vec![ftdi("/dev/ttyUSB0", "A12345")]);
+        assert_eq!(diff(&new, &new), (vec![], vec![]));
+    }
+}
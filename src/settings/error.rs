@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::{fmt, io};
+
+/// Settings Error
+#[derive(Debug)]
+pub enum SettingsError {
+    /// IO Fehler
+    IoError(io::Error),
+    /// Fehlerhafte Zeile in der Einstellungsdatei
+    ParseError {
+        /// Zeilennummer, beginnend bei 1
+        line: usize,
+        /// Beschreibung des Fehlers
+        message: String,
+    },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SettingsError::IoError(ref e) => write!(f, "IO Error: {}", e),
+            SettingsError::ParseError { line, ref message } => {
+                write!(f, "Fehler in Zeile {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(error: io::Error) -> Self {
+        SettingsError::IoError(error)
+    }
+}
+
+impl Error for SettingsError {}
@@ -0,0 +1,320 @@
+//! Einstellungen des Benutzers
+//!
+//! Die Einstellungen stehen in der Datei `rgms_konfig/settings.conf` im
+//! Konfigurationsverzeichnis des Benutzers, unter Linux z.B.
+//! `~/.config/rgms_konfig/settings.conf`. Jede Zeile hat die Form
+//! `schlüssel = wert`, Schlüssel dürfen mehrfach vorkommen. Leere Zeilen und
+//! Zeilen die mit `#` beginnen werden ignoriert.
+//!
+//! ```text
+//! # Nur USB Adapter anzeigen, Bluetooth ausblenden
+//! port_include = /dev/ttyUSB*
+//! port_exclude = *Bluetooth*
+//! # Bevorzugte Adapter (USB Seriennummer)
+//! favourite_adapter = A12345
+//! # Namen für Adapter (USB Seriennummer oder Pfad => Name)
+//! adapter_alias = A12345 => Kammer 2
+//! adapter_alias = /dev/serial/by-path/pci-0000:00:14.0-usb-0:2:1.0-port0 => Kammer 3
+//! # Weitere Schnittstellen, z.B. eines Simulators oder Gateways
+//! endpoint = /dev/pts/3
+//! endpoint = tcp://192.168.1.10:502
+//! # Richtungsumschaltung, Pausen und Treiber (USB Seriennummer oder Pfad => Optionen)
+//! port_options = A12345 => mode=rs485 rts=up rts_delay=1000
+//! port_options = /dev/ttyUSB1 => frame_gap=20 driver=native
+//! ```
+//!
+//! Die Schnittstellen (`endpoint`) werden auch in der Gui hinzugefügt und
+//! entfernt, die übrigen Zeilen der Datei bleiben dabei erhalten.
+use crate::modbus_master::PortOptions;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+mod error;
+
+// Reexports
+pub use error::SettingsError;
+
+/// Einstellungen des Benutzers
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Settings {
+    /// Nur Schnittstellen die auf eines dieser Muster passen werden angezeigt,
+    /// leer zeigt alle an (`port_include`)
+    pub port_include: Vec<String>,
+    /// Schnittstellen die auf eines dieser Muster passen werden ausgeblendet
+    /// (`port_exclude`)
+    pub port_exclude: Vec<String>,
+    /// USB Seriennummern der bevorzugten Adapter, in absteigender Priorität
+    /// (`favourite_adapter`)
+    pub favourite_adapters: Vec<String>,
+    /// Namen der Adapter (`adapter_alias`)
+    pub adapter_aliases: Vec<AdapterAlias>,
+    /// Von Hand hinzugefügte Schnittstellen, z.B. Pseudo-Terminals oder Modbus
+    /// TCP Gateways (`endpoint`)
+    pub endpoints: Vec<String>,
+    /// Richtungsumschaltung, Pausen und Treiber der Adapter (`port_options`)
+    pub port_options: Vec<AdapterOptions>,
+}
+
+/// Name eines Adapters
+///
+/// Der Adapter wird über seine USB Seriennummer oder einen Pfad erkannt. Ein
+/// Pfad unter `/dev/serial/by-path` benennt den USB Anschluss, unabhängig vom
+/// eingesteckten Adapter und der Nummerierung `/dev/ttyUSBn`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdapterAlias {
+    /// USB Seriennummer oder Pfad
+    pub adapter: String,
+    /// Name des Adapters, z.B. "Kammer 2"
+    pub name: String,
+}
+
+/// Optionen eines Adapters
+///
+/// Der Adapter wird wie bei [`AdapterAlias`] über seine USB Seriennummer oder
+/// einen Pfad erkannt.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdapterOptions {
+    /// USB Seriennummer oder Pfad
+    pub adapter: String,
+    /// Optionen der Schnittstelle
+    pub options: PortOptions,
+}
+
+impl Settings {
+    /// Pfad der Einstellungsdatei
+    ///
+    /// Ist kein Konfigurationsverzeichnis des Benutzers bekannt, liegt die
+    /// Datei im temporären Verzeichnis.
+    pub fn path() -> PathBuf {
+        glib::get_user_config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("rgms_konfig")
+            .join("settings.conf")
+    }
+
+    /// Liest die Einstellungsdatei
+    ///
+    /// Fehlt die Datei gelten die Standardeinstellungen. Eine fehlerhafte Datei
+    /// wird gemeldet und ebenfalls durch die Standardeinstellungen ersetzt.
+    pub fn load() -> Self {
+        let path = Self::path();
+        match Self::from_file(&path) {
+            Ok(settings) => {
+                debug!("Settings loaded from {}: {:?}", path.display(), settings);
+                settings
+            }
+            Err(SettingsError::IoError(ref error)) if error.kind() == io::ErrorKind::NotFound => {
+                Settings::default()
+            }
+            Err(error) => {
+                warn!("Settings: {}: {}", path.display(), error);
+                Settings::default()
+            }
+        }
+    }
+
+    /// Liest die Einstellungen aus der Datei `path`
+    pub fn from_file(path: &std::path::Path) -> Result<Self, SettingsError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    /// Liest die Einstellungen aus dem Text einer Einstellungsdatei
+    ///
+    /// # Examples
+    /// ```rust
+    /// use rgms_konfig::settings::Settings;
+    ///
+    /// let settings = Settings::parse("favourite_adapter = A12345").unwrap();
+    /// assert_eq!(settings.favourite_adapters, vec!["A12345"]);
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, SettingsError> {
+        let mut settings = Settings::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => {
+                    return Err(SettingsError::ParseError {
+                        line: index + 1,
+                        message: format!("'=' fehlt: {}", line),
+                    })
+                }
+            };
+            if value.is_empty() {
+                return Err(SettingsError::ParseError {
+                    line: index + 1,
+                    message: format!("Wert fehlt: {}", key),
+                });
+            }
+            if key == "adapter_alias" || key == "port_options" {
+                let (adapter, rest) = match value.find("=>") {
+                    Some(pos) => (value[..pos].trim().to_string(), value[pos + 2..].trim()),
+                    None => {
+                        return Err(SettingsError::ParseError {
+                            line: index + 1,
+                            message: format!("'=>' fehlt: {}", value),
+                        })
+                    }
+                };
+                if key == "adapter_alias" {
+                    settings.adapter_aliases.push(AdapterAlias {
+                        adapter,
+                        name: rest.to_string(),
+                    });
+                } else {
+                    let options =
+                        PortOptions::parse(rest).map_err(|message| SettingsError::ParseError {
+                            line: index + 1,
+                            message,
+                        })?;
+                    settings
+                        .port_options
+                        .push(AdapterOptions { adapter, options });
+                }
+                continue;
+            }
+            let value = value.to_string();
+            match key {
+                "port_include" => settings.port_include.push(value),
+                "port_exclude" => settings.port_exclude.push(value),
+                "favourite_adapter" => settings.favourite_adapters.push(value),
+                "endpoint" => settings.endpoints.push(value),
+                // Unbekannte Schlüssel stammen eventuell von einer neueren Version
+                _ => warn!(
+                    "Settings: unbekannter Schlüssel in Zeile {}: {}",
+                    index + 1,
+                    key
+                ),
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Fügt der Einstellungsdatei eine Schnittstelle hinzu
+    pub fn add_endpoint(endpoint: &str) -> Result<(), SettingsError> {
+        Self::edit(|text| add_line(text, ENDPOINT, endpoint))
+    }
+
+    /// Entfernt eine Schnittstelle aus der Einstellungsdatei
+    pub fn remove_endpoint(endpoint: &str) -> Result<(), SettingsError> {
+        Self::edit(|text| remove_line(text, ENDPOINT, endpoint))
+    }
+
+    // Ändert den Text der Einstellungsdatei, fehlt die Datei wird sie angelegt
+    fn edit(change: impl FnOnce(&str) -> String) -> Result<(), SettingsError> {
+        let path = Self::path();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, change(&text))?;
+        debug!("Settings saved to {}", path.display());
+
+        Ok(())
+    }
+}
+
+const ENDPOINT: &str = "endpoint";
+
+// Hängt die Zeile `key = value` an, sofern es sie noch nicht gibt
+fn add_line(text: &str, key: &str, value: &str) -> String {
+    let mut text = text.to_string();
+    if text.lines().any(|line| is_line(line, key, value)) {
+        return text;
+    }
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&format!("{} = {}\n", key, value));
+
+    text
+}
+
+// Entfernt alle Zeilen `key = value`
+fn remove_line(text: &str, key: &str, value: &str) -> String {
+    text.lines()
+        .filter(|line| !is_line(line, key, value))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn is_line(line: &str, key: &str, value: &str) -> bool {
+    match line.find('=') {
+        Some(pos) => line[..pos].trim() == key && line[pos + 1..].trim() == value,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let text = "
+            # Kommentar
+            port_include = /dev/ttyUSB*
+            port_exclude = *Bluetooth*
+            favourite_adapter = A12345
+            favourite_adapter=B67890
+            adapter_alias = /dev/serial/by-path/pci-0000:00:14.0-usb-0:2:1.0-port0 => Kammer 3
+        ";
+        let settings = Settings::parse(text).unwrap();
+        assert_eq!(settings.port_include, vec!["/dev/ttyUSB*"]);
+        assert_eq!(settings.port_exclude, vec!["*Bluetooth*"]);
+        assert_eq!(settings.favourite_adapters, vec!["A12345", "B67890"]);
+        assert_eq!(
+            settings.adapter_aliases,
+            vec![AdapterAlias {
+                adapter: "/dev/serial/by-path/pci-0000:00:14.0-usb-0:2:1.0-port0".to_string(),
+                name: "Kammer 3".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_port_options() {
+        let settings = Settings::parse("port_options = A12345 => rts=up frame_gap=20").unwrap();
+        assert_eq!(settings.port_options[0].adapter, "A12345");
+        assert_eq!(
+            settings.port_options[0].options.to_string(),
+            "rts=up frame_gap=20"
+        );
+        match Settings::parse("port_options = A12345 => rts=sideways") {
+            Err(SettingsError::ParseError { line, .. }) => assert_eq!(line, 1),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn add_and_remove_endpoint() {
+        let text = "# Kommentar\nport_include = /dev/ttyUSB*";
+        let text = add_line(text, ENDPOINT, "tcp://gateway:502");
+        assert_eq!(add_line(&text, ENDPOINT, "tcp://gateway:502"), text);
+        let settings = Settings::parse(&text).unwrap();
+        assert_eq!(settings.endpoints, vec!["tcp://gateway:502"]);
+        assert_eq!(
+            remove_line(&text, ENDPOINT, "tcp://gateway:502"),
+            "# Kommentar\nport_include = /dev/ttyUSB*\n"
+        );
+    }
+
+    #[test]
+    fn parse_error_names_line() {
+        match Settings::parse("\nport_include /dev/ttyUSB*") {
+            Err(SettingsError::ParseError { line, .. }) => assert_eq!(line, 2),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Dekodiert mitgehörte Frames
+//!
+//! Der Decoder merkt sich pro Slave die letzte Anfrage, so bekommen die
+//! Antworten ihre Register Nummern und Laufzeiten. Die Register werden mit den
+//! Register Listen der [`Platine`] beschrieben. Liest der Master die
+//! Arbeitsweise (Rreg 1) und das Softwaredatum (Rreg 49) mit, wird die Platine
+//! des Slaves erkannt, sonst gilt die in der GUI gewählte Platine.
+use crate::{
+    modbus_master::traffic::{
+        Direction, TrafficRecord, READ_HOLDING_REGISTERS, READ_INPUT_REGISTERS,
+        WRITE_MULTIPLE_REGISTERS, WRITE_SINGLE_REGISTER,
+    },
+    platine::{self, Platine},
+    sniffer::frame::{Frame, Pdu},
+};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+
+// Arbeitsweise
+const RREG_WORKING_MODE: u16 = 1;
+// Softwaredatum
+const RREG_SOFTWARE_DATE: u16 = 49;
+
+/// Dekodiert Frames zu Einträgen des Mitschnitts
+#[derive(Default)]
+pub struct Decoder {
+    // Name der in der GUI gewählten Platine
+    default_platine: Option<String>,
+    // Geladene Platinen nach Name, `None` wenn die Platine nicht geladen werden konnte
+    platines: HashMap<String, Option<Box<dyn Platine>>>,
+    slaves: HashMap<u8, SlaveState>,
+}
+
+// Was über einen Slave bekannt ist
+#[derive(Debug, Default)]
+struct SlaveState {
+    pending: Option<Request>,
+    working_mode: Option<u16>,
+    software_date: Option<u16>,
+}
+
+// Anfrage auf die noch keine Antwort kam
+#[derive(Clone, Debug)]
+struct Request {
+    timestamp: DateTime<Local>,
+    function: u8,
+    address: u16,
+    count: u16,
+    values: Vec<u16>,
+}
+
+impl Decoder {
+    /// Erstellt einen Decoder
+    ///
+    /// `default_platine` ist der Name der Platine aus
+    /// [`HW_VERSIONS`](crate::platine::HW_VERSIONS) die verwendet wird solange
+    /// die Platine eines Slaves nicht erkannt wurde.
+    pub fn new(default_platine: Option<String>) -> Self {
+        Decoder {
+            default_platine,
+            ..Default::default()
+        }
+    }
+
+    /// Dekodiert einen Frame
+    pub fn decode(&mut self, frame: &Frame, timestamp: DateTime<Local>) -> TrafficRecord {
+        let slave = frame.slave();
+        let pdu = match frame.pdu() {
+            Some(pdu) => pdu,
+            None => return self.crc_error(frame, timestamp),
+        };
+
+        let mut record = TrafficRecord {
+            timestamp,
+            direction: Direction::Request,
+            slave,
+            function: frame.bytes.get(1).copied().unwrap_or_default(),
+            address: 0,
+            count: 0,
+            values: vec![],
+            latency: None,
+            error: None,
+            description: None,
+            frame: Some(frame.bytes.clone()),
+        };
+
+        let state = self.slaves.entry(slave).or_default();
+        let text = match pdu {
+            Pdu::ReadRequest {
+                function,
+                address,
+                count,
+            } => {
+                record.address = address;
+                record.count = count;
+                state.pending = Some(Request {
+                    timestamp,
+                    function,
+                    address,
+                    count,
+                    values: vec![],
+                });
+                format!("liest {} {}", register_kind(function), record.registers())
+            }
+            Pdu::ReadResponse { function, values } => {
+                record.direction = Direction::Response;
+                record.values = values.clone();
+                match take_pending(state, function) {
+                    Some(request) => {
+                        record.address = request.address;
+                        record.count = request.count;
+                        record.latency = (timestamp - request.timestamp).to_std().ok();
+                        if function == READ_INPUT_REGISTERS {
+                            state.remember(request.address, &values);
+                        }
+                        let registers = numbered(request.address, &values);
+                        return self.describe(record, function, "liest", &registers);
+                    }
+                    None => format!("antwortet {}", record.values()),
+                }
+            }
+            Pdu::WriteSingle { address, value } => {
+                record.address = address;
+                record.count = 1;
+                record.values = vec![value];
+                let echo = state.pending.as_ref().map_or(false, |request| {
+                    request.function == WRITE_SINGLE_REGISTER
+                        && request.address == address
+                        && request.values == [value]
+                });
+                if echo {
+                    let request = state.pending.take();
+                    record.direction = Direction::Response;
+                    record.latency =
+                        request.and_then(|request| (timestamp - request.timestamp).to_std().ok());
+                    return self.describe(
+                        record,
+                        WRITE_SINGLE_REGISTER,
+                        "bestätigt",
+                        &[(address, value)],
+                    );
+                }
+                state.pending = Some(Request {
+                    timestamp,
+                    function: WRITE_SINGLE_REGISTER,
+                    address,
+                    count: 1,
+                    values: vec![value],
+                });
+                return self.describe(
+                    record,
+                    WRITE_SINGLE_REGISTER,
+                    "schreibt",
+                    &[(address, value)],
+                );
+            }
+            Pdu::WriteMultipleRequest { address, values } => {
+                record.address = address;
+                record.count = values.len() as u16;
+                record.values = values.clone();
+                state.pending = Some(Request {
+                    timestamp,
+                    function: WRITE_MULTIPLE_REGISTERS,
+                    address,
+                    count: record.count,
+                    values: values.clone(),
+                });
+                let registers = numbered(address, &values);
+                return self.describe(record, WRITE_MULTIPLE_REGISTERS, "schreibt", &registers);
+            }
+            Pdu::WriteMultipleResponse { address, count } => {
+                record.direction = Direction::Response;
+                record.address = address;
+                record.count = count;
+                if let Some(request) = take_pending(state, WRITE_MULTIPLE_REGISTERS) {
+                    record.latency = (timestamp - request.timestamp).to_std().ok();
+                }
+                format!(
+                    "bestätigt {} {}",
+                    register_kind(WRITE_MULTIPLE_REGISTERS),
+                    record.registers()
+                )
+            }
+            Pdu::Exception { function, code } => {
+                record.direction = Direction::Response;
+                if let Some(request) = take_pending(state, function) {
+                    record.address = request.address;
+                    record.count = request.count;
+                    record.latency = (timestamp - request.timestamp).to_std().ok();
+                }
+                let error = format!("Exception 0x{:02X} ({})", code, exception_name(code));
+                record.error = Some(error.clone());
+                format!("antwortet mit {}", error)
+            }
+            Pdu::Unknown { function } => {
+                format!("Funktion 0x{:02X} (nicht unterstützt)", function)
+            }
+        };
+        record.description = Some(format!("{} {}", slave_name(slave), text));
+        record
+    }
+
+    // Beschreibt die Register mit der Platine des Slaves
+    fn describe(
+        &mut self,
+        mut record: TrafficRecord,
+        function: u8,
+        verb: &str,
+        registers: &[(u16, u16)],
+    ) -> TrafficRecord {
+        let kind = register_kind(function);
+        let platine = self.platine(record.slave);
+        let registers = registers
+            .iter()
+            .map(|(reg_nr, value)| {
+                let description = platine
+                    .and_then(|platine| register_description(platine, function, *reg_nr))
+                    .map(|description| format!(" ({})", description))
+                    .unwrap_or_default();
+                format!("{} {} = {}{}", kind, reg_nr, value, description)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        record.description = Some(format!(
+            "{} {} {}",
+            slave_name(record.slave),
+            verb,
+            registers
+        ));
+        record
+    }
+
+    // Platine des Slaves, erkannt oder in der GUI gewählt
+    fn platine(&mut self, slave: u8) -> Option<&dyn Platine> {
+        let detected = self
+            .slaves
+            .get(&slave)
+            .and_then(|state| platine::detect_platine(state.working_mode?, state.software_date));
+        let name = detected
+            .map(ToString::to_string)
+            .or_else(|| self.default_platine.clone())?;
+        if !self.platines.contains_key(&name) {
+            let platine = match platine::platine_from_name(&name) {
+                Ok(platine) => Some(platine),
+                Err(error) => {
+                    warn!("Sniffer: Platine {} nicht geladen: {}", name, error);
+                    None
+                }
+            };
+            self.platines.insert(name.clone(), platine);
+        }
+        self.platines.get(&name)?.as_deref()
+    }
+
+    // Frame mit falscher CRC
+    fn crc_error(&mut self, frame: &Frame, timestamp: DateTime<Local>) -> TrafficRecord {
+        let slave = frame.slave();
+        // Wartet der Slave auf eine Antwort, ist es vermutlich die Antwort
+        let direction = match self
+            .slaves
+            .get_mut(&slave)
+            .and_then(|state| state.pending.take())
+        {
+            Some(_) => Direction::Response,
+            None => Direction::Request,
+        };
+        TrafficRecord {
+            timestamp,
+            direction,
+            slave,
+            function: frame.bytes.get(1).copied().unwrap_or_default(),
+            address: 0,
+            count: 0,
+            values: vec![],
+            latency: None,
+            error: Some("CRC Fehler".to_string()),
+            description: Some(format!(
+                "{}: CRC Fehler ({})",
+                slave_name(slave),
+                frame
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )),
+            // Bei ASCII sind die Bytes Hex Zeichen und kein RTU Frame
+            frame: None,
+        }
+    }
+}
+
+impl SlaveState {
+    // Merkt sich Arbeitsweise und Softwaredatum für die Erkennung der Platine
+    fn remember(&mut self, address: u16, values: &[u16]) {
+        for (reg_nr, value) in numbered(address, values) {
+            match reg_nr {
+                RREG_WORKING_MODE => self.working_mode = Some(value),
+                RREG_SOFTWARE_DATE => self.software_date = Some(value),
+                _ => {}
+            }
+        }
+    }
+}
+
+// Ordnet den Werten ihre Register Nummern zu
+fn numbered(address: u16, values: &[u16]) -> Vec<(u16, u16)> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| (address.wrapping_add(i as u16), *value))
+        .collect()
+}
+
+// Nimmt die offene Anfrage, wenn die Antwort zur Funktion passt
+fn take_pending(state: &mut SlaveState, function: u8) -> Option<Request> {
+    match state.pending {
+        Some(ref request) if request.function == function => state.pending.take(),
+        _ => None,
+    }
+}
+
+// Input Register sind die Lese-Register, alle anderen die Schreib.-/ Lese-Register
+fn register_kind(function: u8) -> &'static str {
+    match function {
+        READ_INPUT_REGISTERS => "Rreg",
+        READ_HOLDING_REGISTERS | WRITE_SINGLE_REGISTER | WRITE_MULTIPLE_REGISTERS => "Rwreg",
+        _ => "Register",
+    }
+}
+
+// Slave ID 0 ist der Broadcast
+fn slave_name(slave: u8) -> String {
+    match slave {
+        0 => "Broadcast".to_string(),
+        slave => format!("Slave {}", slave),
+    }
+}
+
+// Beschreibung des Registers ohne Markierung des Schreibschutzes
+fn register_description(platine: &dyn Platine, function: u8, reg_nr: u16) -> Option<String> {
+    let description = if function == READ_INPUT_REGISTERS {
+        platine
+            .rregs()
+            .iter()
+            .find(|rreg| rreg.reg_nr() == u32::from(reg_nr))?
+            .description()
+    } else {
+        platine
+            .rwregs()
+            .iter()
+            .find(|rwreg| rwreg.reg_nr() == u32::from(reg_nr))?
+            .description()
+    };
+    let description = description
+        .trim_end_matches(|c: char| c == '*' || c.is_whitespace())
+        .to_string();
+    if description.is_empty() {
+        None
+    } else {
+        Some(description)
+    }
+}
+
+// Bezeichnung der Modbus Exception Codes
+fn exception_name(code: u8) -> &'static str {
+    match code {
+        0x01 => "Illegal Function",
+        0x02 => "Illegal Data Address",
+        0x03 => "Illegal Data Value",
+        0x04 => "Slave Device Failure",
+        0x05 => "Acknowledge",
+        0x06 => "Slave Device Busy",
+        _ => "unbekannt",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus_master::rtu::crc16;
+
+    fn frame(data: &[u8]) -> Frame {
+        let mut bytes = data.to_vec();
+        bytes.extend_from_slice(&crc16(data).to_le_bytes());
+        Frame {
+            bytes,
+            crc_ok: true,
+        }
+    }
+
+    #[test]
+    fn read_request_and_response() {
+        let mut decoder = Decoder::new(None);
+        let request = decoder.decode(&frame(&[12, 0x04, 0x00, 0x02, 0x00, 0x01]), Local::now());
+        assert_eq!(request.direction, Direction::Request);
+        assert_eq!(
+            request.description.as_deref(),
+            Some("Slave 12 liest Rreg 2")
+        );
+
+        let response = decoder.decode(&frame(&[12, 0x04, 0x02, 0x00, 0x23]), Local::now());
+        assert_eq!(response.direction, Direction::Response);
+        assert_eq!(response.address, 2);
+        assert_eq!(response.values, vec![35]);
+        assert!(response.latency.is_some());
+        assert_eq!(
+            response.description.as_deref(),
+            Some("Slave 12 liest Rreg 2 = 35")
+        );
+    }
+
+    #[test]
+    fn write_single_echo_is_response() {
+        let mut decoder = Decoder::new(None);
+        let data = [1, 0x06, 0x00, 0x4f, 0x26, 0x94];
+        let request = decoder.decode(&frame(&data), Local::now());
+        let response = decoder.decode(&frame(&data), Local::now());
+        assert_eq!(request.direction, Direction::Request);
+        assert_eq!(
+            request.description.as_deref(),
+            Some("Slave 1 schreibt Rwreg 79 = 9876")
+        );
+        assert_eq!(response.direction, Direction::Response);
+    }
+
+    #[test]
+    fn exception_and_crc_error() {
+        let mut decoder = Decoder::new(None);
+        decoder.decode(&frame(&[1, 0x03, 0x00, 0xc8, 0x00, 0x01]), Local::now());
+        let exception = decoder.decode(&frame(&[1, 0x83, 0x02]), Local::now());
+        assert_eq!(exception.address, 200);
+        assert_eq!(
+            exception.error.as_deref(),
+            Some("Exception 0x02 (Illegal Data Address)")
+        );
+
+        let broken = Frame {
+            bytes: vec![1, 0x04, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00],
+            crc_ok: false,
+        };
+        let record = decoder.decode(&broken, Local::now());
+        assert_eq!(record.error.as_deref(), Some("CRC Fehler"));
+        assert_eq!(record.rtu_frame(), None);
+    }
+
+    #[test]
+    fn remember_working_mode() {
+        let mut state = SlaveState::default();
+        state.remember(0, &[0, 12, 35]);
+        assert_eq!(state.working_mode, Some(12));
+        assert_eq!(state.software_date, None);
+    }
+}
@@ -0,0 +1,314 @@
+//! Modbus RTU Frames
+//!
+//! Der Sniffer trennt die Frames zuerst an den Pausen zwischen den Zeichen
+//! ([`frame_gap`]).
+//! USB Adapter liefern die Bytes aber gepuffert, dann kommen Anfrage und
+//! Antwort in einem Stück. Deshalb wird jedes Stück zusätzlich anhand der
+//! erwarteten Länge und der CRC in einzelne Frames zerlegt.
+//!
+//! Modbus ASCII Frames werden an Start- und Endezeichen getrennt und in RTU
+//! Frames umgewandelt, so dekodiert der Sniffer beide Übertragungsarten gleich.
+use crate::modbus_master::{
+    ascii,
+    rtu::{self, crc_ok, expected_lengths, MAX_FRAME_LEN, MIN_FRAME_LEN},
+    traffic::{
+        READ_HOLDING_REGISTERS, READ_INPUT_REGISTERS, WRITE_MULTIPLE_REGISTERS,
+        WRITE_SINGLE_REGISTER,
+    },
+};
+
+pub use crate::modbus_master::rtu::frame_gap;
+
+/// Ein empfangener Frame
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// Bytes inklusive CRC
+    pub bytes: Vec<u8>,
+    /// Stimmt die CRC?
+    pub crc_ok: bool,
+}
+
+impl Frame {
+    /// Modbus Slave ID
+    pub fn slave(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// Dekodiert den Inhalt, nur bei korrekter CRC
+    pub fn pdu(&self) -> Option<Pdu> {
+        if !self.crc_ok {
+            return None;
+        }
+        Pdu::parse(&self.bytes[1..self.bytes.len() - 2])
+    }
+}
+
+/// Inhalt eines Frames (Protocol Data Unit)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Pdu {
+    /// Anfrage Register lesen (0x03, 0x04)
+    ReadRequest {
+        /// Modbus Funktion
+        function: u8,
+        /// Erstes Register
+        address: u16,
+        /// Anzahl Register
+        count: u16,
+    },
+    /// Antwort mit gelesenen Registern (0x03, 0x04)
+    ReadResponse {
+        /// Modbus Funktion
+        function: u8,
+        /// Gelesene Werte
+        values: Vec<u16>,
+    },
+    /// Ein Register schreiben (0x06), Anfrage und Antwort sind gleich
+    WriteSingle {
+        /// Register
+        address: u16,
+        /// Wert
+        value: u16,
+    },
+    /// Anfrage mehrere Register schreiben (0x10)
+    WriteMultipleRequest {
+        /// Erstes Register
+        address: u16,
+        /// Werte
+        values: Vec<u16>,
+    },
+    /// Antwort mehrere Register geschrieben (0x10)
+    WriteMultipleResponse {
+        /// Erstes Register
+        address: u16,
+        /// Anzahl Register
+        count: u16,
+    },
+    /// Fehlerantwort
+    Exception {
+        /// Modbus Funktion der Anfrage
+        function: u8,
+        /// Exception Code
+        code: u8,
+    },
+    /// Nicht unterstützte Funktion
+    Unknown {
+        /// Modbus Funktion
+        function: u8,
+    },
+}
+
+impl Pdu {
+    /// Dekodiert Funktion und Daten (ohne Slave ID und CRC)
+    pub fn parse(pdu: &[u8]) -> Option<Pdu> {
+        let (&function, data) = pdu.split_first()?;
+        let pdu = match function {
+            _ if function & 0x80 != 0 => Pdu::Exception {
+                function: function & 0x7f,
+                code: *data.first()?,
+            },
+            // Die Anfrage hat immer 4 Bytes, die Antwort eine ungerade Anzahl
+            READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS if data.len() == 4 => Pdu::ReadRequest {
+                function,
+                address: u16_at(data, 0)?,
+                count: u16_at(data, 2)?,
+            },
+            READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => Pdu::ReadResponse {
+                function,
+                values: words(data.get(1..1 + usize::from(*data.first()?))?),
+            },
+            WRITE_SINGLE_REGISTER => Pdu::WriteSingle {
+                address: u16_at(data, 0)?,
+                value: u16_at(data, 2)?,
+            },
+            WRITE_MULTIPLE_REGISTERS if data.len() == 4 => Pdu::WriteMultipleResponse {
+                address: u16_at(data, 0)?,
+                count: u16_at(data, 2)?,
+            },
+            WRITE_MULTIPLE_REGISTERS => Pdu::WriteMultipleRequest {
+                address: u16_at(data, 0)?,
+                values: words(data.get(5..5 + usize::from(*data.get(4)?))?),
+            },
+            _ => Pdu::Unknown { function },
+        };
+        Some(pdu)
+    }
+}
+
+/// Zerlegt die zwischen zwei Pausen empfangenen Bytes in Frames
+///
+/// Passt die CRC bei keiner möglichen Länge, werden die restlichen Bytes als
+/// ein Frame mit falscher CRC geliefert.
+pub fn split_frames(mut bytes: &[u8]) -> Vec<Frame> {
+    let mut frames = vec![];
+    while !bytes.is_empty() {
+        let len = expected_lengths(bytes)
+            .into_iter()
+            .chain(MIN_FRAME_LEN..=bytes.len().min(MAX_FRAME_LEN))
+            .find(|len| *len <= bytes.len() && crc_ok(&bytes[..*len]));
+        match len {
+            Some(len) => {
+                frames.push(Frame {
+                    bytes: bytes[..len].to_vec(),
+                    crc_ok: true,
+                });
+                bytes = &bytes[len..];
+            }
+            None => {
+                frames.push(Frame {
+                    bytes: bytes.to_vec(),
+                    crc_ok: false,
+                });
+                break;
+            }
+        }
+    }
+    frames
+}
+
+/// Zerlegt empfangene Modbus ASCII Zeichen in Frames
+///
+/// Zeichen vor dem ersten ':' werden übersprungen, ein unvollständiger Frame
+/// am Ende wird als Frame mit falscher CRC geliefert.
+pub fn split_ascii_frames(bytes: &[u8]) -> Vec<Frame> {
+    let mut frames = vec![];
+    let mut frame = vec![];
+    for byte in bytes {
+        if ascii::push(&mut frame, *byte) {
+            frames.push(from_ascii(&frame));
+            frame.clear();
+        }
+    }
+    if !frame.is_empty() {
+        frames.push(Frame {
+            bytes: frame,
+            crc_ok: false,
+        });
+    }
+    frames
+}
+
+// Wandelt einen vollständigen ASCII Frame in einen RTU Frame, statt der LRC
+// wird die CRC angehängt
+fn from_ascii(frame: &[u8]) -> Frame {
+    match ascii::decode(frame) {
+        Some(mut bytes) if bytes.len() >= 3 => {
+            let crc_ok = ascii::lrc(&bytes) == 0;
+            bytes.pop();
+            Frame {
+                bytes: rtu::encode(bytes[0], &bytes[1..]),
+                crc_ok,
+            }
+        }
+        _ => Frame {
+            bytes: frame.to_vec(),
+            crc_ok: false,
+        },
+    }
+}
+
+// Liest einen Wert High Byte zuerst
+fn u16_at(data: &[u8], index: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *data.get(index)?,
+        *data.get(index + 1)?,
+    ]))
+}
+
+// Wandelt Bytes in Register Werte
+fn words(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Slave 1 liest Input Register 2
+    const REQUEST: &[u8] = &[0x01, 0x04, 0x00, 0x02, 0x00, 0x01, 0x90, 0x0a];
+
+    #[test]
+    fn split_single_frame() {
+        let frames = split_frames(REQUEST);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].crc_ok);
+        assert_eq!(
+            frames[0].pdu(),
+            Some(Pdu::ReadRequest {
+                function: 0x04,
+                address: 2,
+                count: 1
+            })
+        );
+    }
+
+    #[test]
+    fn split_request_and_response() {
+        let response = rtu::encode(0x01, &[0x04, 0x02, 0x00, 0x23]);
+        let mut bytes = REQUEST.to_vec();
+        bytes.extend_from_slice(&response);
+
+        let frames = split_frames(&bytes);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            frames[1].pdu(),
+            Some(Pdu::ReadResponse {
+                function: 0x04,
+                values: vec![35]
+            })
+        );
+    }
+
+    #[test]
+    fn crc_error() {
+        let frames = split_frames(&[0x01, 0x04, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(frames.len(), 1);
+        assert!(!frames[0].crc_ok);
+        assert_eq!(frames[0].pdu(), None);
+    }
+
+    #[test]
+    fn parse_exception_and_writes() {
+        assert_eq!(
+            Pdu::parse(&[0x84, 0x02]),
+            Some(Pdu::Exception {
+                function: 0x04,
+                code: 0x02
+            })
+        );
+        assert_eq!(
+            Pdu::parse(&[0x06, 0x00, 0x4f, 0x26, 0x94]),
+            Some(Pdu::WriteSingle {
+                address: 79,
+                value: 9876
+            })
+        );
+        assert_eq!(
+            Pdu::parse(&[0x10, 0x00, 0x0a, 0x00, 0x02, 0x04, 0x00, 0x01, 0x00, 0x02]),
+            Some(Pdu::WriteMultipleRequest {
+                address: 10,
+                values: vec![1, 2]
+            })
+        );
+    }
+
+    #[test]
+    fn split_ascii() {
+        let mut bytes = ascii::encode(1, &[0x04, 0x00, 0x02, 0x00, 0x01]);
+        bytes.extend_from_slice(b":0104020023D6\r\n:0104");
+
+        let frames = split_ascii_frames(&bytes);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].bytes, REQUEST.to_vec());
+        assert!(frames[1].crc_ok);
+        assert_eq!(
+            frames[1].pdu(),
+            Some(Pdu::ReadResponse {
+                function: 0x04,
+                values: vec![35]
+            })
+        );
+        assert!(!frames[2].crc_ok);
+    }
+}
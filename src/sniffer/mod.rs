@@ -0,0 +1,191 @@
+//! Passives Mithören am RS485 Bus
+//!
+//! Der Sniffer öffnet die Schnittstelle nur lesend und sendet selbst nichts.
+//! Die empfangenen Bytes werden an den Pausen zwischen den Zeichen zu Frames
+//! zusammengesetzt, die CRC geprüft und mit den Register Listen der Platinen
+//! dekodiert, z.B. "Slave 12 liest Rreg 2 = 35 (Gaskonzentration im ppm (für CO))".
+//! Bei Modbus ASCII werden die Frames an Start- und Endezeichen getrennt.
+//!
+//! libmodbus kann nicht passiv mithören, der Sniffer läuft deshalb in einem
+//! eigenen Thread neben dem Modbus Master. Beide dürfen die Schnittstelle nicht
+//! gleichzeitig verwenden, der [`PortLock`] der Schnittstelle verhindert das.
+use crate::{
+    modbus_master::{ascii, LineSettings, ModbusMasterError, TrafficRecord, TransmissionMode},
+    serial_interface::PortLock,
+};
+use chrono::Local;
+use futures::channel::mpsc::UnboundedSender;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tokio::{io::AsyncReadExt, runtime::Runtime, time};
+use tokio_serial::{DataBits, FlowControl, Parity, Serial, SerialPortSettings, StopBits};
+
+pub mod decoder;
+pub mod frame;
+
+pub use decoder::Decoder;
+pub use frame::{frame_gap, split_ascii_frames, split_frames, Frame, Pdu};
+
+/// Ereignisse des Sniffers
+#[derive(Clone, Debug)]
+pub enum SnifferEvent {
+    /// Ein dekodierter Frame
+    Record(TrafficRecord),
+    /// Die Schnittstelle konnte nicht geöffnet oder gelesen werden, danach
+    /// folgt `Stopped`
+    Failed(String),
+    /// Der Sniffer Thread ist beendet
+    Stopped,
+}
+
+/// Der Sniffer Thread
+///
+/// Der Thread läuft bis [`Sniffer::stop`] aufgerufen oder der `Sniffer`
+/// verworfen wird.
+#[derive(Debug)]
+pub struct Sniffer {
+    running: Arc<AtomicBool>,
+}
+
+impl Sniffer {
+    /// Startet den Sniffer auf der Schnittstelle `tty_path`
+    ///
+    /// `platine` ist der Name der Platine die für die Beschreibung der Register
+    /// verwendet wird, solange die Platine eines Slaves nicht erkannt wurde.
+    pub fn start(
+        tty_path: &str,
+        line_settings: LineSettings,
+        platine: Option<String>,
+        events: UnboundedSender<SnifferEvent>,
+    ) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let tty_path = tty_path.to_string();
+
+        std::thread::spawn({
+            let running = running.clone();
+            move || {
+                let mut rt = Runtime::new().expect("create tokio runtime");
+                let result = rt.block_on(listen(
+                    &tty_path,
+                    line_settings,
+                    Decoder::new(platine),
+                    &running,
+                    &events,
+                ));
+                if let Err(error) = result {
+                    let _ = events.unbounded_send(SnifferEvent::Failed(error));
+                }
+                let _ = events.unbounded_send(SnifferEvent::Stopped);
+            }
+        });
+
+        Sniffer { running }
+    }
+
+    /// Beendet den Sniffer
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Sniffer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Liest bis der Sniffer beendet wird
+//
+// Die Bytes werden gesammelt bis für die Dauer von 3,5 Zeichen nichts mehr
+// kommt, dann werden sie in Frames zerlegt und dekodiert.
+async fn listen(
+    tty_path: &str,
+    line_settings: LineSettings,
+    mut decoder: Decoder,
+    running: &AtomicBool,
+    events: &UnboundedSender<SnifferEvent>,
+) -> Result<(), String> {
+    let _port_lock = PortLock::acquire(tty_path).map_err(|holder| {
+        ModbusMasterError::PortBusy {
+            tty_path: tty_path.to_string(),
+            holder,
+        }
+        .to_string()
+    })?;
+    let mut port = Serial::from_path(tty_path, &serial_port_settings(&line_settings))
+        .map_err(|error| format!("{}: {}", tty_path, error))?;
+    debug!("Sniffer: {} ({})", tty_path, line_settings);
+
+    let gap = frame_gap(line_settings.baud_rate);
+    let mut received = vec![];
+    let mut buf = [0u8; 256];
+    while running.load(Ordering::SeqCst) {
+        match time::timeout(gap, port.read(&mut buf)).await {
+            Ok(Ok(0)) => return Err(format!("{}: Schnittstelle geschlossen", tty_path)),
+            Ok(Ok(n)) => received.extend_from_slice(&buf[..n]),
+            Ok(Err(error)) => return Err(format!("{}: {}", tty_path, error)),
+            // Pause, der Frame ist vollständig
+            Err(_) => {
+                if received.is_empty() {
+                    continue;
+                }
+                let timestamp = Local::now();
+                let frames = match line_settings.transmission_mode {
+                    TransmissionMode::Rtu => {
+                        let frames = split_frames(&received);
+                        received.clear();
+                        frames
+                    }
+                    // Pausen innerhalb eines ASCII Frames sind erlaubt, ein
+                    // unvollständiger Frame wird bis zur nächsten Pause aufgehoben
+                    TransmissionMode::Ascii => {
+                        let end = received
+                            .windows(2)
+                            .rposition(|pair| pair == b"\r\n")
+                            .map_or(0, |pos| pos + 2);
+                        let frames = split_ascii_frames(&received[..end]);
+                        received.drain(..end);
+                        if received.len() > ascii::MAX_FRAME_LEN {
+                            received.clear();
+                        }
+                        frames
+                    }
+                };
+                for frame in frames {
+                    let record = decoder.decode(&frame, timestamp);
+                    if events.unbounded_send(SnifferEvent::Record(record)).is_err() {
+                        // Niemand hört mehr zu
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parameter der Schnittstelle für tokio-serial
+pub fn serial_port_settings(line_settings: &LineSettings) -> SerialPortSettings {
+    SerialPortSettings {
+        baud_rate: line_settings.baud_rate as u32,
+        data_bits: match line_settings.data_bits {
+            5 => DataBits::Five,
+            6 => DataBits::Six,
+            7 => DataBits::Seven,
+            _ => DataBits::Eight,
+        },
+        flow_control: FlowControl::None,
+        parity: match line_settings.parity {
+            'E' => Parity::Even,
+            'O' => Parity::Odd,
+            _ => Parity::None,
+        },
+        stop_bits: match line_settings.stop_bits {
+            2 => StopBits::Two,
+            _ => StopBits::One,
+        },
+        ..Default::default()
+    }
+}